@@ -0,0 +1,43 @@
+//! Instruction execution benchmark. Runs the programs in `roms/` at a high speed and
+//! reports the average time per instruction. Run it with and without the `threaded`
+//! feature to compare the execution engines:
+//!
+//! ```text
+//! cargo bench --bench cpu
+//! cargo bench --bench cpu --features threaded
+//! ```
+
+use std::{fs, hint::black_box, time::Instant};
+
+use oxid_8::Chip8Core;
+use oxid_8::input::KeypadState;
+use oxid_8::options::CoreOptions;
+
+const ROMS: [&str; 3] = ["heart_monitor.ch8", "test_opcode.ch8", "tetris.ch8"];
+const FRAMES: u32 = 2_000;
+const INSTRUCTIONS_PER_FRAME: u32 = 1_000;
+
+fn bench(rom: &str) {
+    let data = fs::read(format!("{}/roms/{}", env!("CARGO_MANIFEST_DIR"), rom)).unwrap();
+    let mut core = Chip8Core::with_options(CoreOptions::from_args(&[format!("ipf={}", INSTRUCTIONS_PER_FRAME)]));
+    core.set_seed(0);
+    core.load_program(&data);
+
+    let start = Instant::now();
+    for _ in 0..FRAMES {
+        black_box(core.run_frame(KeypadState::default()));
+    }
+    let elapsed = start.elapsed();
+
+    let instructions = core.counters().instructions;
+    println!("{:<24} {:>10} instructions {:>8.1} ns/instruction", rom, instructions, elapsed.as_nanos() as f64 / instructions as f64);
+}
+
+fn main() {
+    let engine = if cfg!(feature = "threaded") { "threaded" } else { "interpreter" };
+    println!("engine: {}", engine);
+
+    for rom in ROMS {
+        bench(rom);
+    }
+}
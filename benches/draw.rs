@@ -0,0 +1,52 @@
+//! Sprite drawing benchmark. Runs programs that draw continuously and reports the
+//! average time per `DXYN` instruction.
+//!
+//! ```text
+//! cargo bench --bench draw
+//! ```
+
+use std::{hint::black_box, time::Instant};
+
+use oxid_8::Chip8Core;
+use oxid_8::input::KeypadState;
+use oxid_8::options::CoreOptions;
+
+const FRAMES: u32 = 2_000;
+const INSTRUCTIONS_PER_FRAME: u32 = 1_000;
+
+/// Program that draws a sprite with the given draw instruction in an endless loop,
+/// moving it diagonally so that it wraps around and gets clipped by the screen edges.
+fn program(mode: u8, draw: [u8; 2]) -> Vec<u8> {
+    let mut program = vec![
+        0x00, mode,     // LORES / HIRES
+        0xA2, 0x10,     // LD I, 0x210
+        draw[0], draw[1],
+        0x70, 0x05,     // ADD V0, 5
+        0x71, 0x03,     // ADD V1, 3
+        0x12, 0x04,     // JP 0x204
+        0x00, 0x00, 0x00, 0x00,
+    ];
+    program.extend((0..32).map(|i| if i % 2 == 0 { 0xF0 } else { 0x5A }));
+    program
+}
+
+fn bench(name: &str, program: &[u8]) {
+    let args = ["platform=schip".to_string(), format!("ipf={}", INSTRUCTIONS_PER_FRAME)];
+    let mut core = Chip8Core::with_options(CoreOptions::from_args(&args));
+    core.load_program(program);
+
+    let start = Instant::now();
+    for _ in 0..FRAMES {
+        black_box(core.run_frame(KeypadState::default()));
+    }
+    let elapsed = start.elapsed();
+
+    let draws = core.counters().draws;
+    println!("{:<24} {:>10} draws {:>10.1} ns/draw", name, draws, elapsed.as_nanos() as f64 / draws as f64);
+}
+
+fn main() {
+    bench("lores 8x15", &program(0xFE, [0xD0, 0x1F]));
+    bench("hires 8x15", &program(0xFF, [0xD0, 0x1F]));
+    bench("hires 16x16", &program(0xFF, [0xD0, 0x10]));
+}
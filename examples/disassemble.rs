@@ -0,0 +1,35 @@
+//! Print the instructions of a program.
+//!
+//! ```text
+//! cargo run --example disassemble -- [rom] [--format json]
+//! ```
+//!
+//! Disassembles `roms/ibm.ch8` by default. With `--format json`, the instructions, labels,
+//! data regions and sprite candidates found by `analyze` are printed as JSON instead.
+
+use std::{env, fs};
+
+use oxid_8::cpu::disassembler::{analyze, disassemble};
+
+fn main() {
+    let mut rom = None;
+    let mut json = false;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => json = args.next().as_deref() == Some("json"),
+            _ => rom = Some(arg),
+        }
+    }
+
+    let rom = rom.unwrap_or_else(|| format!("{}/roms/ibm.ch8", env!("CARGO_MANIFEST_DIR")));
+    let data = fs::read(&rom).expect("cannot read ROM");
+
+    if json {
+        println!("{}", analyze(&data).to_json());
+    }
+    else {
+        disassemble(&data);
+    }
+}
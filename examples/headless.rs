@@ -0,0 +1,77 @@
+//! Run a program without a frontend and print the screen, optionally writing the audio
+//! to a WAV file.
+//!
+//! ```text
+//! cargo run --example headless -- [rom] [frames] [wav]
+//! ```
+//!
+//! Runs `roms/ibm.ch8` for 60 frames by default.
+
+use std::{env, fs, io};
+
+use oxid_8::Chip8Core;
+use oxid_8::input::KeypadState;
+use oxid_8::video::TextStyle;
+
+/// Write interleaved 16-bit stereo samples as a WAV file.
+fn write_wav(path: &str, samples: &[i16]) -> io::Result<()> {
+    let rate = Chip8Core::SAMPLE_RATE as u32;
+    let size = 2 * samples.len() as u32;
+
+    let mut data = Vec::with_capacity(44 + size as usize);
+    data.extend_from_slice(b"RIFF");
+    data.extend_from_slice(&(36 + size).to_le_bytes());
+    data.extend_from_slice(b"WAVEfmt ");
+    data.extend_from_slice(&16u32.to_le_bytes());
+    data.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    data.extend_from_slice(&2u16.to_le_bytes()); // Stereo
+    data.extend_from_slice(&rate.to_le_bytes());
+    data.extend_from_slice(&(4 * rate).to_le_bytes());
+    data.extend_from_slice(&4u16.to_le_bytes());
+    data.extend_from_slice(&16u16.to_le_bytes());
+    data.extend_from_slice(b"data");
+    data.extend_from_slice(&size.to_le_bytes());
+    samples.iter().for_each(|s| data.extend_from_slice(&s.to_le_bytes()));
+
+    fs::write(path, data)
+}
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let rom = args.next().unwrap_or_else(|| format!("{}/roms/ibm.ch8", env!("CARGO_MANIFEST_DIR")));
+    let frames: u32 = args.next().and_then(|v| v.parse().ok()).unwrap_or(60);
+    let wav = args.next();
+
+    let mut core = Chip8Core::new();
+    core.set_seed(0);
+    core.load_program(&fs::read(&rom).expect("cannot read ROM"));
+    core.set_timing(true);
+
+    let mut samples = Vec::new();
+    for _ in 0..frames {
+        let output = core.run_frame(KeypadState::default());
+
+        // Frames without sound produce no samples, so fill them with silence
+        if output.audio.is_empty() {
+            samples.resize(samples.len() + Chip8Core::AUDIO_FRAME_SIZE, 0);
+        }
+        else {
+            samples.extend_from_slice(output.audio);
+        }
+    }
+
+    if let Some(wav) = wav {
+        write_wav(&wav, &samples).expect("cannot write WAV file");
+    }
+
+    print!("{}", core.screen_text(TextStyle::Braille));
+
+    let counters = core.counters();
+    println!("{} frames, {} instructions, {} draws", counters.frames, counters.instructions, counters.draws);
+
+    if let Some(timings) = core.timings().filter(|t| t.frames > 0) {
+        println!("per frame: {:?} executing, {:?} rendering",
+            timings.execute / timings.frames as u32, timings.render / timings.frames as u32);
+    }
+    println!("screen hash: {:016X}", core.framebuffer_hash());
+}
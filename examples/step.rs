@@ -0,0 +1,42 @@
+//! Execute a program one instruction at a time, printing the registers after each step,
+//! as a debugger would.
+//!
+//! ```text
+//! cargo run --example step -- [rom] [steps]
+//! ```
+//!
+//! Steps through the first 20 instructions of `roms/ibm.ch8` by default.
+
+use std::{env, fs};
+
+use oxid_8::Chip8Core;
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let rom = args.next().unwrap_or_else(|| format!("{}/roms/ibm.ch8", env!("CARGO_MANIFEST_DIR")));
+    let steps: u32 = args.next().and_then(|v| v.parse().ok()).unwrap_or(20);
+
+    let mut core = Chip8Core::new();
+    core.set_seed(0);
+    core.load_program(&fs::read(&rom).expect("cannot read ROM"));
+
+    for _ in 0..steps {
+        let memory = core.dump_memory().memory;
+        let pc = core.pc() as usize;
+        let opcode = u16::from_be_bytes([memory[pc], memory[pc + 1]]);
+
+        core.execute_instruction();
+
+        let registers: Vec<String> = (0..16).map(|x| format!("{:02X}", core.register(x))).collect();
+        println!("0x{:03X}  {:04X}  I={:03X}  DT={:02X}  ST={:02X}  V={}",
+            pc, opcode, core.i_register(), core.delay_timer(), core.sound_timer(), registers.join(" "));
+
+        if let Some(x) = core.key_wait_register() {
+            println!("waiting for a key to store in V{:X}", x);
+            break;
+        }
+        if core.status().halted {
+            break;
+        }
+    }
+}
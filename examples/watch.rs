@@ -0,0 +1,61 @@
+//! Run a program in the terminal and reload it whenever its file changes, for a quick
+//! edit-compile-run loop while developing a program.
+//!
+//! ```text
+//! cargo run --example watch -- <rom> [--keep-screen] [options...]
+//! ```
+//!
+//! The screen is printed twice per second. RPL flags are kept across reloads, and with
+//! `--keep-screen`, the new version starts over the last frame of the previous one.
+//! Other arguments are parsed as core options (e.g. `platform=schip`).
+
+use std::{env, fs, thread, time::{Instant, SystemTime}};
+
+use oxid_8::Chip8Core;
+use oxid_8::clock::Clock;
+use oxid_8::input::KeypadState;
+use oxid_8::options::CoreOptions;
+use oxid_8::video::TextStyle;
+
+fn modified(path: &str) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let Some(rom) = args.iter().find(|a| !a.starts_with("--") && !a.contains('=')) else {
+        eprintln!("usage: watch <rom> [--keep-screen] [options...]");
+        return;
+    };
+    let keep_screen = args.iter().any(|a| a == "--keep-screen");
+
+    let mut core = Chip8Core::with_options(CoreOptions::from_args(&args));
+    core.load_program(&fs::read(rom).expect("cannot read ROM"));
+    let mut last_modified = modified(rom);
+    let mut clock = Clock::new();
+    let mut frame = 0u64;
+
+    loop {
+        for _ in 0..clock.tick(Instant::now()) {
+            if frame.is_multiple_of(30) {
+                let current = modified(rom);
+                if current != last_modified {
+                    last_modified = current;
+                    // The file may be read while it is being written, in which case it is
+                    // reloaded once it changes again
+                    if let Ok(data) = fs::read(rom) {
+                        core.reload_rom(&data, keep_screen);
+                    }
+                }
+
+                // Clear the terminal before printing the screen
+                print!("\x1B[2J\x1B[H{}", core.screen_text(TextStyle::Braille));
+            }
+
+            core.run_frame(KeypadState::default());
+            frame += 1;
+        }
+
+        thread::sleep(clock.until_next_frame());
+    }
+}
@@ -0,0 +1,163 @@
+use std::sync::{Arc, atomic::{AtomicI16, AtomicU64, AtomicUsize, Ordering}};
+
+/// Buffer shared by the two ends of an audio ring. Positions only ever increase (wrapping
+/// around `usize`), so the number of buffered samples is `write - read`.
+struct Ring {
+    samples: Box<[AtomicI16]>,
+    read: AtomicUsize,
+    write: AtomicUsize,
+    overruns: AtomicU64,
+    underruns: AtomicU64,
+}
+
+/// Create a lock-free ring buffer of `capacity` samples (interleaved stereo), for
+/// frontends whose audio API pulls samples from a callback on its own thread (such as
+/// cpal or WebAudio). The core pushes each frame's samples into the producer (see
+/// `Chip8Core::set_audio_output`) and the audio callback pulls as many as it needs from
+/// the consumer, however its buffer size relates to the frame size.
+///
+/// A capacity of a few frames (`Chip8Core::AUDIO_FRAME_SIZE`) is enough to absorb the
+/// jitter between video frames and audio callbacks.
+pub fn ring(capacity: usize) -> (AudioProducer, AudioConsumer) {
+    assert!(capacity > 0, "audio ring capacity must be positive");
+
+    let ring = Arc::new(Ring {
+        samples: (0..capacity).map(|_| AtomicI16::new(0)).collect(),
+        read: AtomicUsize::new(0),
+        write: AtomicUsize::new(0),
+        overruns: AtomicU64::new(0),
+        underruns: AtomicU64::new(0),
+    });
+
+    (AudioProducer { ring: ring.clone() }, AudioConsumer { ring })
+}
+
+/// Writing end of an audio ring, owned by the thread running the core.
+pub struct AudioProducer {
+    ring: Arc<Ring>,
+}
+
+impl AudioProducer {
+    /// Append samples, dropping those that do not fit if the consumer has fallen behind.
+    /// Returns the number of samples written.
+    pub fn push(&mut self, samples: &[i16]) -> usize {
+        self.push_with(samples.len(), |i| samples[i])
+    }
+
+    /// Append `count` samples of silence.
+    pub fn push_silence(&mut self, count: usize) -> usize {
+        self.push_with(count, |_| 0)
+    }
+
+    fn push_with(&mut self, count: usize, sample: impl Fn(usize) -> i16) -> usize {
+        let ring = &self.ring;
+        let capacity = ring.samples.len();
+        let write = ring.write.load(Ordering::Relaxed);
+        let free = capacity - write.wrapping_sub(ring.read.load(Ordering::Acquire));
+        let written = count.min(free);
+
+        for i in 0..written {
+            ring.samples[write.wrapping_add(i) % capacity].store(sample(i), Ordering::Relaxed);
+        }
+
+        ring.write.store(write.wrapping_add(written), Ordering::Release);
+        if written < count {
+            ring.overruns.fetch_add(1, Ordering::Relaxed);
+        }
+        written
+    }
+
+    /// Number of pushes that dropped samples because the ring was full.
+    pub fn overruns(&self) -> u64 {
+        self.ring.overruns.load(Ordering::Relaxed)
+    }
+}
+
+/// Reading end of an audio ring, owned by the audio callback.
+pub struct AudioConsumer {
+    ring: Arc<Ring>,
+}
+
+impl AudioConsumer {
+    /// Number of samples ready to be pulled.
+    pub fn available(&self) -> usize {
+        self.ring.write.load(Ordering::Acquire).wrapping_sub(self.ring.read.load(Ordering::Relaxed))
+    }
+
+    /// Fill `out` with the oldest buffered samples, and the rest with silence if there are
+    /// not enough. Returns the number of buffered samples used.
+    pub fn pull(&mut self, out: &mut [i16]) -> usize {
+        let ring = &self.ring;
+        let capacity = ring.samples.len();
+        let read = ring.read.load(Ordering::Relaxed);
+        let pulled = out.len().min(self.available());
+
+        for (i, sample) in out[..pulled].iter_mut().enumerate() {
+            *sample = ring.samples[read.wrapping_add(i) % capacity].load(Ordering::Relaxed);
+        }
+        out[pulled..].fill(0);
+
+        ring.read.store(read.wrapping_add(pulled), Ordering::Release);
+        if pulled < out.len() {
+            ring.underruns.fetch_add(1, Ordering::Relaxed);
+        }
+        pulled
+    }
+
+    /// Number of pulls that were padded with silence because the ring ran empty.
+    pub fn underruns(&self) -> u64 {
+        self.ring.underruns.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn push_pull() {
+        let (mut producer, mut consumer) = ring(4);
+
+        assert_eq!(producer.push(&[1, 2, 3]), 3);
+        let mut out = [0; 2];
+        assert_eq!(consumer.pull(&mut out), 2);
+        assert_eq!(out, [1, 2]);
+
+        // Wraps around the end of the buffer, dropping what does not fit
+        assert_eq!(producer.push(&[4, 5, 6, 7]), 3);
+        assert_eq!(producer.overruns(), 1);
+        assert_eq!(consumer.available(), 4);
+
+        let mut out = [9; 6];
+        assert_eq!(consumer.pull(&mut out), 4);
+        assert_eq!(out, [3, 4, 5, 6, 0, 0]);
+        assert_eq!(consumer.underruns(), 1);
+
+        assert_eq!(producer.push_silence(2), 2);
+        assert_eq!(consumer.available(), 2);
+    }
+
+    #[test]
+    fn threads() {
+        let (mut producer, mut consumer) = ring(64);
+
+        let reader = std::thread::spawn(move || {
+            let mut received = Vec::new();
+            while received.len() < 1000 {
+                let mut out = [0; 7];
+                let pulled = consumer.pull(&mut out);
+                received.extend_from_slice(&out[..pulled]);
+            }
+            received
+        });
+
+        let mut sent = 0;
+        while sent < 1000 {
+            let chunk: Vec<i16> = (sent..(sent + 13).min(1000)).map(|s| s as i16).collect();
+            sent += producer.push(&chunk) as i32;
+        }
+
+        let expected: Vec<i16> = (0..1000).collect();
+        assert_eq!(reader.join().unwrap(), expected);
+    }
+}
@@ -0,0 +1,180 @@
+//! Report how well the core runs a directory of ROMs.
+//!
+//! ```text
+//! chip8-compat <directory> [--frames <n>] [--output <file>]
+//! ```
+//!
+//! Every ROM in the directory (and its subdirectories) runs for the given number of
+//! frames (300 by default) with no keys pressed, on the platform detected from the
+//! instructions it uses. The report lists, for each ROM, whether it boots (runs past its
+//! first instruction for a second without stopping), draws something, executes
+//! unsupported instructions or crashes (panics or stops on a memory protection fault),
+//! followed by the percentage of ROMs with no problem, which can be tracked across
+//! releases. It is written as a Markdown table to the output file, or to standard output
+//! if none is given.
+
+use std::{env, fs, io, fmt::Write, panic::{self, AssertUnwindSafe}, path::{Path, PathBuf}, process};
+
+use oxid_8::Chip8Core;
+use oxid_8::detect::detect_platform;
+use oxid_8::input::KeypadState;
+use oxid_8::options::{CoreOptions, MemoryProtection};
+use oxid_8::platform::Platform;
+
+/// Frames after which a program that has not stopped is considered to have booted.
+const BOOT_FRAMES: u32 = 60;
+
+struct Report {
+    path: PathBuf,
+    platform: Platform,
+    boots: bool,
+    draws: bool,
+    unsupported: Vec<u16>,
+    /// Cause of the crash, if the program crashed.
+    crash: Option<String>,
+}
+
+impl Report {
+    fn is_compatible(&self) -> bool {
+        self.boots && self.draws && self.unsupported.is_empty() && self.crash.is_none()
+    }
+}
+
+/// Collect ROMs with a supported extension, sorted by path.
+fn find_roms(dir: &Path, roms: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+
+        if path.is_dir() {
+            find_roms(&path, roms)?;
+        }
+        else if path.extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| Chip8Core::EXTENSIONS.contains(&e.to_ascii_lowercase().as_str())) {
+            roms.push(path);
+        }
+    }
+
+    roms.sort();
+    Ok(())
+}
+
+/// Whether the program stopped itself with `EXIT`, rather than on a fault.
+fn exited(core: &Chip8Core) -> bool {
+    let addr = core.pc().wrapping_sub(2) as usize;
+    core.dump_memory().memory.get(addr..addr + 2) == Some(&[0x00, 0xFD])
+}
+
+fn check(path: PathBuf, data: &[u8], frames: u32) -> Report {
+    let platform = detect_platform(data).platform;
+    let mut core = Chip8Core::with_options(CoreOptions {
+        platform,
+        platform_auto: false,
+        opcode_warnings: true,
+        memory_protection: MemoryProtection::Fault,
+        ..CoreOptions::default()
+    });
+    core.set_seed(0);
+    core.load_program(data);
+    core.soft_reset();
+
+    let mut boots = false;
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        for frame in 0..frames {
+            core.run_frame(KeypadState::default());
+
+            if core.status().halted {
+                break;
+            }
+            boots |= frame + 1 >= BOOT_FRAMES.min(frames) && core.coverage().executed_bytes() > 2;
+        }
+    }));
+
+    let crash = match result {
+        Err(payload) => Some(payload.downcast_ref::<&str>().map(|s| s.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "panic".to_string())),
+        // Programs may exit on purpose, but stopping on a protected write is a crash
+        Ok(()) if core.status().halted && !exited(&core) =>
+            Some(format!("memory protection fault at 0x{:03X}", core.pc().wrapping_sub(2))),
+        Ok(()) => None,
+    };
+
+    Report {
+        path,
+        platform,
+        boots,
+        draws: core.counters().draws > 0,
+        unsupported: core.unsupported_opcodes(),
+        crash,
+    }
+}
+
+fn yes_no(value: bool) -> &'static str {
+    if value { "yes" } else { "no" }
+}
+
+fn markdown(dir: &Path, reports: &[Report]) -> String {
+    let mut text = String::from("| ROM | Platform | Boots | Draws | Unsupported instructions | Crash |\n|-----|----------|-------|-------|--------------------------|-------|\n");
+
+    for report in reports {
+        let name = report.path.strip_prefix(dir).unwrap_or(&report.path).display().to_string();
+        let unsupported: Vec<String> = report.unsupported.iter().map(|opcode| format!("`{:04X}`", opcode)).collect();
+        let _ = writeln!(text, "| {} | {} | {} | {} | {} | {} |",
+            name.replace('|', "\\|"), report.platform.name(), yes_no(report.boots), yes_no(report.draws),
+            unsupported.join(" "), report.crash.as_deref().unwrap_or("").replace('|', "\\|"));
+    }
+
+    let compatible = reports.iter().filter(|r| r.is_compatible()).count();
+    let percent = if reports.is_empty() { 0.0 } else { 100.0 * compatible as f64 / reports.len() as f64 };
+    let _ = writeln!(text, "\n{} of {} ROMs run without problems ({:.1}%).", compatible, reports.len(), percent);
+    text
+}
+
+fn run(args: &[String]) -> io::Result<()> {
+    let mut dir = None;
+    let mut frames = 300;
+    let mut output = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--frames" | "-n" => frames = args.next().and_then(|v| v.parse().ok()).unwrap_or(frames),
+            "--output" | "-o" => output = args.next(),
+            _ => dir = Some(arg),
+        }
+    }
+
+    let Some(dir) = dir else {
+        eprintln!("usage: chip8-compat <directory> [--frames <n>] [--output <file>]");
+        process::exit(2);
+    };
+
+    let mut paths = Vec::new();
+    find_roms(Path::new(dir), &mut paths)?;
+
+    let mut reports = Vec::new();
+    for path in paths {
+        let data = fs::read(&path)?;
+        eprintln!("{}", path.display());
+        reports.push(check(path, &data, frames));
+    }
+
+    let text = markdown(Path::new(dir), &reports);
+    match output {
+        Some(path) => fs::write(path, text),
+        None => {
+            print!("{}", text);
+            Ok(())
+        },
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    if let Err(e) = run(&args) {
+        eprintln!("chip8-compat: {}", e);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,228 @@
+//! Run a program without a frontend and dump its memory, registers and stack.
+//!
+//! ```text
+//! chip8-dump <rom> [--frames <n>] [--output <file>] [--load-slot <n>] [--save-slot <n>] [--coverage <file>] [--heatmap <file>] [--record <movie>] [options...]
+//! chip8-dump <rom> --slots
+//! chip8-dump <rom> --verify <movie> [options...]
+//! chip8-dump --diff <earlier.ox8d> <later.ox8d>
+//! chip8-dump --json <dump.ox8d>
+//! ```
+//!
+//! The program runs for the given number of frames (60 by default) with no keys pressed,
+//! and the resulting memory snapshot is written to the output file (`<rom>.ox8d` by
+//! default), as human-readable JSON if its extension is `json`. Any other arguments are
+//! parsed as core options (e.g. `platform=schip`).
+//!
+//! Snapshots can also be kept in numbered slots (`<rom>.slot<n>.ox8d`, next to the
+//! program): `--load-slot` resumes from a slot instead of starting the program,
+//! `--save-slot` writes the snapshot to a slot instead of the output file, and `--slots`
+//! lists the saved slots with the time they were saved.
+//!
+//! `--coverage` writes the bytes of the program that were executed to a file, in the
+//! lcov format if its extension is `info`, or as text otherwise. `--heatmap` writes the
+//! number of reads and writes of each memory address as CSV.
+//!
+//! `--record` also records the run as a movie, with the hash of the screen after every
+//! frame. `--verify` plays a movie back instead, and fails at the first frame whose
+//! screen differs from the recording, for catching accuracy regressions between
+//! releases.
+//!
+//! `--diff` compares two snapshots instead of running a program, and prints only the
+//! registers, timers, stack and memory ranges that changed between them. `--json` prints
+//! a snapshot as JSON, for attaching to bug reports or comparing with other tools.
+
+use std::{env, fs, io, process, time::SystemTime};
+
+use oxid_8::Chip8Core;
+use oxid_8::input::KeypadState;
+use oxid_8::movie::{Movie, MovieError};
+use oxid_8::options::CoreOptions;
+use oxid_8::snapshot::MemorySnapshot;
+use oxid_8::video::TextStyle;
+
+/// Number of save slots.
+const SLOTS: u8 = 10;
+
+fn slot_path(rom: &str, slot: u8) -> String {
+    format!("{}.slot{}.ox8d", rom, slot)
+}
+
+fn parse_slot(value: Option<&String>) -> io::Result<u8> {
+    value.and_then(|v| v.parse().ok())
+        .filter(|&slot| slot < SLOTS)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("slot must be between 0 and {}", SLOTS - 1)))
+}
+
+/// Print the saved slots of a program, with how long ago each was saved.
+fn list_slots(rom: &str) {
+    let now = SystemTime::now();
+
+    for slot in 0..SLOTS {
+        let path = slot_path(rom, slot);
+        let Ok(modified) = fs::metadata(&path).and_then(|m| m.modified()) else { continue };
+
+        let minutes = now.duration_since(modified).map_or(0, |d| d.as_secs() / 60);
+        let age = match minutes {
+            0 => "just now".to_string(),
+            m if m < 60 => format!("{} min ago", m),
+            m if m < 60 * 24 => format!("{} h ago", m / 60),
+            m => format!("{} days ago", m / (60 * 24)),
+        };
+        println!("{}: {} (saved {})", slot, path, age);
+    }
+}
+
+fn run(args: &[String]) -> io::Result<()> {
+    let mut rom = None;
+    let mut frames = 60;
+    let mut output = None;
+    let mut load_slot = None;
+    let mut save_slot = None;
+    let mut list = false;
+    let mut coverage = None;
+    let mut heatmap = None;
+    let mut diff = None;
+    let mut record = None;
+    let mut verify = None;
+    let mut json = None;
+    let mut options = Vec::new();
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--frames" | "-n" => frames = iter.next().and_then(|v| v.parse().ok()).unwrap_or(frames),
+            "--output" | "-o" => output = iter.next().cloned(),
+            "--load-slot" => load_slot = Some(parse_slot(iter.next())?),
+            "--save-slot" => save_slot = Some(parse_slot(iter.next())?),
+            "--slots" => list = true,
+            "--coverage" => coverage = iter.next().cloned(),
+            "--heatmap" => heatmap = iter.next().cloned(),
+            "--record" => record = iter.next().cloned(),
+            "--verify" => verify = iter.next().cloned(),
+            "--json" => json = iter.next().cloned(),
+            "--diff" => diff = iter.next().cloned().zip(iter.next().cloned()),
+            _ if arg.contains('=') || rom.is_some() => options.push(arg.clone()),
+            _ => rom = Some(arg.clone()),
+        }
+    }
+
+    if let Some((earlier, later)) = diff {
+        return print_diff(&earlier, &later);
+    }
+    if let Some(path) = json {
+        print!("{}", read_snapshot(&path)?.to_json());
+        return Ok(());
+    }
+
+    let Some(rom) = rom else {
+        eprintln!("usage: chip8-dump <rom> [--frames <n>] [--output <file>] [--load-slot <n>] [--save-slot <n>] [--slots] [--coverage <file>] [--heatmap <file>] [--record <movie>] [options...]");
+        eprintln!("       chip8-dump <rom> --verify <movie> [options...]");
+        eprintln!("       chip8-dump --diff <earlier.ox8d> <later.ox8d>");
+        eprintln!("       chip8-dump --json <dump.ox8d>");
+        process::exit(2);
+    };
+
+    if list {
+        list_slots(&rom);
+        return Ok(());
+    }
+
+    let mut core = Chip8Core::with_options(CoreOptions::from_args(&options));
+    match load_slot {
+        Some(slot) => core.load_snapshot(&fs::read(slot_path(&rom, slot))?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+        None => core.load_program(&fs::read(&rom)?),
+    }
+    core.set_heatmap(heatmap.is_some());
+
+    if let Some(path) = verify {
+        return verify_movie(&mut core, &path);
+    }
+
+    let mut movie = record.as_ref().map(|_| Movie::record(&core));
+    for _ in 0..frames {
+        core.run_frame(KeypadState::default());
+
+        if let Some(movie) = &mut movie {
+            movie.record_frame(0);
+            movie.record_hash(core.framebuffer_hash());
+        }
+    }
+
+    if let (Some(path), Some(movie)) = (record, movie) {
+        fs::write(&path, movie.to_bytes())?;
+        eprintln!("movie of {} frames written to {}", frames, path);
+    }
+
+    if let Some(path) = coverage {
+        let coverage = core.coverage();
+        let text = if path.ends_with(".info") { coverage.to_lcov(&rom) } else { coverage.to_text() };
+        fs::write(&path, text)?;
+        eprintln!("{} of {} bytes executed, coverage written to {}", coverage.executed_bytes(), coverage.len(), path);
+    }
+
+    if let (Some(path), Some(heatmap)) = (heatmap, core.heatmap()) {
+        fs::write(&path, heatmap.to_csv())?;
+        eprintln!("memory heatmap written to {}", path);
+    }
+
+    let output = match save_slot {
+        Some(slot) => slot_path(&rom, slot),
+        None => output.unwrap_or_else(|| rom + ".ox8d"),
+    };
+    if output.ends_with(".json") {
+        fs::write(&output, core.dump_memory().to_json())?;
+    }
+    else {
+        core.dump_memory_to_file(&output)?;
+    }
+    eprintln!("{} frames run, memory dumped to {}", frames, output);
+
+    Ok(())
+}
+
+/// Play a movie back, exiting with an error at the first frame whose screen differs from
+/// the recording.
+fn verify_movie(core: &mut Chip8Core, path: &str) -> io::Result<()> {
+    let movie = Movie::from_bytes(&fs::read(path)?)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path, e)))?;
+
+    match movie.verify_playback(core) {
+        Ok(frames) => {
+            println!("{} frames match the recording", frames);
+            Ok(())
+        },
+        Err(e @ MovieError::Divergence { .. }) => {
+            print!("{}\n{}", e, core.screen_text(TextStyle::Ascii));
+            process::exit(1);
+        },
+        Err(e) => Err(io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path, e))),
+    }
+}
+
+fn read_snapshot(path: &str) -> io::Result<MemorySnapshot> {
+    MemorySnapshot::from_bytes(&fs::read(path)?)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path, e)))
+}
+
+/// Print the differences between two snapshot files.
+fn print_diff(earlier: &str, later: &str) -> io::Result<()> {
+    let diff = read_snapshot(later)?.diff(&read_snapshot(earlier)?);
+
+    if diff.is_empty() {
+        println!("no differences");
+    }
+    else {
+        print!("{}", diff);
+    }
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    if let Err(e) = run(&args) {
+        eprintln!("chip8-dump: {}", e);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,137 @@
+//! Generate a reference of the instructions implemented by the core.
+//!
+//! ```text
+//! chip8-opcodes [--html] [--output <file>]
+//! ```
+//!
+//! The reference is generated from the instruction table of the interpreter, with the
+//! opcode pattern, the mnemonic used by the disassembler, the instruction set it belongs
+//! to and the description from the documentation of the instruction's implementation.
+//! It is written as a Markdown table, or as an HTML table with `--html`, to the output
+//! file or to standard output if none is given.
+
+use std::{env, fs, io, process};
+
+use oxid_8::cpu::Cpu;
+use oxid_8::detect::{instruction_extension, Extension};
+
+/// Source of the instruction implementations, from which descriptions are taken.
+const SOURCE: &str = include_str!("../lib.rs");
+
+struct Entry {
+    pattern: &'static str,
+    name: &'static str,
+    variant: &'static str,
+    description: String,
+}
+
+/// Documentation comment of the implementation of an instruction, as a single line.
+fn description(name: &str) -> String {
+    let signature = format!("fn {}(&mut self", name.to_lowercase());
+    let lines: Vec<&str> = SOURCE.lines().collect();
+    let Some(index) = lines.iter().position(|line| line.trim_start().starts_with(&signature)) else {
+        return String::new();
+    };
+
+    let doc: Vec<&str> = lines[..index].iter().rev()
+        .map(|line| line.trim())
+        .take_while(|line| line.starts_with("///"))
+        .map(|line| line.trim_start_matches("///").trim())
+        .collect();
+    doc.into_iter().rev().collect::<Vec<_>>().join(" ")
+}
+
+/// Instruction set of an opcode pattern, found by filling in its arguments.
+fn variant(pattern: &str) -> &'static str {
+    let opcode = u16::from_str_radix(&pattern.replace(['X', 'Y', 'N'], "1"), 16).unwrap();
+
+    match instruction_extension(opcode) {
+        Some(Extension::SuperChip) => "SUPER-CHIP",
+        Some(Extension::XoChip) => "XO-CHIP",
+        None => "CHIP-8",
+    }
+}
+
+fn entries() -> Vec<Entry> {
+    Cpu::new().instructions().into_iter()
+        .map(|i| Entry {
+            pattern: i.pattern(),
+            name: i.name(),
+            variant: variant(i.pattern()),
+            description: description(i.name()),
+        })
+        .collect()
+}
+
+fn markdown(entries: &[Entry]) -> String {
+    let mut text = String::from("| Opcode | Mnemonic | Variant | Description |\n|--------|----------|---------|-------------|\n");
+
+    for entry in entries {
+        text += &format!("| `{}` | `{}` | {} | {} |\n", entry.pattern, entry.name, entry.variant, entry.description.replace('|', "\\|"));
+    }
+
+    text
+}
+
+/// Convert the inline code and bold text of a description to HTML.
+fn html_description(description: &str) -> String {
+    let escaped = description.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;");
+    let mut html = String::new();
+
+    for (i, part) in escaped.split("**").enumerate() {
+        let part = part.split('`').enumerate()
+            .map(|(j, code)| if j % 2 == 1 { format!("<code>{}</code>", code) } else { code.to_string() })
+            .collect::<String>();
+        html += &if i % 2 == 1 { format!("<strong>{}</strong>", part) } else { part };
+    }
+
+    html
+}
+
+fn html(entries: &[Entry]) -> String {
+    let mut text = String::from("<table>\n<tr><th>Opcode</th><th>Mnemonic</th><th>Variant</th><th>Description</th></tr>\n");
+
+    for entry in entries {
+        text += &format!("<tr><td><code>{}</code></td><td><code>{}</code></td><td>{}</td><td>{}</td></tr>\n",
+            entry.pattern, entry.name, entry.variant, html_description(&entry.description));
+    }
+
+    text + "</table>\n"
+}
+
+fn run(args: &[String]) -> io::Result<()> {
+    let mut as_html = false;
+    let mut output = None;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--html" => as_html = true,
+            "--output" | "-o" => output = iter.next().cloned(),
+            _ => {
+                eprintln!("usage: chip8-opcodes [--html] [--output <file>]");
+                process::exit(2);
+            },
+        }
+    }
+
+    let entries = entries();
+    let text = if as_html { html(&entries) } else { markdown(&entries) };
+
+    match output {
+        Some(path) => fs::write(path, text),
+        None => {
+            print!("{}", text);
+            Ok(())
+        },
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    if let Err(e) = run(&args) {
+        eprintln!("chip8-opcodes: {}", e);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,131 @@
+//! Generate a program database from a directory of ROMs.
+//!
+//! ```text
+//! chip8-scan <directory> [--output <file>] [--dat <file>]
+//! ```
+//!
+//! Every ROM in the directory (and its subdirectories) is hashed and its platform is
+//! detected from the instructions it uses. The database is written to the output file,
+//! or to standard output if none is given, and can be placed in the frontend's system
+//! directory as `oxid8.db`. A clrmamepro DAT file can also be exported, which RetroArch's
+//! database tools convert into an RDB for playlist scanning.
+
+use std::{env, fs, io, fmt::Write, path::{Path, PathBuf}, process};
+
+use sha1::{Digest, Sha1};
+
+use oxid_8::Chip8Core;
+use oxid_8::database::{hash_hex, Database, DatabaseEntry};
+use oxid_8::detect::detect_platform;
+
+struct Rom {
+    file_name: String,
+    data: Vec<u8>,
+    hash: [u8; 20],
+}
+
+/// Collect ROMs with a supported extension, sorted by path.
+fn find_roms(dir: &Path, roms: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+
+        if path.is_dir() {
+            find_roms(&path, roms)?;
+        }
+        else if path.extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| Chip8Core::EXTENSIONS.contains(&e.to_ascii_lowercase().as_str())) {
+            roms.push(path);
+        }
+    }
+
+    roms.sort();
+    Ok(())
+}
+
+/// CRC-32 (IEEE 802.3) checksum, as used by DAT files.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+fn game_name(file_name: &str) -> &str {
+    Path::new(file_name).file_stem().and_then(|s| s.to_str()).unwrap_or(file_name)
+}
+
+fn dat(roms: &[Rom]) -> String {
+    let mut text = String::from("clrmamepro (\n\tname \"CHIP-8\"\n\tdescription \"CHIP-8\"\n)\n");
+
+    for rom in roms {
+        let name = game_name(&rom.file_name).replace('"', "'");
+        let _ = write!(text,
+            "\ngame (\n\tname \"{0}\"\n\tdescription \"{0}\"\n\trom ( name \"{1}\" size {2} crc {3:08X} sha1 {4} )\n)\n",
+            name, rom.file_name.replace('"', "'"), rom.data.len(), crc32(&rom.data), hash_hex(&rom.hash).to_uppercase());
+    }
+
+    text
+}
+
+fn run(args: &[String]) -> io::Result<()> {
+    let mut dir = None;
+    let mut output = None;
+    let mut dat_output = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--output" | "-o" => output = args.next(),
+            "--dat" => dat_output = args.next(),
+            _ => dir = Some(arg),
+        }
+    }
+
+    let Some(dir) = dir else {
+        eprintln!("usage: chip8-scan <directory> [--output <file>] [--dat <file>]");
+        process::exit(2);
+    };
+
+    let mut paths = Vec::new();
+    find_roms(Path::new(dir), &mut paths)?;
+
+    let mut roms = Vec::new();
+    let mut database = Database::default();
+
+    for path in paths {
+        let data = fs::read(&path)?;
+        let hash: [u8; 20] = Sha1::digest(&data).into();
+        let file_name = path.file_name().and_then(|s| s.to_str()).unwrap_or_default().to_string();
+
+        let detection = detect_platform(&data);
+        eprintln!("{}: {}", path.display(), detection.summary());
+
+        database.insert(hash, DatabaseEntry { name: game_name(&file_name).to_string(), platform: detection.platform });
+        roms.push(Rom { file_name, data, hash });
+    }
+
+    match output {
+        Some(path) => database.save(Path::new(path))?,
+        None => print!("{}", database.to_text()),
+    }
+
+    if let Some(path) = dat_output {
+        fs::write(path, dat(&roms))?;
+    }
+
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    if let Err(e) = run(&args) {
+        eprintln!("chip8-scan: {}", e);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,120 @@
+use std::time::{Duration, Instant};
+
+/// Paces emulation at a fixed frame rate from wall-clock time, for frontends that drive
+/// the core themselves (without libretro, which paces frames for the core).
+///
+/// Each call to `tick` tells how many frames to run to catch up with the time elapsed
+/// since the previous call. Time is tracked in whole nanoseconds per frame rate, so no
+/// drift accumulates however long the program runs. After a stall (e.g. the window was
+/// being dragged), at most `max_catch_up` frames are run and the rest is skipped, rather
+/// than running the program fast until it catches up.
+#[derive(Clone, Debug)]
+pub struct Clock {
+    frame_rate: u32,
+    max_catch_up: u32,
+    /// Elapsed time not yet run, in nanoseconds multiplied by the frame rate.
+    budget: u128,
+    last: Option<Instant>,
+    skipped: u64,
+}
+
+impl Clock {
+    const NANOS_PER_SECOND: u128 = 1_000_000_000;
+    /// Default maximum number of frames run by a single tick.
+    pub const MAX_CATCH_UP: u32 = 4;
+
+    /// Clock running at the frame rate of the core (60 Hz).
+    pub fn new() -> Self {
+        Self::with_frame_rate(crate::Chip8Core::FRAME_RATE as u32)
+    }
+
+    pub fn with_frame_rate(frame_rate: u32) -> Self {
+        assert!(frame_rate > 0, "frame rate must be positive");
+        Self { frame_rate, max_catch_up: Self::MAX_CATCH_UP, budget: 0, last: None, skipped: 0 }
+    }
+
+    /// Set the maximum number of frames run by a single tick. Must be at least 1.
+    pub fn set_max_catch_up(&mut self, frames: u32) {
+        self.max_catch_up = frames.max(1);
+    }
+
+    /// Number of frames to run now. The first tick starts the clock and runs one frame.
+    pub fn tick(&mut self, now: Instant) -> u32 {
+        match self.last.replace(now) {
+            Some(last) => self.advance(now.saturating_duration_since(last)),
+            None => 1,
+        }
+    }
+
+    /// Number of frames to run after `elapsed` time, for frontends that measure time
+    /// themselves (e.g. from the timestamps of `requestAnimationFrame`).
+    pub fn advance(&mut self, elapsed: Duration) -> u32 {
+        self.budget += elapsed.as_nanos() * self.frame_rate as u128;
+
+        let frames = self.budget / Self::NANOS_PER_SECOND;
+        self.budget %= Self::NANOS_PER_SECOND;
+
+        if frames > self.max_catch_up as u128 {
+            self.skipped += (frames - self.max_catch_up as u128) as u64;
+            self.max_catch_up
+        }
+        else {
+            frames as u32
+        }
+    }
+
+    /// Time elapsed since the last frame was due, less than a frame.
+    pub fn drift(&self) -> Duration {
+        Duration::from_nanos((self.budget / self.frame_rate as u128) as u64)
+    }
+
+    /// Time until the next frame is due, for sleeping between ticks.
+    pub fn until_next_frame(&self) -> Duration {
+        let remaining = Self::NANOS_PER_SECOND - self.budget;
+        Duration::from_nanos(remaining.div_ceil(self.frame_rate as u128) as u64)
+    }
+
+    /// Number of frames skipped so far because the frontend fell too far behind.
+    pub fn skipped_frames(&self) -> u64 {
+        self.skipped
+    }
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn advance() {
+        let mut clock = Clock::new();
+
+        // 1/60 s is not a whole number of nanoseconds, but a second is always 60 frames
+        let frames: u32 = (0..100).map(|_| clock.advance(Duration::from_millis(10))).sum();
+        assert_eq!(frames, 60);
+        assert_eq!(clock.drift(), Duration::ZERO);
+        assert_eq!(clock.until_next_frame(), Duration::from_nanos(16_666_667));
+
+        assert_eq!(clock.advance(Duration::from_millis(20)), 1);
+        assert_eq!(clock.drift(), Duration::from_nanos(3_333_333));
+
+        // A stall of a second runs at most 4 frames
+        assert_eq!(clock.advance(Duration::from_secs(1)), Clock::MAX_CATCH_UP);
+        assert_eq!(clock.skipped_frames(), 56);
+    }
+
+    #[test]
+    fn tick() {
+        let mut clock = Clock::new();
+        let start = Instant::now();
+
+        assert_eq!(clock.tick(start), 1);
+        assert_eq!(clock.tick(start + Duration::from_millis(10)), 0);
+        assert_eq!(clock.tick(start + Duration::from_millis(40)), 2);
+    }
+}
@@ -0,0 +1,97 @@
+use std::ops::Range;
+
+/// Bytes of a program that were executed as code, for finding dead code and telling code
+/// apart from data.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Coverage {
+    start: u16,
+    executed: Vec<bool>,
+}
+
+impl Coverage {
+    /// Coverage of the bytes from `start`, with `executed` telling whether each was
+    /// executed.
+    pub fn new(start: u16, executed: &[bool]) -> Self {
+        Self { start, executed: executed.to_vec() }
+    }
+
+    pub fn is_executed(&self, addr: u16) -> bool {
+        addr.checked_sub(self.start).and_then(|i| self.executed.get(i as usize)).copied().unwrap_or(false)
+    }
+
+    /// Number of bytes executed, out of the size of the program.
+    pub fn executed_bytes(&self) -> usize {
+        self.executed.iter().filter(|&&e| e).count()
+    }
+
+    pub fn len(&self) -> usize {
+        self.executed.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.executed.is_empty()
+    }
+
+    /// Consecutive ranges of addresses that were executed (`true`) or not (`false`).
+    pub fn ranges(&self) -> Vec<(Range<u16>, bool)> {
+        let mut ranges = Vec::new();
+        let mut i = 0;
+
+        while i < self.executed.len() {
+            let executed = self.executed[i];
+            let length = self.executed[i..].iter().take_while(|&&e| e == executed).count();
+            let start = self.start + i as u16;
+            ranges.push((start..start + length as u16, executed));
+            i += length;
+        }
+
+        ranges
+    }
+
+    /// Coverage as text, with a line per range of addresses and a summary.
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+
+        for (range, executed) in self.ranges() {
+            let status = if executed { "executed" } else { "never executed" };
+            text += &format!("0x{:03X}-0x{:03X} {}\n", range.start, range.end - 1, status);
+        }
+
+        text += &format!("{} of {} bytes executed\n", self.executed_bytes(), self.len());
+        text
+    }
+
+    /// Coverage in the lcov tracefile format, with the address of each instruction (every
+    /// second byte) in place of a line number, and a hit count of 1 if it was executed.
+    pub fn to_lcov(&self, name: &str) -> String {
+        let mut lcov = format!("SF:{}\n", name);
+        let mut hit = 0;
+
+        for (i, &executed) in self.executed.iter().enumerate().step_by(2) {
+            lcov += &format!("DA:{},{}\n", self.start as usize + i, executed as u8);
+            hit += executed as usize;
+        }
+
+        lcov += &format!("LH:{}\nLF:{}\nend_of_record\n", hit, self.executed.len().div_ceil(2));
+        lcov
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn export() {
+        let coverage = Coverage::new(0x200, &[true, true, false, false, true, true]);
+
+        assert!(coverage.is_executed(0x204));
+        assert!(!coverage.is_executed(0x1FF));
+        assert_eq!(coverage.ranges(), vec![(0x200..0x202, true), (0x202..0x204, false), (0x204..0x206, true)]);
+        assert_eq!(
+            coverage.to_text(),
+            "0x200-0x201 executed\n0x202-0x203 never executed\n0x204-0x205 executed\n4 of 6 bytes executed\n",
+        );
+        assert_eq!(coverage.to_lcov("rom.ch8"), "SF:rom.ch8\nDA:512,1\nDA:514,0\nDA:516,1\nLH:2\nLF:3\nend_of_record\n");
+    }
+}
@@ -1,4 +1,6 @@
 
+use std::{collections::BTreeSet, ops::Range};
+
 use super::*;
 
 /// Prints the disassembled program to standard output, including its instructions,
@@ -15,7 +17,7 @@ pub fn disassemble(data: &[u8]) {
         print!("0x{:X} ({}) | 0x{:04X} | {} [", addr, addr, raw, instruction.name);
 
         let mut args_str = Vec::new();
-        for arg in instruction.args(raw) {
+        for arg in instruction.args(raw).iter() {
             args_str.push(format!("{} = 0x{:X}", arg.0, arg.1));
         }
 
@@ -23,6 +25,129 @@ pub fn disassemble(data: &[u8]) {
     }
 }
 
+/// Instruction at an address of a program, as decoded by `analyze`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DecodedInstruction {
+    pub addr: u16,
+    pub opcode: u16,
+    pub name: &'static str,
+    /// Arguments, sorted by name.
+    pub args: Vec<(&'static str, u16)>,
+    /// Whether the instruction can be reached from the entry point.
+    pub reachable: bool,
+}
+
+/// Static analysis of a program, for external tools that build on the disassembly.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Analysis {
+    /// Every pair of bytes of the program, decoded as an instruction.
+    pub instructions: Vec<DecodedInstruction>,
+    /// Targets of jumps and calls.
+    pub labels: BTreeSet<u16>,
+    /// Ranges of addresses in the program that cannot be reached from the entry point,
+    /// and so are most likely data.
+    pub data: Vec<Range<u16>>,
+    /// Addresses in the program loaded into `I` before a draw instruction.
+    pub sprites: BTreeSet<u16>,
+}
+
+impl Analysis {
+    /// Serialize the analysis as JSON, with addresses and opcodes as numbers.
+    pub fn to_json(&self) -> String {
+        let instructions: Vec<String> = self.instructions.iter()
+            .map(|i| {
+                let args: Vec<String> = i.args.iter().map(|(name, value)| format!("\"{}\":{}", name, value)).collect();
+                format!(
+                    "{{\"addr\":{},\"opcode\":{},\"name\":\"{}\",\"args\":{{{}}},\"reachable\":{}}}",
+                    i.addr, i.opcode, i.name, args.join(","), i.reachable,
+                )
+            })
+            .collect();
+        let list = |addrs: &BTreeSet<u16>| addrs.iter().map(u16::to_string).collect::<Vec<_>>().join(",");
+        let data: Vec<String> = self.data.iter().map(|r| format!("{{\"start\":{},\"end\":{}}}", r.start, r.end)).collect();
+
+        format!(
+            "{{\"instructions\":[{}],\"labels\":[{}],\"data\":[{}],\"sprites\":[{}]}}",
+            instructions.join(","), list(&self.labels), data.join(","), list(&self.sprites),
+        )
+    }
+}
+
+/// Decode a program and find its labels, data regions and sprites by following the
+/// flow of execution from the entry point. Computed jumps (`BNNN`) are not followed.
+pub fn analyze(data: &[u8]) -> Analysis {
+    let cpu = Cpu::new();
+    let start = Cpu::INITIAL_ADDR as usize;
+    let end = start + data.len();
+    let opcode_at = |addr: usize| {
+        (start..end - 1).contains(&addr).then(|| u16::from_be_bytes([data[addr - start], data[addr + 1 - start]]))
+    };
+
+    let mut analysis = Analysis::default();
+    let mut reachable = vec![false; data.len()];
+    let mut pending = vec![start];
+
+    while let Some(addr) = pending.pop() {
+        let Some(opcode) = opcode_at(addr) else { continue };
+        if reachable[addr - start] {
+            continue;
+        }
+        reachable[addr - start] = true;
+        reachable[addr + 1 - start] = true;
+
+        let instruction = cpu.decode_instruction(opcode);
+        let target = opcode as usize & 0xFFF;
+        match instruction.name() {
+            "JMP" => {
+                analysis.labels.insert(target as u16);
+                pending.push(target);
+            },
+            "CALL" => {
+                analysis.labels.insert(target as u16);
+                pending.extend([target, addr + 2]);
+            },
+            "SKPEQ" | "SKPNE" | "SKPEQR" | "SKPNER" | "SKPK" | "SKPNK" => pending.extend([addr + 2, addr + 4]),
+            "RET" | "EXIT" | "JMPR" => {},
+            _ => pending.push(addr + 2),
+        }
+    }
+
+    let mut sprite = None;
+    for addr in (start..end - data.len() % 2).step_by(2) {
+        let opcode = opcode_at(addr).unwrap();
+        let instruction = cpu.decode_instruction(opcode);
+        let mut args: Vec<_> = instruction.args(opcode).iter().collect();
+        args.sort();
+
+        if reachable[addr - start] {
+            match instruction.name() {
+                "MOVI" => sprite = Some(opcode & 0xFFF).filter(|&i| (start..end).contains(&(i as usize))),
+                "DRAW" => analysis.sprites.extend(sprite),
+                _ => {},
+            }
+        }
+
+        analysis.instructions.push(DecodedInstruction {
+            addr: addr as u16,
+            opcode,
+            name: instruction.name(),
+            args,
+            reachable: reachable[addr - start],
+        });
+    }
+
+    let mut addr = start;
+    while addr < end {
+        let length = reachable[addr - start..].iter().take_while(|&&r| r == reachable[addr - start]).count();
+        if !reachable[addr - start] {
+            analysis.data.push(addr as u16..(addr + length) as u16);
+        }
+        addr += length;
+    }
+
+    analysis
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -32,4 +157,24 @@ mod tests {
         let data = [0x84, 0xF2, 0x8E, 0x10, 0xA4, 0x53];
         disassemble(data.as_slice());
     }
+
+    #[test]
+    fn analyze_test() {
+        // 0x200: MOVI 0x20C, CALL 0x208, JMP 0x200
+        // 0x206: unreachable, 0x208: DRAW V0 V0 2, RET
+        // 0x20C: sprite data
+        let data = [0xA2, 0x0C, 0x22, 0x08, 0x12, 0x00, 0xFF, 0xFF, 0xD0, 0x02, 0x00, 0xEE, 0x3C, 0x42];
+        let analysis = analyze(&data);
+
+        assert_eq!(analysis.instructions.len(), 7);
+        assert_eq!(analysis.instructions[4].name, "DRAW");
+        assert_eq!(analysis.instructions[4].args, vec![("N", 2), ("X", 0), ("Y", 0)]);
+        assert_eq!(analysis.labels, BTreeSet::from([0x200, 0x208]));
+        assert_eq!(analysis.data, vec![0x206..0x208, 0x20C..0x20E]);
+        assert_eq!(analysis.sprites, BTreeSet::from([0x20C]));
+
+        let json = analysis.to_json();
+        assert!(json.starts_with("{\"instructions\":[{\"addr\":512,\"opcode\":41484,\"name\":\"MOVI\",\"args\":{\"N\":524},\"reachable\":true}"));
+        assert!(json.ends_with("\"labels\":[512,520],\"data\":[{\"start\":518,\"end\":520},{\"start\":524,\"end\":526}],\"sprites\":[524]}"));
+    }
 }
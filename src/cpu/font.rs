@@ -0,0 +1,213 @@
+//! Hexadecimal digit fonts of different interpreters. Each glyph of the small fonts is
+//! 4x5 pixels (5 bytes), and each glyph of the large fonts is 8x10 pixels (10 bytes).
+//! Large fonts include digits `A` to `F`, which are only loaded if enabled.
+
+/// Font set loaded into the interpreter area, which differs between interpreters and
+/// affects programs that draw digits (e.g. scores).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FontSet {
+    /// SUPER-CHIP fonts, whose small font is also used by most modern interpreters.
+    #[default]
+    SuperChip,
+    /// COSMAC VIP small font, with the SUPER-CHIP large font.
+    Vip,
+    /// SUPER-CHIP small font, with the large font of Octo.
+    Octo,
+    /// DREAM 6800 small font (3 pixels wide), with the SUPER-CHIP large font.
+    Dream6800,
+}
+
+impl FontSet {
+    pub fn small(&self) -> &'static [u8; SMALL_SIZE] {
+        match self {
+            Self::SuperChip | Self::Octo => &SUPERCHIP,
+            Self::Vip => &VIP,
+            Self::Dream6800 => &DREAM_6800,
+        }
+    }
+
+    pub fn large(&self) -> &'static [u8; LARGE_SIZE] {
+        match self {
+            Self::Octo => &LARGE_OCTO,
+            _ => &LARGE_SUPERCHIP,
+        }
+    }
+
+    /// Name used for the font set in command-line arguments and core options.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::SuperChip => "schip",
+            Self::Vip => "vip",
+            Self::Octo => "octo",
+            Self::Dream6800 => "dream6800",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "schip" => Some(Self::SuperChip),
+            "vip" => Some(Self::Vip),
+            "octo" => Some(Self::Octo),
+            "dream6800" => Some(Self::Dream6800),
+            _ => None,
+        }
+    }
+}
+
+/// Size of a small font in bytes.
+pub const SMALL_SIZE: usize = 80;
+/// Size of a large font in bytes, including digits `A` to `F`.
+pub const LARGE_SIZE: usize = 160;
+/// Size of the decimal digits (`0` to `9`) of a large font in bytes.
+pub const LARGE_DECIMAL_SIZE: usize = 100;
+
+/// Font of SUPER-CHIP and most modern interpreters (`1`, `4`, `7`, `B`, `D`):
+///
+/// ```text
+/// ..#.  #..#  ####  ###.  ###.
+/// .##.  #..#  ...#  #..#  #..#
+/// ..#.  ####  ..#.  ###.  #..#
+/// ..#.  ...#  .#..  #..#  #..#
+/// .###  ...#  .#..  ###.  ###.
+/// ```
+pub const SUPERCHIP: [u8; SMALL_SIZE] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+    0x20, 0x60, 0x20, 0x20, 0x70, // 1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+    0x90, 0x90, 0xF0, 0x10, 0x10, // 4
+    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+    0xF0, 0x10, 0x20, 0x40, 0x40, // 7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
+    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+    0xE0, 0x90, 0x90, 0x90, 0xE0, // D
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+];
+
+/// Font of the COSMAC VIP interpreter (`1`, `4`, `7`, `B`, `D`):
+///
+/// ```text
+/// .##.  #.#.  ####  ####  ####
+/// ..#.  #.#.  ...#  .#.#  .#.#
+/// ..#.  ####  ...#  .###  .#.#
+/// ..#.  ..#.  ...#  .#.#  .#.#
+/// .###  ..#.  ...#  ####  ####
+/// ```
+pub const VIP: [u8; SMALL_SIZE] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+    0x60, 0x20, 0x20, 0x20, 0x70, // 1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+    0xA0, 0xA0, 0xF0, 0x20, 0x20, // 4
+    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+    0xF0, 0x10, 0x10, 0x10, 0x10, // 7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+    0xF0, 0x50, 0x70, 0x50, 0xF0, // B
+    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+    0xF0, 0x50, 0x50, 0x50, 0xF0, // D
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+];
+
+/// Font of the DREAM 6800 interpreter (`1`, `4`, `7`, `B`, `D`):
+///
+/// ```text
+/// .#.  #..  ###  ##.  ##.
+/// .#.  #.#  ..#  #.#  #.#
+/// .#.  #.#  ..#  ###  #.#
+/// .#.  ###  ..#  #.#  #.#
+/// .#.  ..#  ..#  ##.  ##.
+/// ```
+pub const DREAM_6800: [u8; SMALL_SIZE] = [
+    0xE0, 0xA0, 0xA0, 0xA0, 0xE0, // 0
+    0x40, 0x40, 0x40, 0x40, 0x40, // 1
+    0xE0, 0x20, 0xE0, 0x80, 0xE0, // 2
+    0xE0, 0x20, 0xE0, 0x20, 0xE0, // 3
+    0x80, 0xA0, 0xA0, 0xE0, 0x20, // 4
+    0xE0, 0x80, 0xE0, 0x20, 0xE0, // 5
+    0xE0, 0x80, 0xE0, 0xA0, 0xE0, // 6
+    0xE0, 0x20, 0x20, 0x20, 0x20, // 7
+    0xE0, 0xA0, 0xE0, 0xA0, 0xE0, // 8
+    0xE0, 0xA0, 0xE0, 0x20, 0xE0, // 9
+    0xE0, 0xA0, 0xE0, 0xA0, 0xA0, // A
+    0xC0, 0xA0, 0xE0, 0xA0, 0xC0, // B
+    0xE0, 0x80, 0x80, 0x80, 0xE0, // C
+    0xC0, 0xA0, 0xA0, 0xA0, 0xC0, // D
+    0xE0, 0x80, 0xE0, 0x80, 0xE0, // E
+    0xE0, 0x80, 0xC0, 0x80, 0x80, // F
+];
+
+/// Large font of SUPER-CHIP 1.1 (`0`, `1`, `2`). The original only covers digits `0` to
+/// `9`; digits `A` to `F` are drawn in the same style:
+///
+/// ```text
+/// ..####..  ...##...  ..#####.
+/// .######.  ..###...  .#######
+/// ###..###  .####...  ##....##
+/// ##....##  ...##...  .....##.
+/// ##....##  ...##...  ....##..
+/// ##....##  ...##...  ...##...
+/// ##....##  ...##...  ..##....
+/// ###..###  ...##...  .##.....
+/// .######.  ...##...  ########
+/// ..####..  ..####..  ########
+/// ```
+pub const LARGE_SUPERCHIP: [u8; LARGE_SIZE] = [
+    0x3C, 0x7E, 0xE7, 0xC3, 0xC3, 0xC3, 0xC3, 0xE7, 0x7E, 0x3C, // 0
+    0x18, 0x38, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3C, // 1
+    0x3E, 0x7F, 0xC3, 0x06, 0x0C, 0x18, 0x30, 0x60, 0xFF, 0xFF, // 2
+    0x3C, 0x7E, 0xC3, 0x03, 0x0E, 0x0E, 0x03, 0xC3, 0x7E, 0x3C, // 3
+    0x06, 0x0E, 0x1E, 0x36, 0x66, 0xC6, 0xFF, 0xFF, 0x06, 0x06, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFE, 0x03, 0xC3, 0x7E, 0x3C, // 5
+    0x3E, 0x7C, 0xC0, 0xC0, 0xFC, 0xFE, 0xC3, 0xC3, 0x7E, 0x3C, // 6
+    0xFF, 0xFF, 0x03, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x60, 0x60, // 7
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7E, 0x7E, 0xC3, 0xC3, 0x7E, 0x3C, // 8
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7F, 0x3F, 0x03, 0x03, 0x3E, 0x7C, // 9
+    0x3C, 0x7E, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, // A
+    0xFC, 0xFE, 0xC3, 0xC3, 0xFE, 0xFE, 0xC3, 0xC3, 0xFE, 0xFC, // B
+    0x3C, 0x7E, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0x7E, 0x3C, // C
+    0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFC, 0xC0, 0xC0, 0xFF, 0xFF, // E
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFC, 0xC0, 0xC0, 0xC0, 0xC0, // F
+];
+
+/// Large font of Octo, covering all hexadecimal digits (`0`, `1`, `2`):
+///
+/// ```text
+/// ########  ...##...  ########
+/// ########  .####...  ########
+/// ##....##  .####...  ......##
+/// ##....##  ...##...  ......##
+/// ##....##  ...##...  ########
+/// ##....##  ...##...  ########
+/// ##....##  ...##...  ##......
+/// ##....##  ...##...  ##......
+/// ########  ########  ########
+/// ########  ########  ########
+/// ```
+pub const LARGE_OCTO: [u8; LARGE_SIZE] = [
+    0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, // 0
+    0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0xFF, // 1
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // 2
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 3
+    0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0x03, 0x03, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 5
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 6
+    0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18, // 7
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 8
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 9
+    0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // A
+    0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, // B
+    0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C, // C
+    0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // E
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0, // F
+];
@@ -1,16 +1,80 @@
 
 pub mod disassembler;
+pub mod font;
 
 use std::collections::HashMap;
 use crate::Chip8Core;
+use font::FontSet;
+
+/// Arguments of a decoded instruction by name (`X`, `Y` or `N`), stored inline so that
+/// executing an instruction does not allocate.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Args {
+    values: [(&'static str, u16); Args::MAX],
+    len: usize,
+}
+
+impl Args {
+    /// Maximum number of arguments of an instruction.
+    pub const MAX: usize = 3;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the value of an argument. Panics if there are already `MAX` arguments.
+    pub fn insert(&mut self, name: &'static str, value: u16) {
+        match self.values[..self.len].iter_mut().find(|(k, _)| *k == name) {
+            Some(entry) => entry.1 = value,
+            None => {
+                self.values[self.len] = (name, value);
+                self.len += 1;
+            },
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&u16> {
+        self.values[..self.len].iter().find(|(k, _)| *k == name).map(|(_, v)| v)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&'static str, u16)> + '_ {
+        self.values[..self.len].iter().copied()
+    }
+}
+
+impl FromIterator<(&'static str, u16)> for Args {
+    fn from_iter<T: IntoIterator<Item = (&'static str, u16)>>(iter: T) -> Self {
+        let mut args = Self::new();
+        for (name, value) in iter {
+            args.insert(name, value);
+        }
+        args
+    }
+}
+
+impl<const N: usize> From<[(&'static str, u16); N]> for Args {
+    fn from(values: [(&'static str, u16); N]) -> Self {
+        values.into_iter().collect()
+    }
+}
 
 pub struct Instruction {
     name: &'static str,
+    /// Opcode with the arguments as letters (e.g. `8XY4`).
+    pattern: &'static str,
     arg_masks: HashMap<&'static str, u16>,
-    pub callback: fn(&mut Chip8Core, HashMap<&'static str, u16>),
+    pub callback: fn(&mut Chip8Core, Args),
 }
 
 impl Instruction {
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn pattern(&self) -> &'static str {
+        self.pattern
+    }
+
     // Useful constants for specifying bit masks
     const HEX_0: u16 = 0x000F;
     const HEX_1: u16 = 0x00F0;
@@ -26,8 +90,55 @@ impl Instruction {
     }
 
     /// Extract all arguments from an instruction via their bitmasks.
-    pub fn args(&self, instruction: u16) -> HashMap<&'static str, u16> {
-        self.arg_masks.iter().map(|(&k, _)| (k, self.arg(instruction, k))).collect()
+    pub fn args(&self, instruction: u16) -> Args {
+        self.arg_masks.keys().map(|&k| (k, self.arg(instruction, k))).collect()
+    }
+}
+
+/// Built-in fonts and their placement in the interpreter area (`0x000` to `0x1FF`),
+/// which some programs read directly, and the address programs are loaded at.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MemoryLayout {
+    /// Address programs are loaded at and start executing from, which is the end of
+    /// the interpreter area.
+    pub program_addr: u16,
+    pub font: FontSet,
+    pub font_addr: u16,
+    pub large_font_addr: u16,
+    /// The large font covers digits `A` to `F`. Otherwise, only digits `0` to `9` are
+    /// loaded and `FX30` wraps around to them, as on SUPER-CHIP 1.1.
+    pub large_font_hex: bool,
+    /// Fill the rest of the interpreter area with a fixed non-zero pattern, standing in
+    /// for the interpreter code found there on the COSMAC VIP.
+    pub fill_interpreter_area: bool,
+}
+
+impl MemoryLayout {
+    /// Size of the small font in bytes.
+    pub const FONT_SIZE: usize = font::SMALL_SIZE;
+
+    /// Whether both fonts fit in the interpreter area without overlapping, and programs
+    /// start within memory.
+    pub fn is_valid(&self) -> bool {
+        let small = self.font_addr as usize..self.font_addr as usize + Self::FONT_SIZE;
+        let large = self.large_font_addr as usize..self.large_font_addr as usize + font::LARGE_SIZE;
+        let program = self.program_addr as usize;
+
+        program < Cpu::MEMORY_SIZE && small.end <= program && large.end <= program
+            && (small.end <= large.start || large.end <= small.start)
+    }
+}
+
+impl Default for MemoryLayout {
+    fn default() -> Self {
+        Self {
+            program_addr: Cpu::INITIAL_ADDR,
+            font: FontSet::default(),
+            font_addr: 0x000,
+            large_font_addr: 0x080,
+            large_font_hex: true,
+            fill_interpreter_area: false,
+        }
     }
 }
 
@@ -35,289 +146,336 @@ pub struct Cpu {
     instructions: HashMap<&'static str, Instruction>,
     pub registers: [u8; 16],
     pub i_register: u16,
-    pub memory: [u8; 4 * 1024], // 4 KiB RAM
+    pub memory: Box<[u8; 4 * 1024]>, // 4 KiB RAM, boxed so that its address is stable
     pub pc: u16,
     pub stack: Vec<u16>,
     pub store_keypress: Option<usize>,
     pub last_keypress: Option<usize>,
     pub delay_timer: u8,
     pub sound_timer: u8,
+    /// Font placement, applied when the CPU is reset.
+    pub layout: MemoryLayout,
 }
 
 impl Cpu {
-    const INITIAL_ADDR: u16 = 0x200;
-
-    const DIGITS: [u8; 80] = [
-        0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
-        0x20, 0x60, 0x20, 0x20, 0x70, // 1
-        0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
-        0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
-        0x90, 0x90, 0xF0, 0x10, 0x10, // 4
-        0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
-        0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
-        0xF0, 0x10, 0x20, 0x40, 0x40, // 7
-        0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
-        0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
-        0xF0, 0x90, 0xF0, 0x90, 0x90, // A
-        0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
-        0xF0, 0x80, 0x80, 0x80, 0xF0, // C
-        0xE0, 0x90, 0x90, 0x90, 0xE0, // D
-        0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
-        0xF0, 0x80, 0xF0, 0x80, 0x80, // F
-    ];
-
-    const LARGE_DIGITS: [u8; 100] = [
-        0x3C, 0x7E, 0xE7, 0xC3, 0xC3, 0xC3, 0xC3, 0xE7, 0x7E, 0x3C, // 0
-        0x18, 0x38, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3C, // 1
-        0x3E, 0x7F, 0xC3, 0x06, 0x0C, 0x18, 0x30, 0x60, 0xFF, 0xFF, // 2
-        0x3C, 0x7E, 0xC3, 0x03, 0x0E, 0x0E, 0x03, 0xC3, 0x7E, 0x3C, // 3
-        0x06, 0x0E, 0x1E, 0x36, 0x66, 0xC6, 0xFF, 0xFF, 0x06, 0x06, // 4
-        0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFE, 0x03, 0xC3, 0x7E, 0x3C, // 5
-        0x3E, 0x7C, 0xC0, 0xC0, 0xFC, 0xFE, 0xC3, 0xC3, 0x7E, 0x3C, // 6
-        0xFF, 0xFF, 0x03, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x60, 0x60, // 7
-        0x3C, 0x7E, 0xC3, 0xC3, 0x7E, 0x7E, 0xC3, 0xC3, 0x7E, 0x3C, // 8
-        0x3C, 0x7E, 0xC3, 0xC3, 0x7F, 0x3F, 0x03, 0x03, 0x3E, 0x7C, // 9
-    ];
+    /// Default address programs are loaded at (see `MemoryLayout::program_addr`).
+    pub const INITIAL_ADDR: u16 = 0x200;
+    const MEMORY_SIZE: usize = 4 * 1024;
 
     /// Create and initialize a new CPU instance.
     pub fn new() -> Self {
-        let mut memory = [0; 4 * 1024];
-        memory[..80].clone_from_slice(&Self::DIGITS);
-        memory[Chip8Core::LARGE_DIGIT_OFFSET..Chip8Core::LARGE_DIGIT_OFFSET + 100].clone_from_slice(&Self::LARGE_DIGITS);
+        let layout = MemoryLayout::default();
+        let mut memory = Box::new([0; 4 * 1024]);
+        Self::load_fonts(memory.as_mut_slice(), layout);
 
         Self {
             instructions: Self::create_instructions(),
             registers: [0; 16],
             i_register: 0,
             memory,
-            pc: Self::INITIAL_ADDR,
+            pc: layout.program_addr,
             stack: Vec::with_capacity(64),
             store_keypress: None,
             last_keypress: None,
             delay_timer: 0,
             sound_timer: 0,
+            layout,
         }
     }
 
+    /// Restore the CPU to its initial state. The memory allocation is reused so that
+    /// pointers exposed to the frontend remain valid.
+    pub fn reset(&mut self) {
+        self.memory.fill(0);
+        Self::load_fonts(self.memory.as_mut_slice(), self.layout);
+
+        self.registers = [0; 16];
+        self.i_register = 0;
+        self.pc = self.layout.program_addr;
+        self.stack.clear();
+        self.store_keypress = None;
+        self.last_keypress = None;
+        self.delay_timer = 0;
+        self.sound_timer = 0;
+    }
+
+    fn load_fonts(memory: &mut [u8], layout: MemoryLayout) {
+        if layout.fill_interpreter_area {
+            for (i, byte) in memory[..layout.program_addr as usize].iter_mut().enumerate() {
+                *byte = (i as u8).wrapping_mul(0x9D) ^ 0x5B;
+            }
+        }
+
+        let small = layout.font_addr as usize;
+        let large = layout.large_font_addr as usize;
+        memory[small..small + font::SMALL_SIZE].clone_from_slice(layout.font.small());
+        let large_size = if layout.large_font_hex { font::LARGE_SIZE } else { font::LARGE_DECIMAL_SIZE };
+        memory[large..large + large_size].clone_from_slice(&layout.font.large()[..large_size]);
+    }
+
     fn create_instructions() -> HashMap<&'static str, Instruction> {
         let instructions = vec![
             Instruction {
                 name: "NOP",
+                pattern: "0NNN",
                 arg_masks: HashMap::new(),
                 callback: Chip8Core::nop,
             },
-            Instruction { // 00CN
+            Instruction {
                 name: "SCD",
+                pattern: "00CN",
                 arg_masks: HashMap::from([("N", Instruction::HEX_0)]),
                 callback: Chip8Core::scd,
             },
-            Instruction { // 00E0
+            Instruction {
                 name: "CLS",
+                pattern: "00E0",
                 arg_masks: HashMap::new(),
                 callback: Chip8Core::cls,
             },
-            Instruction { // 00EE
+            Instruction {
                 name: "RET",
+                pattern: "00EE",
                 arg_masks: HashMap::new(),
                 callback: Chip8Core::ret,
             },
-            Instruction { // 00FB
+            Instruction {
                 name: "SCR",
+                pattern: "00FB",
                 arg_masks: HashMap::new(),
                 callback: Chip8Core::scr,
             },
-            Instruction { // 00FC
+            Instruction {
                 name: "SCL",
+                pattern: "00FC",
                 arg_masks: HashMap::new(),
                 callback: Chip8Core::scl,
             },
-            Instruction { // 00FD
+            Instruction {
                 name: "EXIT",
+                pattern: "00FD",
                 arg_masks: HashMap::new(),
                 callback: Chip8Core::exit,
             },
-            Instruction { // 00FE
+            Instruction {
                 name: "LORES",
+                pattern: "00FE",
                 arg_masks: HashMap::new(),
                 callback: Chip8Core::lores,
             },
-            Instruction { // 00FF
+            Instruction {
                 name: "HIRES",
+                pattern: "00FF",
                 arg_masks: HashMap::new(),
                 callback: Chip8Core::hires,
             },
-            Instruction { // 1NNN
+            Instruction {
                 name: "JMP",
+                pattern: "1NNN",
                 arg_masks: HashMap::from([("N", Instruction::HEX_012)]),
                 callback: Chip8Core::jmp,
             },
-            Instruction { // 2NNN
+            Instruction {
                 name: "CALL",
+                pattern: "2NNN",
                 arg_masks: HashMap::from([("N", Instruction::HEX_012)]),
                 callback: Chip8Core::call,
             },
-            Instruction { // 3XNN
+            Instruction {
                 name: "SKPEQ",
+                pattern: "3XNN",
                 arg_masks: HashMap::from([("X", Instruction::HEX_2), ("N", Instruction::HEX_01)]),
                 callback: Chip8Core::skpeq,
             },
-            Instruction { // 4XNN
+            Instruction {
                 name: "SKPNE",
+                pattern: "4XNN",
                 arg_masks: HashMap::from([("X", Instruction::HEX_2), ("N", Instruction::HEX_01)]),
                 callback: Chip8Core::skpne,
             },
-            Instruction { // 5XY0
+            Instruction {
                 name: "SKPEQR",
+                pattern: "5XY0",
                 arg_masks: HashMap::from([("X", Instruction::HEX_2), ("Y", Instruction::HEX_1)]),
                 callback: Chip8Core::skpeqr,
             },
-            Instruction { // 6XNN
+            Instruction {
                 name: "MOV",
+                pattern: "6XNN",
                 arg_masks: HashMap::from([("X", Instruction::HEX_2), ("N", Instruction::HEX_01)]),
                 callback: Chip8Core::mov,
             },
-            Instruction { // 7XNN
+            Instruction {
                 name: "ADD",
+                pattern: "7XNN",
                 arg_masks: HashMap::from([("X", Instruction::HEX_2), ("N", Instruction::HEX_01)]),
                 callback: Chip8Core::add,
             },
-            Instruction { // 8XY0
+            Instruction {
                 name: "MOVR",
+                pattern: "8XY0",
                 arg_masks: HashMap::from([("X", Instruction::HEX_2), ("Y", Instruction::HEX_1)]),
                 callback: Chip8Core::movr,
             },
-            Instruction { // 8XY1
+            Instruction {
                 name: "OR",
+                pattern: "8XY1",
                 arg_masks: HashMap::from([("X", Instruction::HEX_2), ("Y", Instruction::HEX_1)]),
                 callback: Chip8Core::or,
             },
-            Instruction { // 8XY2
+            Instruction {
                 name: "AND",
+                pattern: "8XY2",
                 arg_masks: HashMap::from([("X", Instruction::HEX_2), ("Y", Instruction::HEX_1)]),
                 callback: Chip8Core::and,
             },
-            Instruction { // 8XY3
+            Instruction {
                 name: "XOR",
+                pattern: "8XY3",
                 arg_masks: HashMap::from([("X", Instruction::HEX_2), ("Y", Instruction::HEX_1)]),
                 callback: Chip8Core::xor,
             },
-            Instruction { // 8XY4
+            Instruction {
                 name: "ADDR",
+                pattern: "8XY4",
                 arg_masks: HashMap::from([("X", Instruction::HEX_2), ("Y", Instruction::HEX_1)]),
                 callback: Chip8Core::addr,
             },
-            Instruction { // 8XY5
+            Instruction {
                 name: "SUBR",
+                pattern: "8XY5",
                 arg_masks: HashMap::from([("X", Instruction::HEX_2), ("Y", Instruction::HEX_1)]),
                 callback: Chip8Core::subr,
             },
-            Instruction { // 8XY6
+            Instruction {
                 name: "SHR",
+                pattern: "8XY6",
                 arg_masks: HashMap::from([("X", Instruction::HEX_2), ("Y", Instruction::HEX_1)]),
                 callback: Chip8Core::shr,
             },
-            Instruction { // 8XY7
+            Instruction {
                 name: "RSUBR",
+                pattern: "8XY7",
                 arg_masks: HashMap::from([("X", Instruction::HEX_2), ("Y", Instruction::HEX_1)]),
                 callback: Chip8Core::rsubr,
             },
-            Instruction { // 8XYE
+            Instruction {
                 name: "SHL",
+                pattern: "8XYE",
                 arg_masks: HashMap::from([("X", Instruction::HEX_2), ("Y", Instruction::HEX_1)]),
                 callback: Chip8Core::shl,
             },
-            Instruction { // 9XY0
+            Instruction {
                 name: "SKPNER",
+                pattern: "9XY0",
                 arg_masks: HashMap::from([("X", Instruction::HEX_2), ("Y", Instruction::HEX_1)]),
                 callback: Chip8Core::skpner,
             },
-            Instruction { // ANNN
+            Instruction {
                 name: "MOVI",
+                pattern: "ANNN",
                 arg_masks: HashMap::from([("N", Instruction::HEX_012)]),
                 callback: Chip8Core::movi,
             },
-            Instruction { // BNNN
+            Instruction {
                 name: "JMPR",
+                pattern: "BNNN",
                 arg_masks: HashMap::from([("N", Instruction::HEX_012)]),
                 callback: Chip8Core::jmpr,
             },
-            Instruction { // CXNN
+            Instruction {
                 name: "RAND",
+                pattern: "CXNN",
                 arg_masks: HashMap::from([("X", Instruction::HEX_2), ("N", Instruction::HEX_01)]),
                 callback: Chip8Core::rand,
             },
-            Instruction { // DXYN
+            Instruction {
                 name: "DRAW",
+                pattern: "DXYN",
                 arg_masks: HashMap::from([("X", Instruction::HEX_2), ("Y", Instruction::HEX_1), ("N", Instruction::HEX_0)]),
                 callback: Chip8Core::draw,
             },
-            Instruction { // EX9E
+            Instruction {
                 name: "SKPK",
+                pattern: "EX9E",
                 arg_masks: HashMap::from([("X", Instruction::HEX_2)]),
                 callback: Chip8Core::skpk,
             },
-            Instruction { // EXA1
+            Instruction {
                 name: "SKPNK",
+                pattern: "EXA1",
                 arg_masks: HashMap::from([("X", Instruction::HEX_2)]),
                 callback: Chip8Core::skpnk,
             },
-            Instruction { // FX0A
+            Instruction {
                 name: "KEY",
+                pattern: "FX0A",
                 arg_masks: HashMap::from([("X", Instruction::HEX_2)]),
                 callback: Chip8Core::key,
             },
-            Instruction { // FX07
+            Instruction {
                 name: "TIMR",
+                pattern: "FX07",
                 arg_masks: HashMap::from([("X", Instruction::HEX_2)]),
                 callback: Chip8Core::timr,
             },
-            Instruction { // FX15
+            Instruction {
                 name: "DELR",
+                pattern: "FX15",
                 arg_masks: HashMap::from([("X", Instruction::HEX_2)]),
                 callback: Chip8Core::delr,
             },
-            Instruction { // FX29
+            Instruction {
                 name: "DIGIT",
+                pattern: "FX29",
                 arg_masks: HashMap::from([("X", Instruction::HEX_2)]),
                 callback: Chip8Core::digit,
             },
             Instruction {
                 name: "LDIGIT",
+                pattern: "FX30",
                 arg_masks: HashMap::from([("X", Instruction::HEX_2)]),
                 callback: Chip8Core::ldigit,
             },
-            Instruction { // FX18
+            Instruction {
                 name: "SNDR",
+                pattern: "FX18",
                 arg_masks: HashMap::from([("X", Instruction::HEX_2)]),
                 callback: Chip8Core::sndr,
             },
-            Instruction { // FX1E
+            Instruction {
                 name: "ADDI",
+                pattern: "FX1E",
                 arg_masks: HashMap::from([("X", Instruction::HEX_2)]),
                 callback: Chip8Core::addi,
             },
-            Instruction { // FX33
+            Instruction {
                 name: "BCD",
+                pattern: "FX33",
                 arg_masks: HashMap::from([("X", Instruction::HEX_2)]),
                 callback: Chip8Core::bcd,
             },
-            Instruction { // FX55
+            Instruction {
                 name: "SAVE",
+                pattern: "FX55",
                 arg_masks: HashMap::from([("X", Instruction::HEX_2)]),
                 callback: Chip8Core::save,
             },
-            Instruction { // FX65
+            Instruction {
                 name: "LOAD",
+                pattern: "FX65",
                 arg_masks: HashMap::from([("X", Instruction::HEX_2)]),
                 callback: Chip8Core::load,
             },
-            Instruction { // FX75
+            Instruction {
                 name: "SAVEF",
+                pattern: "FX75",
                 arg_masks: HashMap::from([("X", Instruction::HEX_2)]),
                 callback: Chip8Core::savef,
             },
-            Instruction { // FX85
+            Instruction {
                 name: "LOADF",
+                pattern: "FX85",
                 arg_masks: HashMap::from([("X", Instruction::HEX_2)]),
                 callback: Chip8Core::loadf,
             },
@@ -326,6 +484,13 @@ impl Cpu {
         instructions.into_iter().map(|i| (i.name, i)).collect()
     }
 
+    /// All instructions, ordered by opcode pattern.
+    pub fn instructions(&self) -> Vec<&Instruction> {
+        let mut instructions: Vec<&Instruction> = self.instructions.values().collect();
+        instructions.sort_by_key(|i| i.pattern);
+        instructions
+    }
+
     fn instruction(&self, name: &str) -> &Instruction {
         self.instructions.get(name).unwrap()
     }
@@ -341,7 +506,7 @@ impl Cpu {
     pub fn load_program(&mut self, data: &[u8]) {
         // TODO: change return type to signal an error when program is too large.
 
-        let addr = Self::INITIAL_ADDR as usize;
+        let addr = self.layout.program_addr as usize;
         let program_size = data.len();
 
         if program_size <= self.memory.len() - addr {
@@ -379,7 +544,8 @@ impl Cpu {
             0x2000 => self.instruction("CALL"),
             0x3000 => self.instruction("SKPEQ"),
             0x4000 => self.instruction("SKPNE"),
-            0x5000 => self.instruction("SKPEQR"),
+            // The low nibble must be 0. Other forms are data or XO-CHIP instructions.
+            0x5000 if instruction & 0x000F == 0 => self.instruction("SKPEQR"),
             0x6000 => self.instruction("MOV"),
             0x7000 => self.instruction("ADD"),
             0x8000 => match instruction & 0x000F {
@@ -394,7 +560,7 @@ impl Cpu {
                 0x000E => self.instruction("SHL"),
                 _ => nop,
             },
-            0x9000 => self.instruction("SKPNER"),
+            0x9000 if instruction & 0x000F == 0 => self.instruction("SKPNER"),
             0xA000 => self.instruction("MOVI"),
             0xB000 => self.instruction("JMPR"),
             0xC000 => self.instruction("RAND"),
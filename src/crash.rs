@@ -0,0 +1,111 @@
+use std::fmt::Write;
+
+use crate::cpu::Cpu;
+use crate::snapshot::MemorySnapshot;
+
+/// Addresses and opcodes of the last instructions executed, kept for crash dumps.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct History {
+    entries: [(u16, u16); Self::LEN],
+    next: usize,
+    len: usize,
+}
+
+impl History {
+    /// Number of instructions kept.
+    pub const LEN: usize = 32;
+
+    pub(crate) fn record(&mut self, addr: u16, opcode: u16) {
+        self.entries[self.next] = (addr, opcode);
+        self.next = (self.next + 1) % Self::LEN;
+        self.len = (self.len + 1).min(Self::LEN);
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    /// Recorded instructions as `(address, opcode)`, oldest first.
+    pub fn entries(&self) -> impl Iterator<Item = (u16, u16)> + '_ {
+        (0..self.len).map(|i| self.entries[(self.next + Self::LEN - self.len + i) % Self::LEN])
+    }
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self { entries: [(0, 0); Self::LEN], next: 0, len: 0 }
+    }
+}
+
+/// Instructions shown on each side of the failing instruction.
+const CONTEXT: u16 = 8;
+
+fn disassemble(cpu: &Cpu, addr: u16, opcode: u16) -> String {
+    let instruction = cpu.decode_instruction(opcode);
+    let mut args: Vec<_> = instruction.args(opcode).iter().collect();
+    args.sort();
+
+    let args: Vec<String> = args.iter().map(|(name, value)| format!("{} = 0x{:X}", name, value)).collect();
+    format!("0x{:03X}  {:04X}  {} [{}]", addr, opcode, instruction.name(), args.join(", "))
+}
+
+/// Text report of the state of the core when emulation stopped on a fatal error: the
+/// reason, the registers and stack, the last instructions executed and the disassembly
+/// around the failing instruction (the one before `PC`), marked with `>`. `snapshot`
+/// names the file holding the full memory snapshot, if one was written.
+pub fn report(reason: &str, state: &MemorySnapshot, history: &History, snapshot: Option<&str>) -> String {
+    let cpu = Cpu::new();
+    let failing = state.pc.wrapping_sub(2);
+    let opcode_at = |addr: u16| {
+        let addr = addr as usize;
+        state.memory.get(addr..addr + 2).map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+    };
+
+    let mut text = format!("oxid-8 crash dump\n\nReason: {}\n", reason);
+    if let Some((platform, quirks)) = state.config {
+        let _ = writeln!(text, "Platform: {} (quirks 0x{:02X})", platform.name(), quirks.to_bits());
+    }
+    if let Some(path) = snapshot {
+        let _ = writeln!(text, "Snapshot: {}", path);
+    }
+
+    let registers: Vec<String> = state.registers.iter().enumerate().map(|(x, v)| format!("V{:X}={:02X}", x, v)).collect();
+    let stack: Vec<String> = state.stack.iter().map(|addr| format!("0x{:03X}", addr)).collect();
+    let _ = writeln!(text, "\nPC=0x{:03X} I=0x{:03X} DT={} ST={}\n{}\nStack: [{}]",
+        state.pc, state.i_register, state.delay_timer, state.sound_timer, registers.join(" "), stack.join(", "));
+
+    text += "\nLast instructions executed:\n";
+    for (addr, opcode) in history.entries() {
+        let _ = writeln!(text, "  {}", disassemble(&cpu, addr, opcode));
+    }
+
+    text += "\nDisassembly:\n";
+    for addr in (failing.saturating_sub(2 * CONTEXT)..=failing.saturating_add(2 * CONTEXT)).step_by(2) {
+        if let Some(opcode) = opcode_at(addr) {
+            let marker = if addr == failing { '>' } else { ' ' };
+            let _ = writeln!(text, "{} {}", marker, disassemble(&cpu, addr, opcode));
+        }
+    }
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn history() {
+        let mut history = History::default();
+        for addr in 0..40 {
+            history.record(0x200 + 2 * addr, addr);
+        }
+
+        let entries: Vec<_> = history.entries().collect();
+        assert_eq!(entries.len(), History::LEN);
+        assert_eq!((entries[0], entries[31]), ((0x210, 8), (0x24E, 39)));
+
+        history.clear();
+        assert_eq!(history.entries().count(), 0);
+    }
+}
@@ -0,0 +1,112 @@
+use std::{collections::HashMap, fmt::Write, fs, io, path::Path};
+
+use crate::platform::Platform;
+
+/// Known program, identified by the SHA-1 hash of its contents.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DatabaseEntry {
+    pub name: String,
+    pub platform: Platform,
+}
+
+/// Database of known programs, used to select the platform of a program automatically.
+/// It can be generated from a directory of ROMs with the `chip8-scan` tool.
+///
+/// The file contains one program per line, with tab-separated fields: the SHA-1 hash of
+/// the program in hexadecimal, the platform name and the program name. Lines starting
+/// with `#` are ignored.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Database {
+    entries: HashMap<[u8; 20], DatabaseEntry>,
+}
+
+impl Database {
+    /// Name of the database file, looked for in the frontend's system directory.
+    pub const FILE_NAME: &'static str = "oxid8.db";
+
+    /// Read a database from a file. A missing file yields an empty database.
+    pub fn load(path: &Path) -> Self {
+        fs::read_to_string(path).map(|text| Self::parse(&text)).unwrap_or_default()
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.to_text())
+    }
+
+    /// Parse a database, skipping malformed lines.
+    pub fn parse(text: &str) -> Self {
+        let entries = text.lines()
+            .filter(|line| !line.starts_with('#'))
+            .filter_map(|line| {
+                let mut fields = line.splitn(3, '\t');
+                let hash = parse_hash(fields.next()?)?;
+                let platform = Platform::from_name(fields.next()?)?;
+                let name = fields.next().unwrap_or_default().to_string();
+                Some((hash, DatabaseEntry { name, platform }))
+            })
+            .collect();
+
+        Self { entries }
+    }
+
+    /// Serialize the database, sorted by program name.
+    pub fn to_text(&self) -> String {
+        let mut entries: Vec<_> = self.entries.iter().collect();
+        entries.sort_by(|a, b| (&a.1.name, a.0).cmp(&(&b.1.name, b.0)));
+
+        let mut text = String::new();
+        for (hash, entry) in entries {
+            let _ = writeln!(text, "{}\t{}\t{}", hash_hex(hash), entry.platform.name(), entry.name);
+        }
+        text
+    }
+
+    pub fn get(&self, hash: &[u8; 20]) -> Option<&DatabaseEntry> {
+        self.entries.get(hash)
+    }
+
+    pub fn insert(&mut self, hash: [u8; 20], entry: DatabaseEntry) {
+        self.entries.insert(hash, entry);
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// Format a SHA-1 hash as lowercase hexadecimal.
+pub fn hash_hex(hash: &[u8; 20]) -> String {
+    hash.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn parse_hash(hex: &str) -> Option<[u8; 20]> {
+    if hex.len() != 40 || !hex.is_ascii() {
+        return None;
+    }
+
+    let mut hash = [0; 20];
+    for (i, byte) in hash.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).ok()?;
+    }
+    Some(hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_roundtrip() {
+        let mut database = Database::default();
+        database.insert([0xAB; 20], DatabaseEntry { name: "Space Invaders".to_string(), platform: Platform::Chip8 });
+        database.insert([0x01; 20], DatabaseEntry { name: "Ant".to_string(), platform: Platform::SuperChip });
+
+        let text = database.to_text();
+        assert!(text.starts_with("0101"));
+        assert_eq!(Database::parse(&("# comment\nbogus\n".to_string() + &text)), database);
+    }
+}
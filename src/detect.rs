@@ -0,0 +1,193 @@
+use crate::platform::Platform;
+
+/// Instruction set extension an instruction belongs to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Extension {
+    SuperChip,
+    /// XO-CHIP, which is not supported. Programs using it are run as SUPER-CHIP, the
+    /// closest supported platform.
+    XoChip,
+}
+
+/// Instruction found in a program that is not part of the original CHIP-8 instruction set.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Evidence {
+    /// Address of the instruction, assuming the program is loaded at `0x200`.
+    pub address: u16,
+    pub opcode: u16,
+    pub name: &'static str,
+    pub extension: Extension,
+    /// Weak evidence is only considered alongside other evidence, since it is also a
+    /// valid CHIP-8 instruction or a common byte pattern in sprites.
+    pub weak: bool,
+}
+
+/// Result of a static scan of a program for platform-specific instructions.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Detection {
+    pub platform: Platform,
+    /// Most advanced extension found, if any.
+    pub extension: Option<Extension>,
+    pub evidence: Vec<Evidence>,
+}
+
+impl Detection {
+    const SUMMARY_EVIDENCE: usize = 4;
+
+    /// Human-readable explanation of the detected platform, for logging.
+    pub fn summary(&self) -> String {
+        let Some(extension) = self.extension else {
+            return format!("platform {}: no extended instructions found", self.platform.name());
+        };
+
+        let listed: Vec<String> = self.evidence.iter()
+            .filter(|e| !e.weak)
+            .take(Self::SUMMARY_EVIDENCE)
+            .map(|e| format!("{} ({:04X}) at 0x{:03X}", e.name, e.opcode, e.address))
+            .collect();
+        let more = self.evidence.len() - listed.len();
+
+        let mut summary = format!("platform {}: found {}", self.platform.name(), listed.join(", "));
+        if more > 0 {
+            summary += &format!(" and {} more", more);
+        }
+        if extension == Extension::XoChip {
+            summary += "; XO-CHIP instructions are not supported";
+        }
+        summary
+    }
+}
+
+/// Warning for a program that uses instructions the selected platform does not support,
+/// suggesting a better platform where possible.
+pub fn compatibility_warning(platform: Platform, detection: &Detection) -> Option<String> {
+    match detection.extension? {
+        Extension::XoChip =>
+            Some("program uses XO-CHIP instructions, which are not supported and will be ignored".to_string()),
+        Extension::SuperChip if platform == Platform::Chip8 =>
+            Some(format!("program uses SUPER-CHIP instructions, try platform=schip ({})", detection.summary())),
+        Extension::SuperChip => None,
+    }
+}
+
+/// Explanation for an instruction that the selected platform ignores, naming the
+/// extension it belongs to and suggesting a platform that supports it where possible.
+pub fn instruction_hint(platform: Platform, opcode: u16) -> Option<String> {
+    let (name, extension, _) = extended_instruction(opcode)?;
+    match extension {
+        Extension::XoChip => Some(format!("{} is an XO-CHIP instruction, which is not supported", name)),
+        Extension::SuperChip if platform == Platform::Chip8 => Some(format!("{} is a SUPER-CHIP instruction, try platform=schip", name)),
+        Extension::SuperChip => None,
+    }
+}
+
+/// Name and extension of an instruction not available on CHIP-8, if the opcode is one.
+/// The flag is set for weak evidence.
+fn extended_instruction(opcode: u16) -> Option<(&'static str, Extension, bool)> {
+    use Extension::*;
+
+    let instruction = match opcode {
+        0x00C0..=0x00CF => ("SCD", SuperChip, false),
+        0x00D0..=0x00DF => ("SCU", XoChip, false),
+        0x00FB => ("SCR", SuperChip, false),
+        0x00FC => ("SCL", SuperChip, false),
+        0x00FD => ("EXIT", SuperChip, false),
+        0x00FE => ("LORES", SuperChip, false),
+        0x00FF => ("HIRES", SuperChip, false),
+        0xF000 => ("LONG", XoChip, false),
+        0xF002 => ("AUDIO", XoChip, false),
+        _ => match opcode & 0xF00F {
+            0x5002 => ("SAVER", XoChip, true),
+            0x5003 => ("LOADR", XoChip, true),
+            0xD000 => ("DRAW", SuperChip, true),
+            _ => match opcode & 0xF0FF {
+                0xF001 => ("PLANE", XoChip, false),
+                0xF030 => ("LDIGIT", SuperChip, false),
+                0xF03A => ("PITCH", XoChip, false),
+                0xF075 => ("SAVEF", SuperChip, false),
+                0xF085 => ("LOADF", SuperChip, false),
+                _ => return None,
+            },
+        },
+    };
+
+    Some(instruction)
+}
+
+/// Extension an opcode belongs to, or `None` for original CHIP-8 instructions. Opcodes
+/// that are only weak evidence of an extension (e.g. `DXY0`) are also reported.
+pub fn instruction_extension(opcode: u16) -> Option<Extension> {
+    extended_instruction(opcode).map(|(_, extension, _)| extension)
+}
+
+/// Guess the platform a program was written for from the instructions it contains.
+///
+/// Every aligned pair of bytes is treated as an instruction, so sprite data can produce
+/// false positives. Weak evidence, such as `DXY0`, only counts if strong evidence is
+/// also found.
+pub fn detect_platform(program: &[u8]) -> Detection {
+    let evidence: Vec<Evidence> = program.chunks_exact(2)
+        .enumerate()
+        .filter_map(|(i, pair)| {
+            let opcode = u16::from_be_bytes([pair[0], pair[1]]);
+            extended_instruction(opcode).map(|(name, extension, weak)| Evidence {
+                address: 0x200 + 2 * i as u16, opcode, name, extension, weak,
+            })
+        })
+        .collect();
+
+    let extension = if evidence.iter().any(|e| !e.weak) {
+        evidence.iter().map(|e| e.extension).max()
+    }
+    else {
+        None
+    };
+
+    let platform = if extension.is_some() { Platform::SuperChip } else { Platform::Chip8 };
+
+    Detection { platform, extension, evidence }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect() {
+        // CLS, HIRES, JMP 0x204
+        let detection = detect_platform(&[0x00, 0xE0, 0x00, 0xFF, 0x12, 0x04]);
+
+        assert_eq!(detection.platform, Platform::SuperChip);
+        assert_eq!(detection.extension, Some(Extension::SuperChip));
+        assert_eq!(detection.evidence.len(), 1);
+        assert_eq!(detection.evidence[0].address, 0x202);
+        assert_eq!(detection.evidence[0].name, "HIRES");
+
+        // CLS, DRAW V0, V1, 0
+        let detection = detect_platform(&[0x00, 0xE0, 0xD0, 0x10]);
+        assert_eq!(detection.platform, Platform::Chip8);
+        assert_eq!(detection.extension, None);
+
+        // PLANE 1, DRAW V0, V1, 0
+        let detection = detect_platform(&[0xF1, 0x01, 0xD0, 0x10]);
+        assert_eq!(detection.extension, Some(Extension::XoChip));
+        assert!(detection.summary().contains("PLANE (F101) at 0x200 and 1 more"));
+    }
+
+    #[test]
+    fn warning() {
+        let detection = detect_platform(&[0x00, 0xFF]);
+
+        assert!(compatibility_warning(Platform::Chip8, &detection).is_some());
+        assert!(compatibility_warning(Platform::SuperChip, &detection).is_none());
+        assert!(compatibility_warning(Platform::Chip8, &detect_platform(&[0x00, 0xE0])).is_none());
+    }
+
+    #[test]
+    fn hint() {
+        assert_eq!(instruction_hint(Platform::Chip8, 0xF330).as_deref(), Some("LDIGIT is a SUPER-CHIP instruction, try platform=schip"));
+        assert_eq!(instruction_hint(Platform::SuperChip, 0xF330), None);
+        assert_eq!(instruction_hint(Platform::SuperChip, 0xF101).as_deref(), Some("PLANE is an XO-CHIP instruction, which is not supported"));
+        assert_eq!(instruction_hint(Platform::Chip8, 0xE000), None);
+    }
+}
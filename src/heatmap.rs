@@ -0,0 +1,61 @@
+/// Number of reads and writes of each memory address made by instructions, for spotting
+/// variables, sprite tables and buffers in unknown programs. Instruction fetches are not
+/// counted (see `Coverage` for executed code).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MemoryHeatmap {
+    reads: Vec<u64>,
+    writes: Vec<u64>,
+}
+
+impl MemoryHeatmap {
+    pub fn new(size: usize) -> Self {
+        Self { reads: vec![0; size], writes: vec![0; size] }
+    }
+
+    pub(crate) fn record_read(&mut self, addr: usize) {
+        let size = self.reads.len();
+        self.reads[addr % size] += 1;
+    }
+
+    pub(crate) fn record_write(&mut self, addr: usize) {
+        let size = self.writes.len();
+        self.writes[addr % size] += 1;
+    }
+
+    pub fn reads(&self, addr: usize) -> u64 {
+        self.reads[addr]
+    }
+
+    pub fn writes(&self, addr: usize) -> u64 {
+        self.writes[addr]
+    }
+
+    /// Heatmap as CSV, with a row per address that was accessed at least once.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("address,reads,writes\n");
+
+        for (addr, (reads, writes)) in self.reads.iter().zip(&self.writes).enumerate() {
+            if reads + writes > 0 {
+                csv += &format!("0x{:03X},{},{}\n", addr, reads, writes);
+            }
+        }
+
+        csv
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn to_csv() {
+        let mut heatmap = MemoryHeatmap::new(16);
+        heatmap.record_read(0x3);
+        heatmap.record_read(0x13);
+        heatmap.record_write(0xA);
+
+        assert_eq!(heatmap.reads(0x3), 2);
+        assert_eq!(heatmap.to_csv(), "address,reads,writes\n0x003,2,0\n0x00A,0,1\n");
+    }
+}
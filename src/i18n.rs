@@ -0,0 +1,87 @@
+/// Language of the core option labels shown by the frontend. The discriminants are the
+/// libretro language identifiers (`RETRO_LANGUAGE_*`).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Language {
+    #[default]
+    English = 0,
+    Japanese = 1,
+    French = 2,
+    Spanish = 3,
+    German = 4,
+    Italian = 5,
+    Dutch = 6,
+    PortugueseBrazil = 7,
+    PortuguesePortugal = 8,
+    Russian = 9,
+}
+
+impl Language {
+    /// Language with a libretro language identifier, if labels can be provided for it.
+    pub fn from_id(id: u32) -> Option<Self> {
+        [
+            Self::English, Self::Japanese, Self::French, Self::Spanish, Self::German,
+            Self::Italian, Self::Dutch, Self::PortugueseBrazil, Self::PortuguesePortugal, Self::Russian,
+        ].into_iter().find(|&language| language as u32 == id)
+    }
+
+    /// Language from its code, as used by the `lang` argument (e.g. `fr` or `pt_BR`).
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "en" => Some(Self::English),
+            "ja" => Some(Self::Japanese),
+            "fr" => Some(Self::French),
+            "es" => Some(Self::Spanish),
+            "de" => Some(Self::German),
+            "it" => Some(Self::Italian),
+            "nl" => Some(Self::Dutch),
+            "pt_BR" => Some(Self::PortugueseBrazil),
+            "pt" | "pt_PT" => Some(Self::PortuguesePortugal),
+            "ru" => Some(Self::Russian),
+            _ => None,
+        }
+    }
+}
+
+/// Translated core option definitions, by language. Each entry replaces the English
+/// definition of an option in `CoreOptions::DEFINITIONS` with the same key, and must
+/// keep its values, which are parsed by the core, unchanged: only the label before `;`
+/// is translated. Options missing from a language keep their English definition.
+const TRANSLATIONS: &[(Language, &[(&str, &str)])] = &[];
+
+/// Definition of a core option in a language, if it was translated.
+pub fn translation(language: Language, key: &str) -> Option<&'static str> {
+    TRANSLATIONS.iter()
+        .filter(|(l, _)| *l == language)
+        .flat_map(|(_, definitions)| definitions.iter())
+        .find(|(k, _)| *k == key)
+        .map(|&(_, definition)| definition)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::CoreOptions;
+
+    fn values(definition: &str) -> &str {
+        definition.split_once("; ").map_or("", |(_, values)| values)
+    }
+
+    #[test]
+    fn translations_keep_values() {
+        for (language, definitions) in TRANSLATIONS {
+            for (key, definition) in definitions.iter() {
+                let english = CoreOptions::DEFINITIONS.iter().find(|(k, _)| k == key)
+                    .unwrap_or_else(|| panic!("{:?} translation of unknown option {}", language, key)).1;
+                assert_eq!(values(definition), values(english), "{:?} translation of {}", language, key);
+            }
+        }
+    }
+
+    #[test]
+    fn language() {
+        assert_eq!(Language::from_id(7), Some(Language::PortugueseBrazil));
+        assert_eq!(Language::from_id(99), None);
+        assert_eq!(Language::from_name("pt"), Some(Language::PortuguesePortugal));
+        assert_eq!(translation(Language::French, "oxid8_platform"), None);
+    }
+}
@@ -1,6 +1,9 @@
+use std::fmt;
 
+use strum::IntoEnumIterator;
 use strum_macros::EnumIter;
 
+/// Default keyboard key of each CHIP-8 key, in keypad order, as libretro key codes.
 #[derive(EnumIter)]
 pub enum Chip8Key {
     X    = 120,
@@ -20,3 +23,392 @@ pub enum Chip8Key {
     F    = 102,
     V    = 118,
 }
+
+/// State of the 16-key hexadecimal keypad, where bit `K` is set if key `K` is pressed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct KeypadState(pub u16);
+
+impl KeypadState {
+    pub fn is_pressed(&self, key: usize) -> bool {
+        self.0 & (1 << key) != 0
+    }
+
+    pub fn set(&mut self, key: usize, pressed: bool) {
+        if pressed {
+            self.0 |= 1 << key;
+        }
+        else {
+            self.0 &= !(1 << key);
+        }
+    }
+}
+
+/// Keypad state driven by key press and release events, for frontends that report
+/// keyboard events instead of (or in addition to) being polled once per frame.
+///
+/// Presses shorter than a frame are latched, so that the next frame still sees the key
+/// as pressed. The order in which keys were pressed and released is also tracked.
+#[derive(Clone, Debug, Default)]
+pub struct KeyTracker {
+    state: KeypadState,
+    latched: KeypadState,
+    previous: KeypadState,
+    press_order: [u64; 16],
+    release_order: [u64; 16],
+    sequence: u64,
+}
+
+impl KeyTracker {
+    /// Record a key press or release.
+    pub fn key_event(&mut self, key: usize, pressed: bool) {
+        if pressed && !self.state.is_pressed(key) {
+            self.record_press(key);
+            self.latched.set(key, true);
+        }
+        else if !pressed && self.state.is_pressed(key) {
+            self.record_release(key);
+        }
+        self.state.set(key, pressed);
+    }
+
+    /// Keypad state for the next frame: keys held according to events or polling, plus
+    /// keys pressed and released since the previous frame. Keys that became pressed or
+    /// released through polling are ordered after those reported by events.
+    pub fn frame_state(&mut self, polled: KeypadState) -> KeypadState {
+        let state = KeypadState(self.state.0 | self.latched.0 | polled.0);
+
+        for key in 0..16 {
+            if polled.is_pressed(key) && !self.previous.is_pressed(key) && !self.latched.is_pressed(key) {
+                self.record_press(key);
+            }
+            else if !state.is_pressed(key) && self.previous.is_pressed(key) && self.release_order[key] < self.press_order[key] {
+                self.record_release(key);
+            }
+        }
+
+        self.latched = KeypadState::default();
+        self.previous = state;
+        state
+    }
+
+    /// Sequence number of the latest press of a key. Higher values are more recent.
+    pub fn press_order(&self, key: usize) -> u64 {
+        self.press_order[key]
+    }
+
+    /// Sequence number of the latest release of a key. Higher values are more recent.
+    pub fn release_order(&self, key: usize) -> u64 {
+        self.release_order[key]
+    }
+
+    fn record_press(&mut self, key: usize) {
+        self.sequence += 1;
+        self.press_order[key] = self.sequence;
+    }
+
+    fn record_release(&mut self, key: usize) {
+        self.sequence += 1;
+        self.release_order[key] = self.sequence;
+    }
+}
+
+/// Names of keyboard keys that are not a single character, with their libretro key
+/// codes (`RETROK_*`). Keys that are a single character are named by it, and their codes
+/// are the ASCII codes of the lowercase characters.
+const KEY_NAMES: &[(&str, u32)] = &[
+    ("backspace", 8), ("tab", 9), ("enter", 13), ("escape", 27), ("space", 32),
+    ("kp0", 256), ("kp1", 257), ("kp2", 258), ("kp3", 259), ("kp4", 260),
+    ("kp5", 261), ("kp6", 262), ("kp7", 263), ("kp8", 264), ("kp9", 265),
+    ("kp_period", 266), ("kp_divide", 267), ("kp_multiply", 268), ("kp_minus", 269), ("kp_plus", 270), ("kp_enter", 271),
+    ("up", 273), ("down", 274), ("right", 275), ("left", 276),
+    ("insert", 277), ("home", 278), ("end", 279), ("pageup", 280), ("pagedown", 281),
+    ("f1", 282), ("f2", 283), ("f3", 284), ("f4", 285), ("f5", 286), ("f6", 287),
+    ("f7", 288), ("f8", 289), ("f9", 290), ("f10", 291), ("f11", 292), ("f12", 293),
+    ("scroll_lock", 302), ("rshift", 303), ("lshift", 304), ("rctrl", 305), ("lctrl", 306), ("ralt", 307), ("lalt", 308),
+];
+
+/// Keyboard hotkeys of RetroArch in its default configuration, with what they do. The
+/// frontend acts on these keys even while they are also bound to the keypad, unless
+/// game focus mode is enabled.
+const FRONTEND_HOTKEYS: &[(u32, &str)] = &[
+    (27, "quit"), (282, "menu"), (283, "save state"), (284, "FPS display"), (285, "load state"),
+    (287, "previous state slot"), (288, "next state slot"), (289, "screenshot"), (290, "mute"),
+    (292, "mouse grab"), (293, "on-screen keyboard"), (32, "fast-forward toggle"), (108, "fast-forward"),
+    (101, "slow motion"), (114, "rewind"), (112, "pause"), (107, "frame advance"), (104, "reset"),
+    (102, "fullscreen"), (109, "next shader"), (110, "previous shader"), (111, "movie recording"),
+    (105, "netplay spectate"), (116, "previous cheat"), (121, "next cheat"), (117, "cheat toggle"),
+    (270, "volume up"), (269, "volume down"), (302, "game focus"),
+];
+
+/// Libretro key code of a keyboard key, by name (e.g. `w`, `5`, `up` or `kp8`).
+pub fn keycode(name: &str) -> Option<u32> {
+    let name = name.to_ascii_lowercase();
+    let mut chars = name.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if c.is_ascii_graphic() => Some(c as u32),
+        _ => KEY_NAMES.iter().find(|&&(n, _)| n == name).map(|&(_, code)| code),
+    }
+}
+
+/// Name of a keyboard key, as accepted by `keycode`.
+pub fn key_name(keycode: u32) -> String {
+    match KEY_NAMES.iter().find(|&&(_, code)| code == keycode) {
+        Some((name, _)) => name.to_string(),
+        None => char::from_u32(keycode).filter(char::is_ascii_graphic).map_or_else(|| format!("#{}", keycode), String::from),
+    }
+}
+
+/// Problem with a keyboard mapping, found by `KeyMap::conflicts`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum KeyConflict {
+    /// A keyboard key is bound to several CHIP-8 keys, which it presses together.
+    Duplicate { keycode: u32, keys: Vec<u8> },
+    /// A keyboard key bound by the user is a default hotkey of the frontend, which may
+    /// act on it as well as (or instead of) the program.
+    Hotkey { keycode: u32, key: u8, action: &'static str },
+}
+
+impl fmt::Display for KeyConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Duplicate { keycode, keys } => {
+                let keys: Vec<String> = keys.iter().map(|k| format!("{:X}", k)).collect();
+                write!(f, "keyboard key {} is bound to CHIP-8 keys {}", key_name(*keycode), keys.join(", "))
+            },
+            Self::Hotkey { keycode, key, action } =>
+                write!(f, "keyboard key {} (CHIP-8 key {:X}) is the frontend's default {} hotkey", key_name(*keycode), key, action),
+        }
+    }
+}
+
+/// Keyboard keys bound to each CHIP-8 key, which is pressed while any of them is held,
+/// so that alternate layouts (e.g. the arrow keys and the numeric keypad) can be used
+/// side by side. Keys not bound by the user keep the default layout of `Chip8Key`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeyMap {
+    /// Libretro key codes bound to each key, with 0 (`RETROK_UNKNOWN`) for unused slots.
+    keycodes: [[u32; Self::MAX_BINDINGS]; 16],
+    /// Keys bound by the user, where bit `K` is set if key `K` was bound.
+    custom: KeypadState,
+}
+
+impl KeyMap {
+    /// Maximum number of keyboard keys bound to a CHIP-8 key.
+    pub const MAX_BINDINGS: usize = 4;
+
+    /// Bind a keyboard key (a libretro key code) to a CHIP-8 key. The first key bound by
+    /// the user replaces the default one, and further keys are added as alternates.
+    /// Returns `false` if the key already has `MAX_BINDINGS` keys bound.
+    pub fn bind(&mut self, key: u8, keycode: u32) -> bool {
+        let key = key as usize;
+        if !self.custom.is_pressed(key) {
+            self.keycodes[key] = [0; Self::MAX_BINDINGS];
+            self.custom.set(key, true);
+        }
+
+        let bindings = &mut self.keycodes[key];
+        if bindings.contains(&keycode) {
+            return true;
+        }
+        match bindings.iter_mut().find(|code| **code == 0) {
+            Some(slot) => {
+                *slot = keycode;
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Keyboard keys bound to a CHIP-8 key.
+    pub fn keycodes(&self, key: u8) -> impl Iterator<Item = u32> + '_ {
+        self.keycodes[key as usize].iter().copied().filter(|&code| code != 0)
+    }
+
+    /// Whether any key was bound by the user.
+    pub fn is_custom(&self) -> bool {
+        self.custom.0 != 0
+    }
+
+    /// CHIP-8 keys pressed by a keyboard key.
+    pub fn keys(&self, keycode: u32) -> impl Iterator<Item = u8> + '_ {
+        (0..16).filter(move |&key| keycode != 0 && self.keycodes[key as usize].contains(&keycode))
+    }
+
+    /// Keyboard keys bound to several CHIP-8 keys, and keys bound by the user that are
+    /// also frontend hotkeys. Keys of the default layout are not checked against the
+    /// hotkeys, so that problems are only reported for mappings the user chose.
+    pub fn conflicts(&self) -> Vec<KeyConflict> {
+        let mut conflicts = Vec::new();
+
+        for key in 0..16 {
+            for keycode in self.keycodes(key) {
+                let keys: Vec<u8> = self.keys(keycode).collect();
+                if keys.len() > 1 && keys[0] == key {
+                    conflicts.push(KeyConflict::Duplicate { keycode, keys });
+                }
+            }
+        }
+
+        for key in (0..16).filter(|&key| self.custom.is_pressed(key as usize)) {
+            for keycode in self.keycodes(key) {
+                if let Some(&(_, action)) = FRONTEND_HOTKEYS.iter().find(|&&(code, _)| code == keycode) {
+                    conflicts.push(KeyConflict::Hotkey { keycode, key, action });
+                }
+            }
+        }
+
+        conflicts
+    }
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        let mut keycodes = [[0; Self::MAX_BINDINGS]; 16];
+        for (bindings, key) in keycodes.iter_mut().zip(Chip8Key::iter()) {
+            bindings[0] = key as u32;
+        }
+        Self { keycodes, custom: KeypadState::default() }
+    }
+}
+
+/// Key press scheduled by an input script.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ScriptedPress {
+    pub key: u8,
+    /// Frame at which the key is pressed, counted from 0 since the last reset.
+    pub frame: u64,
+    /// Number of frames the key is held for.
+    pub frames: u64,
+}
+
+/// Sequence of key presses injected after boot, for games that need a key press to
+/// start when run automatically (screenshots, kiosks or tests). Scripted keys are
+/// combined with user input.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct InputScript {
+    presses: Vec<ScriptedPress>,
+}
+
+impl InputScript {
+    /// Number of frames a key is held for if no duration is given.
+    pub const HOLD_FRAMES: u64 = 4;
+
+    /// Parse a script of comma-separated presses, each written as `key@frame` or
+    /// `key@frame+frames` with the key as a hexadecimal digit, e.g. `5@60,A@120+10`
+    /// presses key 5 at frame 60 and holds key A for 10 frames starting at frame 120.
+    pub fn parse(text: &str) -> Option<Self> {
+        let presses = text.split(',')
+            .map(|press| {
+                let (key, timing) = press.trim().split_once('@')?;
+                let (frame, frames) = timing.split_once('+').unwrap_or((timing, ""));
+
+                let key = u8::from_str_radix(key, 16).ok().filter(|&k| k < 16 && key.len() == 1)?;
+                let frame = frame.parse().ok()?;
+                let frames = if frames.is_empty() { Self::HOLD_FRAMES } else { frames.parse().ok()? };
+
+                Some(ScriptedPress { key, frame, frames })
+            })
+            .collect::<Option<_>>()?;
+
+        Some(Self { presses })
+    }
+
+    pub fn presses(&self) -> &[ScriptedPress] {
+        &self.presses
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.presses.is_empty()
+    }
+
+    /// Keys held by the script at a frame.
+    pub fn state(&self, frame: u64) -> KeypadState {
+        let mut state = KeypadState::default();
+        for press in &self.presses {
+            if (press.frame..press.frame + press.frames).contains(&frame) {
+                state.set(press.key as usize, true);
+            }
+        }
+        state
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn latch_short_press() {
+        let mut tracker = KeyTracker::default();
+
+        tracker.key_event(0x4, true);
+        tracker.key_event(0x4, false);
+
+        assert!(tracker.frame_state(KeypadState::default()).is_pressed(0x4));
+        assert!(!tracker.frame_state(KeypadState::default()).is_pressed(0x4));
+    }
+
+    #[test]
+    fn press_order() {
+        let mut tracker = KeyTracker::default();
+
+        tracker.key_event(0xA, true);
+        tracker.key_event(0x2, true);
+        tracker.frame_state(KeypadState(1 << 0x7));
+
+        assert!(tracker.press_order(0xA) < tracker.press_order(0x2));
+        assert!(tracker.press_order(0x2) < tracker.press_order(0x7));
+
+        tracker.frame_state(KeypadState::default());
+        tracker.key_event(0xA, false);
+
+        assert!(tracker.release_order(0x7) < tracker.release_order(0xA));
+    }
+
+    #[test]
+    fn key_map() {
+        let mut map = KeyMap::default();
+        assert_eq!(map.keycodes(0x0).collect::<Vec<_>>(), [keycode("x").unwrap()]);
+        assert_eq!(map.keycodes(0xF).collect::<Vec<_>>(), [keycode("V").unwrap()]);
+        assert!(map.conflicts().is_empty());
+
+        map.bind(0x8, keycode("up").unwrap());
+        map.bind(0x2, keycode("up").unwrap());
+        map.bind(0x5, keycode("f").unwrap());
+        assert_eq!(map.keys(273).collect::<Vec<_>>(), [0x2, 0x8]);
+        assert_eq!(map.conflicts(), [
+            KeyConflict::Duplicate { keycode: 273, keys: vec![0x2, 0x8] },
+            KeyConflict::Duplicate { keycode: 102, keys: vec![0x5, 0xE] },
+            KeyConflict::Hotkey { keycode: 102, key: 0x5, action: "fullscreen" },
+        ]);
+        assert_eq!(map.conflicts()[0].to_string(), "keyboard key up is bound to CHIP-8 keys 2, 8");
+
+        // Alternate bindings
+        let mut map = KeyMap::default();
+        for name in ["w", "up", "kp8", "up", "8"] {
+            assert!(map.bind(0x5, keycode(name).unwrap()));
+        }
+        assert!(!map.bind(0x5, keycode("i").unwrap()));
+        assert_eq!(map.keycodes(0x5).collect::<Vec<_>>(), [119, 273, 264, 56]);
+        assert_eq!((map.keys(264).collect::<Vec<_>>(), map.keys(0).count()), (vec![0x5], 0));
+        assert!(map.conflicts().is_empty());
+
+        assert_eq!(keycode("kp8"), Some(264));
+        assert_eq!(keycode("nope"), None);
+        assert_eq!((key_name(276), key_name(119), key_name(400)), ("left".to_string(), "w".to_string(), "#400".to_string()));
+    }
+
+    #[test]
+    fn input_script() {
+        let script = InputScript::parse("5@60, A@62+2").unwrap();
+
+        assert_eq!(script.presses()[0], ScriptedPress { key: 0x5, frame: 60, frames: InputScript::HOLD_FRAMES });
+        assert_eq!(script.state(59), KeypadState::default());
+        assert_eq!(script.state(62), KeypadState((1 << 0x5) | (1 << 0xA)));
+        assert_eq!(script.state(64), KeypadState(0));
+
+        assert_eq!(InputScript::parse("5@"), None);
+        assert_eq!(InputScript::parse("G@10"), None);
+        assert_eq!(InputScript::parse("10@10"), None);
+    }
+}
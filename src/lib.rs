@@ -1,970 +1,3683 @@
 
-use std::{collections::HashMap, fs::File, fs::read, io::Write, io::Read, process, env};
-use bitvec::{prelude::Msb0, view::BitView};
-use rand::Rng;
+use std::{collections::{BTreeMap, HashMap, HashSet}, fs::{File, OpenOptions}, fs::read, io::{self, BufWriter, Write}, io::Read, env, path::{Path, PathBuf}, time::{Duration, Instant}};
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use sha1::{Digest, Sha1};
 
 use libretro_rs::{libretro_core, RetroCore, RetroEnvironment, RetroGame,
     RetroLoadGameResult, RetroRuntime, RetroSystemInfo, RetroAudioInfo,
-    RetroVideoInfo, RetroPixelFormat, RetroRegion, RetroDevicePort};
-use strum::IntoEnumIterator;
-
-use cpu::Cpu;
-use input::Chip8Key;
-
+    RetroVideoInfo, RetroPixelFormat, RetroRegion, RetroDevicePort, RetroMemoryType};
+
+use audio::AudioProducer;
+use coverage::Coverage;
+use crash::History;
+use cpu::{Args, Cpu};
+use database::Database;
+use heatmap::MemoryHeatmap;
+use input::{InputScript, KeyTracker, KeypadState};
+use memory_map::RegisterPage;
+use options::{CoreOptions, ExitAction, MemoryProtection};
+use overrides::GameOverrides;
+use palette::Palette;
+use platform::{LoresDxy0, Platform, Quirks};
+use random::RandomSource;
+use snapshot::{MemorySnapshot, SnapshotError};
+use stack::StackMonitor;
+use video::{TextStyle, VideoFilter};
+
+pub mod audio;
+pub mod clock;
+pub mod coverage;
 pub mod cpu;
+pub mod crash;
+pub mod database;
+pub mod detect;
+pub mod heatmap;
+pub mod i18n;
 pub mod input;
+pub mod memory_map;
+pub mod movie;
+pub mod options;
+pub mod overrides;
+pub mod palette;
+pub mod platform;
+pub mod random;
+pub mod snapshot;
+pub mod stack;
+pub mod validate;
+#[cfg(feature = "threaded")]
+mod threaded;
+pub mod video;
+
+/// One packed row of pixels per line, with column 0 in the most significant bit, so that
+/// sprite rows can be drawn with a single XOR.
+type FrameBuffer = [u128; Chip8Core::SCREEN_HEIGHT];
+
+/// Running totals of emulated work since the core was created. Counters are not
+/// cleared by resets.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Counters {
+    /// Number of video frames run.
+    pub frames: u64,
+    /// Number of CHIP-8 instructions executed.
+    pub instructions: u64,
+    /// Number of sprite draw instructions executed.
+    pub draws: u64,
+    /// Number of frames whose instructions were cut short by the watchdog (see
+    /// `Chip8Core::set_frame_budget`).
+    pub clamped_frames: u64,
+}
+
+/// Time spent in each stage of emulation since timing was enabled (see
+/// `Chip8Core::set_timing`), for performance overlays and optimization work.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Timings {
+    /// Number of frames timed.
+    pub frames: u64,
+    /// Time spent executing instructions.
+    pub execute: Duration,
+    /// Time spent converting the frame buffer into the video output.
+    pub render: Duration,
+}
+
+/// Writes to memory that was previously executed as code ("self-modifying code"), which
+/// many classic programs rely on. Like `Counters`, the statistics are not cleared by resets.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CodeWrites {
+    /// Total number of writes to executed memory.
+    pub writes: u64,
+    /// Number of writes to each executed address.
+    pub addresses: BTreeMap<u16, u64>,
+}
+
+/// Display size in low-resolution mode, in pixels.
+pub const LORES_WIDTH: usize = 64;
+pub const LORES_HEIGHT: usize = 32;
+/// Display size in high-resolution mode, in pixels. The video output always has this
+/// size (before upscaling), with each low-resolution pixel drawn as 2x2 pixels.
+pub const HIRES_WIDTH: usize = 128;
+pub const HIRES_HEIGHT: usize = 64;
+
+/// Display resolution mode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Resolution {
+    /// 64x32 pixels.
+    Low,
+    /// 128x64 pixels. **SUPER-CHIP mode.**
+    High,
+}
+
+impl Resolution {
+    /// Width of the display in this mode, in pixels.
+    pub fn width(&self) -> usize {
+        match self {
+            Self::Low => LORES_WIDTH,
+            Self::High => HIRES_WIDTH,
+        }
+    }
+
+    /// Height of the display in this mode, in pixels.
+    pub fn height(&self) -> usize {
+        match self {
+            Self::Low => LORES_HEIGHT,
+            Self::High => HIRES_HEIGHT,
+        }
+    }
+}
 
-type FrameBuffer = [[bool; Chip8Core::SCREEN_WIDTH]; Chip8Core::SCREEN_HEIGHT];
+/// Snapshot of the emulator configuration and execution state.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CoreStatus {
+    pub resolution: Resolution,
+    pub platform: Platform,
+    pub quirks: Quirks,
+    pub instructions_per_frame: usize,
+    /// Whether the interpreter was stopped by the `EXIT` instruction.
+    pub halted: bool,
+    /// Whether the interpreter is waiting for a keypress (`KEY` instruction).
+    pub waiting_for_key: bool,
+    /// Number of bytes of the program loaded into memory, less than its size if it was
+    /// truncated, and 0 if it did not fit or a memory snapshot is loaded.
+    pub program_size: usize,
+}
+
+/// Video and audio produced by a single emulated frame.
+pub struct FrameOutput<'a> {
+    /// Frame in RGB565 format (little endian), stored row by row.
+    pub video: &'a [u8],
+    pub width: usize,
+    pub height: usize,
+    /// Size of a row of the video frame in bytes.
+    pub pitch: usize,
+    /// Interleaved stereo samples at 48 kHz (`Chip8Core::AUDIO_FRAME_SIZE` per frame).
+    /// Empty if no sound is playing, in which case frontends that output a continuous
+    /// stream should play silence.
+    pub audio: &'a [i16],
+}
 
+/// CHIP-8 / SUPER-CHIP emulator core.
+///
+/// All state is owned by the instance, so the core is `Send` and `Sync`: it can run on a
+/// worker thread or be held across `.await` points. Emulation requires `&mut self`, so
+/// sharing an instance between threads needs external synchronization (e.g. a `Mutex`).
 pub struct Chip8Core {
     cpu: Cpu,
+    /// Working frame buffer, modified by instructions.
     frame_buffer: FrameBuffer,
+    /// Frame buffer shown by the video output, committed from the working buffer once per
+    /// frame, so that the output never shows a partially drawn frame.
+    presented: FrameBuffer,
+    /// The video output must be rendered again even if the presented frame buffer did not
+    /// change, e.g. after the palette changed.
+    video_dirty: bool,
     high_resolution: bool,
+    halted: bool,
+    exit_action: ExitAction,
+    memory_protection: MemoryProtection,
+    /// Instructions whose protected writes were already logged.
+    protection_warnings: HashSet<u16>,
+    /// Addresses written since the last reset, or holding a font or the program
+    /// ("shadow memory"), checked by reads if `uninit_warnings` is enabled.
+    initialized: Box<[bool; Self::MEMORY_SIZE]>,
+    uninit_warnings: bool,
+    /// Instructions whose uninitialized reads were already logged.
+    uninit_read_warnings: HashSet<u16>,
+    shutdown_requested: bool,
+    /// Last instructions executed, for crash dumps.
+    history: History,
+    /// Error that stopped emulation, until the next reset.
+    fatal_error: Option<String>,
+    /// A crash dump was already written for the current fatal error.
+    crash_dumped: bool,
     keypad_state: [bool; Self::KEYPAD_SIZE],
+    /// Keys held when the pending `KEY` instruction started waiting, which are ignored
+    /// until released if `key_edge` is enabled.
+    key_wait_held: [bool; Self::KEYPAD_SIZE],
+    /// Keys pressed while the pending `KEY` instruction was waiting ("key release" quirk).
+    key_wait_pressed: [bool; Self::KEYPAD_SIZE],
+    key_edge: bool,
+    /// Key stored by `KEY` without waiting, for automated runs.
+    auto_key: Option<u8>,
+    /// Key presses injected after boot, and the number of frames run since the last reset.
+    input_script: InputScript,
+    boot_frames: u64,
+    opcode_warnings: bool,
+    /// Poll the keypad a second time halfway through each frame.
+    mid_frame_poll: bool,
+    /// Load the part of programs too large for memory that fits.
+    truncate: bool,
+    paused: bool,
+    /// Pause while the frontend does not display video, e.g. while it is in the background.
+    pause_hidden: bool,
+    /// Unsupported opcodes already logged, so that each is only reported once.
+    warned_opcodes: HashSet<u16>,
+    /// Machine code routines (`0NNN`) the program tried to call, each reported once.
+    machine_code_calls: HashSet<u16>,
+    /// Message to show on screen on the next frame.
+    pending_message: Option<String>,
+    key_tracker: KeyTracker,
+    /// Whether the frontend reports keyboard events, in which case the keyboard is not polled.
+    keyboard_callback: bool,
+    /// Keyboard keys held according to keyboard events, as libretro key codes.
+    held_keycodes: HashSet<u32>,
+    video_frame: Vec<u8>,
+    video_filter: VideoFilter,
+    palette: Palette,
+    /// Palette being faded from and the number of frames of the fade shown so far, while
+    /// a palette change is in progress.
+    palette_fade: Option<(Palette, u32)>,
     wave: [i16; 2 * Self::SAMPLE_RATE as usize],
     wave_idx: usize,
     instructions_per_frame: usize,
-    // Quirks
-    quirk_memory: bool,
-    quirk_shift: bool,
-    quirk_collision: bool,
-    quirk_resolution: bool,
-    quirk_lores16: bool,
+    speed_percent: usize,
+    fast_forward_percent: usize,
+    fast_forward_mute: bool,
+    /// Number of parts of each audio frame, see `set_audio_chunks`.
+    audio_chunks: usize,
+    /// Audio of the current frame, when assembled from chunks.
+    audio_frame: Vec<i16>,
+    /// Whether the frontend is fast-forwarding.
+    fast_forward: bool,
+    /// Fractions of an instruction and of a timer tick carried over to the next frame,
+    /// in hundredths, when the speed is not a whole multiple.
+    instruction_credit: usize,
+    timer_credit: usize,
+    /// Time the instructions of a frame may take before the rest are skipped, see
+    /// `set_frame_budget`.
+    frame_budget: Option<Duration>,
+    /// The watchdog already cut a frame short since the program was loaded.
+    watchdog_warned: bool,
+    #[cfg(feature = "threaded")]
+    blocks: threaded::BlockCache,
+    /// Maximum number of sprites drawn per frame, or 0 if unlimited.
+    draw_limit: usize,
+    frame_draws: usize,
+    draw_stalled: bool,
+    register_page: Box<RegisterPage>,
+    program: Vec<u8>,
+    /// Memory snapshot loaded as content, restored instead of the program on reset.
+    snapshot: Option<MemorySnapshot>,
+    program_hash: [u8; 20],
+    seed: u64,
+    rng: StdRng,
+    /// Replaces `rng` if set.
+    random_source: Option<Box<dyn RandomSource>>,
+    /// Ring buffer receiving the audio of every frame, see `set_audio_output`.
+    audio_output: Option<AudioProducer>,
+    options: CoreOptions,
+    /// Options parsed from the command line, before core options are applied.
+    base_options: CoreOptions,
+    /// File the RPL user flags are persisted to, if any.
+    flags_path: Option<PathBuf>,
+    /// RPL user flags, kept in memory when there is no flags file.
+    flags: [u8; Self::FLAGS_SIZE],
+    /// Text report written when emulation stops on a fatal error, next to a memory
+    /// snapshot with the same name and the `ox8d` extension.
+    crash_dump_path: PathBuf,
+    /// Per-game option overrides, updated when the user changes a core option.
+    overrides: GameOverrides,
+    overrides_path: Option<PathBuf>,
+    /// Core option values last reported by the frontend.
+    variable_values: HashMap<&'static str, String>,
+    reset_held: bool,
+    reset_apply_held: bool,
+    palette_held: bool,
+    speed_held: bool,
+    counters: Counters,
+    /// Addresses fetched as part of an instruction since the last reset.
+    executed: Box<[bool; Self::MEMORY_SIZE]>,
+    code_writes: CodeWrites,
+    /// Time spent per stage, if enabled.
+    timings: Option<Timings>,
+    /// Memory accesses counted while profiling, see `set_heatmap`.
+    heatmap: Option<Box<MemoryHeatmap>>,
+    /// Recursion and stack growth checks, see `set_stack_monitor`.
+    stack_monitor: Option<StackMonitor>,
+    /// Per-frame trace output, see `set_trace_file`.
+    trace: Option<BufWriter<File>>,
+    platform: Platform,
+    quirks: Quirks,
+}
+
+/// Sample of a square wave at index `sample`, for frequencies in Hertz.
+#[cfg(not(feature = "integer-only"))]
+fn sample_square_wave(amplitude: i16, frequency: u32, sample: usize, sample_rate: u32) -> i16 {
+    let t = sample as f64 / sample_rate as f64;
+    amplitude * i16::pow(-1, (frequency as f64 * t).floor() as u32)
+}
+
+/// Sample of a square wave at index `sample`, for frequencies in Hertz. Produces the
+/// same samples as the floating point version.
+#[cfg(feature = "integer-only")]
+fn sample_square_wave(amplitude: i16, frequency: u32, sample: usize, sample_rate: u32) -> i16 {
+    let periods = frequency as u64 * sample as u64 / sample_rate as u64;
+    if periods & 1 == 0 { amplitude } else { -amplitude }
 }
 
-fn sample_square_wave(amplitude: i16, frequency: f64, t: f64) -> i16 {
-    amplitude * i16::pow(-1, (frequency * t).floor() as u32)
+/// Duplicate every bit of a sprite row, for drawing in low-resolution mode where each
+/// pixel spans two frame buffer columns.
+fn double_bits(bits: u16) -> u32 {
+    let mut x = bits as u32;
+    x = (x | x << 8) & 0x00FF_00FF;
+    x = (x | x << 4) & 0x0F0F_0F0F;
+    x = (x | x << 2) & 0x3333_3333;
+    x = (x | x << 1) & 0x5555_5555;
+    x | x << 1
 }
 
 impl Chip8Core {
-    const SCREEN_WIDTH: usize = 128;
-    const SCREEN_HEIGHT: usize = 64;
+    const SCREEN_WIDTH: usize = HIRES_WIDTH;
+    const SCREEN_HEIGHT: usize = HIRES_HEIGHT;
 
-    /// RGB565 representation of the white (on) pixel color.
-    const WHITE_COLOR: u16 = 0x9DE2;
-    /// RGB565 representation of the black (off) pixel color.
-    const BLACK_COLOR: u16 = 0x11C2;
+    /// Size of a video frame in bytes (two bytes per pixel).
+    const VIDEO_FRAME_SIZE: usize = 2 * Self::SCREEN_WIDTH * Self::SCREEN_HEIGHT;
 
     const DIGIT_SIZE: usize = 5;
     const LARGE_DIGIT_SIZE: usize = 10;
-    const LARGE_DIGIT_OFFSET: usize = 128;
 
-    const FLAGS_FILE: &'static str = "flags.rpl";
+    /// File extensions of programs the core can load, reported to the frontend.
+    pub const EXTENSIONS: &'static [&'static str] = &["ch8", "sc8", "xo8", "c8x", "bin", "ox8d"];
+    /// Number of RPL user flags.
+    const FLAGS_SIZE: usize = 8;
+    const CRASH_DUMP_FILE: &'static str = "crash.txt";
 
     /// Number of video frames to display each second. Typically, a rate of 60Hz is used.
-    const FRAME_RATE: f64 = 60.0;
+    pub const FRAME_RATE: f64 = 60.0;
     /// Default number of CHIP-8 instruction executed per video frame. Frequency is equal
     /// to `FRAME_RATE` * `INSTRUCTIONS_PER_FRAME`.
     const INSTRUCTIONS_PER_FRAME: usize = 10;
 
+    /// Number of frames the options in effect are shown on screen when a game starts.
+    const SUMMARY_MESSAGE_FRAMES: u32 = 180;
+    /// Number of frames the new palette or speed is shown on screen after a hotkey.
+    const HOTKEY_MESSAGE_FRAMES: u32 = 60;
+    /// Number of frames a warning about the running program is shown on screen.
+    const WARNING_MESSAGE_FRAMES: u32 = 240;
+    /// Number of frames over which the colors change when fading between palettes.
+    const PALETTE_FADE_FRAMES: u32 = 12;
+
     /// Audio sample rate in Hertz.
-    const SAMPLE_RATE: f64 = 48000.0;
-    /// Size of a single audio frame in bytes.
-    const AUDIO_FRAME_SIZE: usize = 2 * (Self::SAMPLE_RATE / Self::FRAME_RATE) as usize;
+    pub const SAMPLE_RATE: f64 = 48000.0;
+    /// Number of samples (interleaved stereo) produced by a frame while sound is playing.
+    pub const AUDIO_FRAME_SIZE: usize = 2 * (Self::SAMPLE_RATE / Self::FRAME_RATE) as usize;
     /// Amplitude of the square wave.
     const WAVE_AMPLITUDE: i16 = 1200;
     /// Frequency of the square wave. For best results, this value should divide
     /// the audio sample rate.
-    const WAVE_FREQUENCY: f64 = 500.0;
+    const WAVE_FREQUENCY: u32 = 500;
+    /// Maximum number of chunks per audio frame, which divides it evenly.
+    pub const MAX_AUDIO_CHUNKS: usize = 8;
+    /// Number of instructions between checks of the frame's time budget.
+    pub const WATCHDOG_INTERVAL: usize = 1024;
     /// Maximum value of the wave_idx member field.
     const MAX_WAVE_IDX: usize = Self::SAMPLE_RATE as usize / Self::AUDIO_FRAME_SIZE;
 
     const KEYPAD_SIZE: usize = 16;
+    const MEMORY_SIZE: usize = 4 * 1024;
 
     pub fn new() -> Self {
-        Self::with_quirks(false, false, false, false, false)
+        Self::with_quirks(Quirks::default())
     }
 
-    pub fn with_quirks(memory: bool, shift: bool, collision: bool, resolution: bool, lores16: bool) -> Self {
+    pub fn with_quirks(quirks: Quirks) -> Self {
         // Precalculate square wave to decrease required computation.
         let mut wave = [0; 2 * Self::SAMPLE_RATE as usize];
         for (i, sample) in wave.iter_mut().enumerate() {
-            *sample = sample_square_wave(Self::WAVE_AMPLITUDE, Self::WAVE_FREQUENCY, i as f64 / Self::SAMPLE_RATE); 
+            *sample = sample_square_wave(Self::WAVE_AMPLITUDE, Self::WAVE_FREQUENCY, i, Self::SAMPLE_RATE as u32);
         }
 
+        let seed = rand::thread_rng().gen();
+
         Self {
             cpu: Cpu::new(),
-            frame_buffer: [[false; Chip8Core::SCREEN_WIDTH]; Chip8Core::SCREEN_HEIGHT],
+            frame_buffer: [0; Chip8Core::SCREEN_HEIGHT],
+            presented: [0; Chip8Core::SCREEN_HEIGHT],
+            video_dirty: true,
             high_resolution: false,
+            halted: false,
+            exit_action: ExitAction::default(),
+            memory_protection: MemoryProtection::default(),
+            protection_warnings: HashSet::new(),
+            initialized: Box::new([false; Self::MEMORY_SIZE]),
+            uninit_warnings: false,
+            uninit_read_warnings: HashSet::new(),
+            shutdown_requested: false,
+            history: History::default(),
+            fatal_error: None,
+            crash_dumped: false,
             keypad_state: [false; Self::KEYPAD_SIZE],
+            key_wait_held: [false; Self::KEYPAD_SIZE],
+            key_wait_pressed: [false; Self::KEYPAD_SIZE],
+            key_edge: false,
+            auto_key: None,
+            input_script: InputScript::default(),
+            boot_frames: 0,
+            opcode_warnings: false,
+            mid_frame_poll: false,
+            truncate: false,
+            paused: false,
+            pause_hidden: false,
+            warned_opcodes: HashSet::new(),
+            machine_code_calls: HashSet::new(),
+            pending_message: None,
+            key_tracker: KeyTracker::default(),
+            keyboard_callback: false,
+            held_keycodes: HashSet::new(),
+            video_frame: Vec::with_capacity(Self::VIDEO_FRAME_SIZE * VideoFilter::MAX_SCALE * VideoFilter::MAX_SCALE),
+            video_filter: VideoFilter::default(),
+            palette: Palette::default(),
+            palette_fade: None,
             wave,
             wave_idx: 0,
             instructions_per_frame: Self::INSTRUCTIONS_PER_FRAME,
-            quirk_memory: memory,
-            quirk_shift: shift,
-            quirk_collision: collision,
-            quirk_resolution: resolution,
-            quirk_lores16: lores16,
+            speed_percent: 100,
+            fast_forward_percent: 0,
+            fast_forward_mute: false,
+            audio_chunks: 1,
+            frame_budget: None,
+            watchdog_warned: false,
+            audio_frame: vec![0; Self::AUDIO_FRAME_SIZE],
+            fast_forward: false,
+            instruction_credit: 0,
+            timer_credit: 0,
+            #[cfg(feature = "threaded")]
+            blocks: threaded::BlockCache::default(),
+            draw_limit: 0,
+            frame_draws: 0,
+            draw_stalled: false,
+            register_page: Box::default(),
+            program: Vec::new(),
+            snapshot: None,
+            program_hash: Sha1::digest([]).into(),
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+            random_source: None,
+            audio_output: None,
+            options: CoreOptions::default(),
+            base_options: CoreOptions::default(),
+            flags_path: None,
+            flags: [0; Self::FLAGS_SIZE],
+            crash_dump_path: PathBuf::from(Self::CRASH_DUMP_FILE),
+            overrides: GameOverrides::default(),
+            overrides_path: None,
+            variable_values: HashMap::new(),
+            reset_held: false,
+            reset_apply_held: false,
+            palette_held: false,
+            speed_held: false,
+            counters: Counters::default(),
+            executed: Box::new([false; Self::MEMORY_SIZE]),
+            code_writes: CodeWrites::default(),
+            heatmap: None,
+            stack_monitor: None,
+            timings: None,
+            trace: None,
+            platform: Platform::default(),
+            quirks,
         }
     }
 
-    pub fn set_instructions_per_frame(&mut self, v: usize) {
-        self.instructions_per_frame = v;
-    }
+    pub fn with_options(options: CoreOptions) -> Self {
+        let mut core = Self::new();
+        core.video_filter.scale = options.scale;
+        core.video_filter.rotation = options.rotation;
+        core.video_filter.aspect = options.aspect;
 
-    pub fn execute_instruction(&mut self) {
-        let raw_instruction = self.cpu.fetch_instruction();
-        let instruction = self.cpu.decode_instruction(raw_instruction);
+        // Trace failures skip `log_warning`, since the trace is what failed
+        if let Some(path) = &options.trace_path {
+            if let Err(e) = core.set_trace_file(path) {
+                eprintln!("oxid-8: cannot open trace file {}: {}", path.display(), e);
+            }
+        }
 
-        (instruction.callback)(self, instruction.args(raw_instruction));
-    }
+        // Report keyboard mapping problems instead of leaving keys that do not respond
+        // unexplained
+        let conflicts = options.key_map.conflicts();
+        for conflict in &conflicts {
+            core.log_warning(&format!("key mapping: {}", conflict));
+        }
+        match conflicts.as_slice() {
+            [] => {},
+            [conflict] => core.pending_message = Some(format!("Key mapping: {}", conflict)),
+            _ => core.pending_message = Some(format!("Key mapping has {} conflicts, see the log", conflicts.len())),
+        }
 
-    /// No operation.
-    fn nop(&mut self, _args: HashMap<&'static str, u16>) {
+        core.apply_options(options);
+        core.palette_fade = None;
+        core.cpu.reset();
+        core
+    }
+
+    /// Apply a new configuration. Quirk and font placement changes take full effect after a reset. The
+    /// upscaling factor is fixed when the core is created, since it determines the
+    /// output geometry.
+    pub fn apply_options(&mut self, options: CoreOptions) {
+        self.instructions_per_frame = options.instructions_per_frame;
+        self.set_speed_percent(options.speed_percent);
+        self.fast_forward_percent = options.fast_forward_percent;
+        self.fast_forward_mute = options.fast_forward_mute;
+        self.set_audio_chunks(options.audio_chunks);
+        self.frame_budget = (options.frame_budget_ms != 0).then(|| Duration::from_millis(options.frame_budget_ms));
+        self.draw_limit = options.draw_limit;
+        self.exit_action = options.exit_action;
+        self.memory_protection = options.memory_protection;
+        self.key_edge = options.key_edge;
+        self.auto_key = options.auto_key;
+        self.input_script = options.input_script.clone();
+        self.opcode_warnings = options.opcode_warnings;
+        self.uninit_warnings = options.uninit_warnings;
+        self.set_stack_monitor(options.max_recursion);
+        self.mid_frame_poll = options.mid_frame_poll;
+        self.truncate = options.truncate;
+        self.pause_hidden = options.pause_hidden;
+        self.video_filter.scanlines = options.scanlines;
+        self.set_palette(options.palette, options.palette_fade);
+        self.platform = options.platform;
+        self.quirks = options.effective_quirks();
+        self.cpu.layout = options.memory_layout();
+        self.options = options;
+    }
+
+    /// Apply the options that can change while a program runs, as when the user changes
+    /// core options in the frontend's menu, without resetting: the emulation speed (also
+    /// while fast-forwarding), the palette (faded if enabled), the sprite draw limit,
+    /// audio chunks and the frame time budget. Other options take effect on the next reset
+    /// with `apply_options`.
+    pub fn apply_runtime_options(&mut self, options: &CoreOptions) {
+        self.set_speed_percent(options.speed_percent);
+        self.fast_forward_percent = options.fast_forward_percent;
+        self.fast_forward_mute = options.fast_forward_mute;
+        self.draw_limit = options.draw_limit;
+        self.set_audio_chunks(options.audio_chunks);
+        self.frame_budget = (options.frame_budget_ms != 0).then(|| Duration::from_millis(options.frame_budget_ms));
+        self.set_palette(options.palette, options.palette_fade);
+
+        self.options.speed_percent = options.speed_percent;
+        self.options.fast_forward_percent = options.fast_forward_percent;
+        self.options.fast_forward_mute = options.fast_forward_mute;
+        self.options.draw_limit = options.draw_limit;
+        self.options.audio_chunks = options.audio_chunks;
+        self.options.frame_budget_ms = options.frame_budget_ms;
+        self.options.palette = options.palette;
+        self.options.palette_fade = options.palette_fade;
+        self.options.palette_file = options.palette_file;
+    }
+
+    /// Change the display colors, fading from the current ones over a few frames if `fade`
+    /// is set. The video output is rendered again on the next frame even if the program
+    /// does not draw, or if emulation is paused.
+    pub fn set_palette(&mut self, palette: Palette, fade: bool) {
+        if palette == self.palette {
+            return;
+        }
 
+        self.palette_fade = fade.then(|| (self.displayed_palette(), 0));
+        self.palette = palette;
+        self.video_dirty = true;
     }
 
-    /// Clear the screen.
-    fn cls(&mut self, _args: HashMap<&'static str, u16>) {
-        for row in &mut self.frame_buffer {
-            row.fill(false);
+    /// Colors currently shown, partway between two palettes during a fade.
+    fn displayed_palette(&self) -> Palette {
+        match self.palette_fade {
+            Some((from, frame)) => from.blend(&self.palette, frame, Self::PALETTE_FADE_FRAMES),
+            None => self.palette,
         }
     }
 
-    /// Jump to address `NNN`.
-    fn jmp(&mut self, args: HashMap<&'static str, u16>) {
-        let n = *args.get("N").unwrap();
-
-        self.cpu.pc = n;
+    /// Split each frame of audio into `chunks` parts (1, 2, 4 or 8, rounded up), each
+    /// played or silent according to the sound timer after the matching share of the
+    /// frame's instructions. A beep then starts close to the instruction that set the
+    /// sound timer rather than at the next frame, reducing its latency by up to a frame.
+    /// With a single chunk (the default), the whole frame follows the sound timer at the
+    /// end of the frame.
+    pub fn set_audio_chunks(&mut self, chunks: usize) {
+        self.audio_chunks = chunks.clamp(1, Self::MAX_AUDIO_CHUNKS).next_power_of_two();
     }
 
-    /// Execute subroutine starting at address `NNN`.
-    fn call(&mut self, args: HashMap<&'static str, u16>) {
-        let n = *args.get("N").unwrap();
-
-        self.cpu.stack.push(self.cpu.pc);
-        self.cpu.pc = n;
+    pub fn audio_chunks(&self) -> usize {
+        self.audio_chunks
     }
 
-    /// Return from a subroutine.
-    fn ret(&mut self, _args: HashMap<&'static str, u16>) {
-        if let Some(stack_top) = self.cpu.stack.pop() {
-            self.cpu.pc = stack_top;
-        }
+    /// Limit the time spent executing the instructions of a frame, or remove the limit
+    /// with `None` (the default). When a frame exceeds the budget, e.g. because the
+    /// instructions per frame are set far beyond what the device can run, the rest of its
+    /// instructions are skipped, so that the frontend stays responsive instead of
+    /// freezing. A warning is logged the first time, and `counters().clamped_frames`
+    /// counts the frames cut short. The time is checked every `WATCHDOG_INTERVAL`
+    /// instructions.
+    pub fn set_frame_budget(&mut self, budget: Option<Duration>) {
+        self.frame_budget = budget;
     }
 
-    /// Scroll display down by `N` pixels, or `N/2` pixels in low-resolution mode.
-    /// **SUPER-CHIP instruction.**
-    fn scd(&mut self, args: HashMap<&'static str, u16>) {
-        let n = *args.get("N").unwrap() as usize % Self::SCREEN_HEIGHT;
-        if n == 0 {
-            return;
-        }
+    pub fn frame_budget(&self) -> Option<Duration> {
+        self.frame_budget
+    }
 
-        let mut new_buffer = [[false; Chip8Core::SCREEN_WIDTH]; Chip8Core::SCREEN_HEIGHT];
-        new_buffer[n..].copy_from_slice(&self.frame_buffer[..Chip8Core::SCREEN_HEIGHT - n]);
-        self.frame_buffer = new_buffer;
+    pub fn set_instructions_per_frame(&mut self, v: usize) {
+        self.instructions_per_frame = v;
     }
 
-    /// Scroll display right by 4 pixels, or 2 in low-resolution mode. **SUPER-CHIP instruction.**
-    fn scr(&mut self, _args: HashMap<&'static str, u16>) {
-        let pixels = 4;
+    pub fn instructions_per_frame(&self) -> usize {
+        self.instructions_per_frame
+    }
 
-        for row in &mut self.frame_buffer {
-            let mut new_row = [false; Chip8Core::SCREEN_WIDTH];
-            new_row[pixels..].copy_from_slice(&row[..Chip8Core::SCREEN_WIDTH - pixels]);
-            *row = new_row;
-        }
+    /// Set the emulation speed in percent, clamped to the range of 0.25x to 8x. Both the
+    /// instructions run per frame and the timer tick rate are scaled, so programs run in
+    /// slow motion (or fast forward) without changing their timing relative to the timers.
+    pub fn set_speed_percent(&mut self, percent: usize) {
+        self.speed_percent = percent.clamp(CoreOptions::MIN_SPEED_PERCENT, CoreOptions::MAX_SPEED_PERCENT);
     }
 
-    /// Scroll display left by 4 pixels, or 2 in low-resolution mode. **SUPER-CHIP instruction.**
-    fn scl(&mut self, _args: HashMap<&'static str, u16>) {
-        let pixels = 4;
+    pub fn speed_percent(&self) -> usize {
+        self.speed_percent
+    }
 
-        for row in &mut self.frame_buffer {
-            let mut new_row = [false; Chip8Core::SCREEN_WIDTH];
-            new_row[..Chip8Core::SCREEN_WIDTH - pixels].copy_from_slice(&row[pixels..]);
-            *row = new_row;
-        }
+    /// Make the `KEY` instruction store the given key immediately instead of waiting for
+    /// input, so that automated runs (fuzzing, benchmarks, demos) never stall. `None`
+    /// restores normal waiting.
+    pub fn set_auto_key(&mut self, key: Option<u8>) {
+        self.auto_key = key.map(|k| k % Self::KEYPAD_SIZE as u8);
     }
 
-    /// Exit the interpreter. **SUPER-CHIP instruction.**
-    fn exit(&mut self, _args: HashMap<&'static str, u16>) {
-        process::exit(0);
+    /// Inject the key presses of a script, timed in frames since the last reset, in
+    /// addition to the keys pressed by the user.
+    pub fn set_input_script(&mut self, script: InputScript) {
+        self.input_script = script;
     }
 
-    /// Disable -resolution mode. **SUPER-CHIP instruction.**
-    fn lores(&mut self, _args: HashMap<&'static str, u16>) {
-        self.high_resolution = false;
-        if self.quirk_resolution {
-            self.cls(HashMap::new());
-        }
+    /// Stop or resume emulation. While paused, frames show the last screen without
+    /// running instructions, ticking the timers or playing sound.
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
     }
 
-    /// Enable -resolution mode. **SUPER-CHIP instruction.**
-    fn hires(&mut self, _args: HashMap<&'static str, u16>) {
-        self.high_resolution = true;
-        if self.quirk_resolution {
-            self.cls(HashMap::new());
-        }
+    pub fn is_paused(&self) -> bool {
+        self.paused
     }
-    
-    /// Skip following instruction if value of register `VX` equals `NN`.
-    fn skpeq(&mut self, args: HashMap<&'static str, u16>) {
-        let x = *args.get("X").unwrap() as usize;
-        let n = *args.get("N").unwrap() as u8;
 
-        let x_val = self.cpu.registers[x];
+    /// Report whether the frontend is fast-forwarding. While it is, the configured
+    /// fast-forward speed replaces the normal speed, and audio is muted if configured.
+    pub fn set_fast_forward(&mut self, fast_forward: bool) {
+        self.fast_forward = fast_forward;
+    }
 
-        if x_val == n {
-            self.cpu.pc += 2;
-        }
+    /// Speed in percent in effect for the next frame.
+    fn effective_speed_percent(&self) -> usize {
+        if self.fast_forward && self.fast_forward_percent != 0 { self.fast_forward_percent } else { self.speed_percent }
     }
 
-    /// Skip following instruction if value of register `VX` does not equals `NN`.
-    fn skpne(&mut self, args: HashMap<&'static str, u16>) {
-        let x = *args.get("X").unwrap() as usize;
-        let n = *args.get("N").unwrap() as u8;
+    pub fn platform(&self) -> Platform {
+        self.platform
+    }
 
-        let x_val = self.cpu.registers[x];
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
 
-        if x_val != n {
-            self.cpu.pc += 2;
+    /// Current resolution mode, configuration and execution state.
+    pub fn status(&self) -> CoreStatus {
+        CoreStatus {
+            resolution: if self.high_resolution { Resolution::High } else { Resolution::Low },
+            platform: self.platform,
+            quirks: self.quirks,
+            instructions_per_frame: self.instructions_per_frame,
+            halted: self.halted,
+            waiting_for_key: self.cpu.store_keypress.is_some(),
+            program_size: self.program.len(),
         }
     }
 
-    /// Skip following instruction if value of register `VX` is equal to value of register `VY`.
-    fn skpeqr(&mut self, args: HashMap<&'static str, u16>) {
-        let x = *args.get("X").unwrap() as usize;
-        let y = *args.get("Y").unwrap() as usize;
-
-        let x_val = self.cpu.registers[x];
-        let y_val = self.cpu.registers[y];
-
-        if x_val == y_val {
-            self.cpu.pc += 2;
-        }
+    /// Address of the next instruction.
+    pub fn pc(&self) -> u16 {
+        self.cpu.pc
     }
 
-    /// Skip following instruction if value of register `VX` is not equal to `VY`.
-    fn skpner(&mut self, args: HashMap<&'static str, u16>) {
-        let x = *args.get("X").unwrap() as usize;
-        let y = *args.get("Y").unwrap() as usize;
+    /// Set the address of the next instruction, wrapped to the size of memory.
+    pub fn set_pc(&mut self, pc: u16) {
+        self.cpu.pc = pc % Self::MEMORY_SIZE as u16;
+    }
 
-        let x_val = self.cpu.registers[x];
-        let y_val = self.cpu.registers[y];
+    pub fn i_register(&self) -> u16 {
+        self.cpu.i_register
+    }
 
-        if x_val != y_val {
-            self.cpu.pc += 2;
-        }
+    pub fn set_i_register(&mut self, value: u16) {
+        self.cpu.i_register = value;
     }
 
-    /// Jump to address `NNN + V0`.
-    fn jmpr(&mut self, args: HashMap<&'static str, u16>) {
-        let n = *args.get("N").unwrap();
-        let reg_val = self.cpu.registers[0x0] as u16;
-        let mem_size = self.cpu.memory.len() as u16;
+    /// Value of register `VX`, for `X` from `0x0` to `0xF`.
+    pub fn register(&self, x: usize) -> u8 {
+        self.cpu.registers[x]
+    }
 
-        self.cpu.pc = (n + reg_val) % mem_size;
+    pub fn set_register(&mut self, x: usize, value: u8) {
+        self.cpu.registers[x] = value;
     }
 
-    /// Add value of register `VY` to register `VX`. Set `VF` to `01` if carry
-    /// occurs, `00` otherwise.
-    fn addr(&mut self, args: HashMap<&'static str, u16>) {
-        let x = *args.get("X").unwrap() as usize;
-        let y = *args.get("Y").unwrap() as usize;
+    pub fn delay_timer(&self) -> u8 {
+        self.cpu.delay_timer
+    }
 
-        let x_val = self.cpu.registers[x];
-        let y_val = self.cpu.registers[y];
+    pub fn set_delay_timer(&mut self, value: u8) {
+        self.cpu.delay_timer = value;
+    }
 
-        let (result, carry) = x_val.overflowing_add(y_val);
+    pub fn sound_timer(&self) -> u8 {
+        self.cpu.sound_timer
+    }
 
-        self.cpu.registers[x] = result;
-        self.cpu.registers[0xF] = carry as u8;
+    pub fn set_sound_timer(&mut self, value: u8) {
+        self.cpu.sound_timer = value;
     }
 
-    /// Subtract value of register `VY` from register `VX`. Set `VF` to `00` if a borrow
-    /// occurs, `01` otherwise.
-    fn subr(&mut self, args: HashMap<&'static str, u16>) {
-        let x = *args.get("X").unwrap() as usize;
-        let y = *args.get("Y").unwrap() as usize;
+    /// Register that will receive the key of the pending `KEY` instruction, if the
+    /// interpreter is waiting for a keypress.
+    pub fn key_wait_register(&self) -> Option<usize> {
+        self.cpu.store_keypress
+    }
 
-        let x_val = self.cpu.registers[x];
-        let y_val = self.cpu.registers[y];
+    /// Seed used by the random number generator of the `RAND` instruction.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
 
-        let (result, borrow) = x_val.overflowing_sub(y_val);
+    /// Reseed the random number generator, making `RAND` results reproducible.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.rng = StdRng::seed_from_u64(seed);
+    }
 
-        self.cpu.registers[x] = result;
-        self.cpu.registers[0xF] = !borrow as u8;
+    /// Generate the numbers of the `RAND` instruction with `source` (e.g. a
+    /// `random::FixedSequence`) instead of the seeded generator, or restore the seeded
+    /// generator with `None`. The source is restarted on reset.
+    pub fn set_random_source(&mut self, source: Option<Box<dyn RandomSource>>) {
+        self.random_source = source;
     }
 
-    /// Set `VX` to value of `VY` minus `VX`. Set `VF` to `00` if a borrow
-    /// occurs, `01` otherwise.
-    fn rsubr(&mut self, args: HashMap<&'static str, u16>) {
-        let x = *args.get("X").unwrap() as usize;
-        let y = *args.get("Y").unwrap() as usize;
+    /// Push the audio of every frame into a ring buffer (see `audio::ring`), as a
+    /// continuous stream in which frames without sound are silence, for frontends that
+    /// pull audio from a callback. The samples are still returned by `run_frame`.
+    pub fn set_audio_output(&mut self, output: Option<AudioProducer>) {
+        self.audio_output = output;
+    }
 
-        let x_val = self.cpu.registers[x];
-        let y_val = self.cpu.registers[y];
+    /// Path of the file storing the RPL user flags (`SAVEF` / `LOADF`). Without one (the
+    /// default), the flags are only kept in memory while the core exists.
+    pub fn flags_path(&self) -> Option<&Path> {
+        self.flags_path.as_deref()
+    }
 
-        let (result, borrow) = y_val.overflowing_sub(x_val);
+    pub fn set_flags_path(&mut self, path: Option<PathBuf>) {
+        self.flags_path = path;
+    }
 
-        self.cpu.registers[x] = result;
-        self.cpu.registers[0xF] = !borrow as u8;
+    /// SHA-1 hash of the loaded program.
+    pub fn program_hash(&self) -> [u8; 20] {
+        self.program_hash
     }
 
-    /// Store `NN` in register `VX`.
-    fn mov(&mut self, args: HashMap<&'static str, u16>) {
-        let x = *args.get("X").unwrap() as usize;
-        let n = *args.get("N").unwrap() as u8;
+    pub fn keypad(&self) -> KeypadState {
+        let mut keypad = KeypadState::default();
+        for (k, &pressed) in self.keypad_state.iter().enumerate() {
+            keypad.set(k, pressed);
+        }
+        keypad
+    }
 
-        self.cpu.registers[x] = n;
+    pub fn set_keypad(&mut self, keypad: KeypadState) {
+        for (k, pressed) in self.keypad_state.iter_mut().enumerate() {
+            *pressed = keypad.is_pressed(k);
+        }
     }
 
-    /// Add `NN` to register `VX`.
-    fn add(&mut self, args: HashMap<&'static str, u16>) {
-        let x = *args.get("X").unwrap() as usize;
-        let n = *args.get("N").unwrap() as u8;
+    /// Report a key press or release as it happens. Presses are applied at the start of
+    /// the next frame, even if the key was released in the meantime.
+    pub fn key_event(&mut self, key: usize, pressed: bool) {
+        if key < Self::KEYPAD_SIZE {
+            self.key_tracker.key_event(key, pressed);
+        }
+    }
 
-        let x_val = self.cpu.registers[x];
+    /// Report a press or release of a keyboard key (a libretro key code) as it happens.
+    /// The CHIP-8 keys bound to it (see `KeyMap`) are pressed while any of their keyboard
+    /// keys is held, so releasing one of several alternate keys does not release them.
+    pub fn keyboard_key_event(&mut self, keycode: u32, pressed: bool) {
+        if pressed {
+            self.held_keycodes.insert(keycode);
+        }
+        else {
+            self.held_keycodes.remove(&keycode);
+        }
 
-        self.cpu.registers[x] = x_val.wrapping_add(n);
+        let key_map = self.options.key_map;
+        for key in key_map.keys(keycode) {
+            let held = key_map.keycodes(key).any(|code| self.held_keycodes.contains(&code));
+            self.key_event(key as usize, held);
+        }
     }
 
-    /// Store value of register `VY` in register `VX`.
-    fn movr(&mut self, args: HashMap<&'static str, u16>) {
-        let x = *args.get("X").unwrap() as usize;
-        let y = *args.get("Y").unwrap() as usize;
+    /// Load a program into memory, keeping a copy so that it can be reloaded on reset.
+    /// Programs larger than `max_program_size` are not loaded, unless truncation is
+    /// enabled, in which case only the part that fits is loaded (some dumps have trailing
+    /// junk) and a warning is shown. `status().program_size` tells how much was loaded.
+    pub fn load_program(&mut self, data: &[u8]) {
+        let max = self.max_program_size();
+        let loaded = if data.len() <= max {
+            data
+        }
+        else if self.truncate {
+            self.log_warning(&format!("program is {} bytes, only the first {} fit in memory and were loaded", data.len(), max));
+            self.pending_message = Some(format!("Program truncated to {} of {} bytes", max, data.len()));
+            &data[..max]
+        }
+        else {
+            self.log_warning(&format!("program is {} bytes, more than the {} that fit in memory", data.len(), max));
+            &[]
+        };
 
-        self.cpu.registers[x] = self.cpu.registers[y];
+        self.program = loaded.to_vec();
+        self.program_hash = Sha1::digest(data).into();
+        self.snapshot = None;
+        self.watchdog_warned = false;
+        self.cpu.load_program(loaded);
+        self.reset_initialized();
     }
 
-    /// Store memory address `NNN` in register `I`.
-    fn movi(&mut self, args: HashMap<&'static str, u16>) {
-        let n = *args.get("N").unwrap();
+    /// Size of the largest program that fits in memory from the start address.
+    pub fn max_program_size(&self) -> usize {
+        Self::MEMORY_SIZE - self.cpu.layout.program_addr as usize
+    }
 
-        self.cpu.i_register = n;
+    /// Mark the fonts and the program as the only initialized memory, or all memory if a
+    /// snapshot is loaded.
+    fn reset_initialized(&mut self) {
+        for addr in 0..Self::MEMORY_SIZE {
+            self.initialized[addr] = self.snapshot.is_some() || self.is_protected(addr);
+        }
     }
 
-    /// Set sound timer to value of register `VX`.
-    fn sndr(&mut self, args: HashMap<&'static str, u16>) {
-        let x = *args.get("X").unwrap() as usize;
+    /// Replace the program with a new version and restart it, for an edit-compile-run
+    /// loop. The RPL flags file and the options are kept, and with `keep_screen`, the new
+    /// version starts over the last frame of the previous one.
+    pub fn reload_rom(&mut self, data: &[u8], keep_screen: bool) {
+        let (frame_buffer, high_resolution) = (self.frame_buffer, self.high_resolution);
+
+        self.load_program(data);
+        self.soft_reset();
 
-        self.cpu.sound_timer = self.cpu.registers[x];
+        if keep_screen {
+            self.frame_buffer = frame_buffer;
+            self.high_resolution = high_resolution;
+        }
     }
 
-    /// Store current value of delay timer in register `VX`.
-    fn timr(&mut self, args: HashMap<&'static str, u16>) {
-        let x = *args.get("X").unwrap() as usize;
+    /// Load a memory snapshot (see `MemorySnapshot`) in place of a program. Execution
+    /// resumes from the captured state, which resets restore again. A warning is logged
+    /// if the snapshot was taken under another platform or quirks.
+    pub fn load_snapshot(&mut self, data: &[u8]) -> Result<(), SnapshotError> {
+        let snapshot = MemorySnapshot::from_bytes(data)?;
+        if let Some(warning) = snapshot.config_warning(self.platform, self.quirks) {
+            self.log_warning(&format!("warning: {}", warning));
+        }
 
-        self.cpu.registers[x] = self.cpu.delay_timer;
+        self.program = Vec::new();
+        self.program_hash = Sha1::digest(data).into();
+        self.snapshot = Some(snapshot);
+        self.watchdog_warned = false;
+        self.soft_reset();
+        Ok(())
+    }
+
+    /// Restore the machine to its power-on state and reload the current program, or
+    /// the loaded memory snapshot.
+    pub fn soft_reset(&mut self) {
+        self.cpu.reset();
+        match &self.snapshot {
+            Some(snapshot) => snapshot.restore(&mut self.cpu),
+            None => self.cpu.load_program(&self.program),
+        }
+        self.cls(Args::new());
+        self.high_resolution = false;
+        self.halted = false;
+        self.shutdown_requested = false;
+        self.history.clear();
+        self.fatal_error = None;
+        self.crash_dumped = false;
+        self.keypad_state = [false; Self::KEYPAD_SIZE];
+        self.wave_idx = 0;
+        self.boot_frames = 0;
+        self.rng = StdRng::seed_from_u64(self.seed);
+        if let Some(source) = &mut self.random_source {
+            source.reset();
+        }
+        self.executed.fill(false);
+        self.reset_initialized();
+        if let Some(monitor) = &mut self.stack_monitor {
+            monitor.reset();
+        }
+        self.register_page.update(&self.cpu);
     }
 
-    /// Set delay timer to value of register `VX`.
-    fn delr(&mut self, args: HashMap<&'static str, u16>) {
-        let x = *args.get("X").unwrap() as usize;
+    /// Emulation statistics, useful as reproducibility metadata.
+    pub fn counters(&self) -> Counters {
+        self.counters
+    }
 
-        self.cpu.delay_timer = self.cpu.registers[x];
+    /// Bytes of the program executed as code since the last reset. For memory snapshots,
+    /// all memory from the start of programs is covered.
+    pub fn coverage(&self) -> Coverage {
+        let start = self.cpu.layout.program_addr as usize;
+        let end = if self.snapshot.is_some() { Self::MEMORY_SIZE } else { start + self.program.len() };
+        Coverage::new(start as u16, &self.executed[start..end.min(Self::MEMORY_SIZE)])
     }
 
-    /// Set `I` to memory address of 5-byte sprite data corresponding to hex digit stored in register `VX`.
-    fn digit(&mut self, args: HashMap<&'static str, u16>) {
-        let x = *args.get("X").unwrap() as usize;
+    /// Start or stop measuring the time spent executing instructions and rendering the
+    /// video output. Timings start from zero.
+    pub fn set_timing(&mut self, enabled: bool) {
+        self.timings = enabled.then(Timings::default);
+    }
 
-        let x_val = self.cpu.registers[x] as usize % Self::KEYPAD_SIZE;
-        self.cpu.i_register = (x_val * Self::DIGIT_SIZE) as u16;
+    pub fn timings(&self) -> Option<Timings> {
+        self.timings
     }
 
-    /// Set `I` to memory address of 10-byte sprite data corresponding to  hex digit stored in register `VX`.
-    /// Only digits `0-9` have -resolution sprite representations. **SUPER-CHIP instruction.**
-    fn ldigit(&mut self, args: HashMap<&'static str, u16>) {
-        let x = *args.get("X").unwrap() as usize;
+    /// Start or stop counting the reads and writes of each memory address made by
+    /// instructions. Counting starts from zero and, like `Counters`, is not cleared by
+    /// resets.
+    pub fn set_heatmap(&mut self, enabled: bool) {
+        self.heatmap = enabled.then(|| Box::new(MemoryHeatmap::new(Self::MEMORY_SIZE)));
+    }
 
-        let x_val = self.cpu.registers[x] as usize % Self::KEYPAD_SIZE;
-        self.cpu.i_register = (Self::LARGE_DIGIT_OFFSET + x_val * Self::LARGE_DIGIT_SIZE) as u16;
+    /// Memory accesses counted since `set_heatmap` was enabled.
+    pub fn heatmap(&self) -> Option<&MemoryHeatmap> {
+        self.heatmap.as_deref()
     }
 
-    /// Add value of register `VX` to register `I`.
-    fn addi(&mut self, args: HashMap<&'static str, u16>) {
-        let x = *args.get("X").unwrap() as usize;
+    /// Start or stop checking the use of the stack (see `StackMonitor`), reporting
+    /// subroutines with more than `max_recursion` calls active at once and stacks that keep
+    /// growing. Problems are logged to standard error and the trace file.
+    pub fn set_stack_monitor(&mut self, max_recursion: Option<usize>) {
+        self.stack_monitor = max_recursion.map(StackMonitor::new);
+    }
 
-        let x_val = self.cpu.registers[x] as u16;
-        let i_val = self.cpu.i_register;
+    /// Stack checks since the last reset, if enabled.
+    pub fn stack_monitor(&self) -> Option<&StackMonitor> {
+        self.stack_monitor.as_ref()
+    }
 
-        self.cpu.i_register = i_val.wrapping_add(x_val);
+    /// Writes to memory that was previously executed, for reverse engineering programs
+    /// that modify themselves.
+    pub fn code_writes(&self) -> &CodeWrites {
+        &self.code_writes
     }
 
-    /// Wait for keypress and store result in register `VX`.
-    fn key(&mut self, args: HashMap<&'static str, u16>) {
-        let x = *args.get("X").unwrap() as usize;
+    /// Unsupported instructions executed so far, in ascending order. Only recorded while
+    /// opcode warnings are enabled.
+    pub fn unsupported_opcodes(&self) -> Vec<u16> {
+        let mut opcodes: Vec<u16> = self.warned_opcodes.iter().copied().collect();
+        opcodes.sort();
+        opcodes
+    }
 
-        self.cpu.store_keypress = Some(x);
+    /// Error that stopped emulation (a blocked write with memory protection set to
+    /// `fault`), if any since the last reset.
+    pub fn fatal_error(&self) -> Option<&str> {
+        self.fatal_error.as_deref()
     }
 
-    // Skip following instruction if key corresponding to hex value in `VX` is pressed.
-    fn skpk(&mut self, args: HashMap<&'static str, u16>) {
-        let x = *args.get("X").unwrap() as usize;
+    /// Write a crash dump for a fatal error: the memory snapshot to `path` with the `ox8d`
+    /// extension, and a text report with the registers, the last instructions executed
+    /// and the disassembly around the failing instruction to `path` (see `crash::report`).
+    pub fn write_crash_dump(&self, reason: &str, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        let snapshot_path = path.with_extension("ox8d");
+        let snapshot = self.dump_memory();
 
-        let x_val = self.cpu.registers[x] as usize % Self::KEYPAD_SIZE;
-        
-        if self.keypad_state[x_val] {
-            self.cpu.pc += 2;
-        }
+        std::fs::write(&snapshot_path, snapshot.to_bytes())?;
+        let name = snapshot_path.file_name().map(|name| name.to_string_lossy());
+        std::fs::write(path, crash::report(reason, &snapshot, &self.history, name.as_deref()))
     }
 
-    // Skip following instruction if key corresponding to hex value in `VX` is not pressed.
-    fn skpnk(&mut self, args: HashMap<&'static str, u16>) {
-        let x = *args.get("X").unwrap() as usize;
-
-        let x_val = self.cpu.registers[x] as usize % Self::KEYPAD_SIZE;
-        
-        if !self.keypad_state[x_val] {
-            self.cpu.pc += 2;
+    /// Render the screen as text at its current resolution, for logs and test failure
+    /// messages.
+    pub fn screen_text(&self, style: TextStyle) -> String {
+        if self.high_resolution {
+            return video::to_text(&self.frame_buffer, Self::SCREEN_WIDTH, style);
         }
+
+        // Each low-resolution pixel spans 2x2 frame buffer pixels
+        let rows: Vec<u128> = self.frame_buffer.iter()
+            .step_by(2)
+            .map(|&row| (0..Self::SCREEN_WIDTH / 2)
+                .filter(|&x| video::pixel(row, 2 * x))
+                .fold(0, |acc, x| acc | 1 << (127 - x)))
+            .collect();
+        video::to_text(&rows, Self::SCREEN_WIDTH / 2, style)
     }
 
-    /// Store value of `VY` in `VX` shifted right one bit. Set `VF` to least
-    /// significant bit prior to shift. `VX` is shifted instead if the "shift" quirk is active.
-    fn shr(&mut self, args: HashMap<&'static str, u16>) {
-        let x = *args.get("X").unwrap() as usize;
-        let y = *args.get("Y").unwrap() as usize;
+    /// Hash of the screen contents and resolution, for comparing frames cheaply (e.g. in
+    /// regression tests) without storing full images. Use `screen_text` to show the
+    /// screen when hashes differ.
+    pub fn framebuffer_hash(&self) -> u64 {
+        let hash = video::hash(&self.frame_buffer, Self::SCREEN_WIDTH);
+        if self.high_resolution { !hash } else { hash }
+    }
 
-        let y_val = if self.quirk_shift { self.cpu.registers[x] } else { self.cpu.registers[y] };
+    /// Capture the full memory image, registers and stack.
+    pub fn dump_memory(&self) -> MemorySnapshot {
+        MemorySnapshot { config: Some((self.platform, self.quirks)), ..MemorySnapshot::from_cpu(&self.cpu) }
+    }
 
-        // Store least significant bit in VF
-        self.cpu.registers[0xF] = y_val & 0x01;
-        self.cpu.registers[x] = y_val >> 1;
+    /// Write the full memory image, registers and stack to a file (see `MemorySnapshot`).
+    pub fn dump_memory_to_file(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        std::fs::write(path, self.dump_memory().to_bytes())
     }
 
-    /// Store value of `VY` in `VX` shifted left one bit. Set `VF` to most
-    /// significant bit prior to shift. `VX` is shifted instead if the "shift" quirk is active.
-    fn shl(&mut self, args: HashMap<&'static str, u16>) {
-        let x = *args.get("X").unwrap() as usize;
-        let y = *args.get("Y").unwrap() as usize;
+    /// Hash of the registers, timers, stack and `I`, complementing `framebuffer_hash` in
+    /// traces.
+    pub fn registers_hash(&self) -> u64 {
+        let cpu = &self.cpu;
+        let mut hash = video::fnv1a(video::FNV_OFFSET_BASIS, &cpu.registers);
+        hash = video::fnv1a(hash, &cpu.i_register.to_be_bytes());
+        hash = video::fnv1a(hash, &cpu.pc.to_be_bytes());
+        hash = video::fnv1a(hash, &[cpu.delay_timer, cpu.sound_timer, cpu.stack.len() as u8]);
+        cpu.stack.iter().fold(hash, |hash, addr| video::fnv1a(hash, &addr.to_be_bytes()))
+    }
 
-        let y_val = if self.quirk_shift { self.cpu.registers[x] } else { self.cpu.registers[y] };
+    /// Append a line with the frame number, screen hash and register hash to a file after
+    /// every frame, to find the first frame where two emulator versions diverge, e.g. by
+    /// comparing traces with `diff`.
+    pub fn set_trace_file(&mut self, path: impl AsRef<Path>) -> io::Result<()> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        self.trace = Some(BufWriter::new(file));
+        Ok(())
+    }
 
-        // Store most significant bit in VF
-        self.cpu.registers[0xF] = (y_val & 0x80) >> 7;
-        self.cpu.registers[x] = y_val << 1;
+    /// Update the keypad with polled input, scripted input and key events.
+    fn apply_input(&mut self, input: KeypadState) {
+        let input = KeypadState(input.0 | self.input_script.state(self.boot_frames).0);
+        let input = self.key_tracker.frame_state(input);
+        self.set_keypad(input);
     }
 
-    /// Set 'VX' to 'VX' OR 'VY'.
-    fn or(&mut self, args: HashMap<&'static str, u16>) {
-        let x: usize = *args.get("X").unwrap() as usize;
-        let y = *args.get("Y").unwrap() as usize;
+    fn write_trace(&mut self) {
+        let (frame, screen, registers) = (self.counters.frames, self.framebuffer_hash(), self.registers_hash());
 
-        self.cpu.registers[x] |= self.cpu.registers[y];
+        if let Some(trace) = &mut self.trace {
+            if let Err(e) = writeln!(trace, "{} {:016x} {:016x}", frame, screen, registers) {
+                // Not through `log_warning`, which would write to the failing trace
+                eprintln!("oxid-8: cannot write trace: {}", e);
+                self.trace = None;
+            }
+        }
     }
 
-    /// Set `VX` to `VX` AND `VY`.
-    fn and(&mut self, args: HashMap<&'static str, u16>) {
-        let x = *args.get("X").unwrap() as usize;
-        let y = *args.get("Y").unwrap() as usize;
+    pub fn execute_instruction(&mut self) {
+        let pc = self.cpu.pc as usize;
+        self.executed[pc % Self::MEMORY_SIZE] = true;
+        self.executed[(pc + 1) % Self::MEMORY_SIZE] = true;
 
-        self.cpu.registers[x] &= self.cpu.registers[y];
-    }
+        let raw_instruction = self.cpu.fetch_instruction();
+        self.history.record(pc as u16, raw_instruction);
+        let instruction = self.cpu.decode_instruction(raw_instruction);
+        let (name, callback, args) = (instruction.name(), instruction.callback, instruction.args(raw_instruction));
 
-    /// Set `VX` to `VX` XOR `VY`.
-    fn xor(&mut self, args: HashMap<&'static str, u16>) {
-        let x = *args.get("X").unwrap() as usize;
-        let y = *args.get("Y").unwrap() as usize;
+        // `00NN` instructions of extensions (e.g. XO-CHIP `00DN`) are not machine code calls,
+        // and are reported as unsupported instructions instead
+        let machine_code = raw_instruction & 0xF000 == 0 && detect::instruction_extension(raw_instruction).is_none();
+        if name == "NOP" && raw_instruction != 0x0000 && machine_code {
+            self.report_machine_code_call(raw_instruction & 0x0FFF);
+        }
+        else if self.opcode_warnings && name == "NOP" && self.warned_opcodes.insert(raw_instruction) {
+            let mut message = format!("unsupported instruction {:04X} at 0x{:03X} ignored", raw_instruction, self.cpu.pc.wrapping_sub(2));
+            if let Some(hint) = detect::instruction_hint(self.platform, raw_instruction) {
+                message += &format!(" ({})", hint);
+            }
+            self.log_warning(&message);
+        }
 
-        self.cpu.registers[x] ^= self.cpu.registers[y];
+        callback(self, args);
+        self.counters.instructions += 1;
     }
 
-    /// Draw a sprite at `(VX, VY)` with `N` bytes of sprite data starting at
-    /// address stored in `I`. Set `VF` to `01` if any pixels are set to black,
-    /// `00` otherwise.
-    fn draw(&mut self, args: HashMap<&'static str, u16>) {
-        let x = *args.get("X").unwrap() as usize;
-        let y = *args.get("Y").unwrap() as usize;
-        let mut n = *args.get("N").unwrap() as usize;
+    /// Execute up to `max` instructions (at least one), returning the number executed.
+    /// The threaded-code engine replaces this with block execution.
+    #[cfg(not(feature = "threaded"))]
+    fn execute(&mut self, _max: usize) -> usize {
+        self.execute_instruction();
+        1
+    }
 
-        let scaling_factor = !self.high_resolution as usize + 1;
+    /// Commit the working frame buffer for display, rendering the video output only if the
+    /// presented frame changed.
+    fn present(&mut self) {
+        if !self.video_dirty && self.presented == self.frame_buffer {
+            return;
+        }
 
-        let mut columns = 8;
-        let draw_large_sprite = (self.high_resolution || self.quirk_lores16) && n == 0;
-        let addr_scaling_factor = draw_large_sprite as usize + 1;
+        if let Some((_, frame)) = &mut self.palette_fade {
+            *frame += 1;
+        }
+        let palette = self.displayed_palette();
+        if self.palette_fade.is_some_and(|(_, frame)| frame >= Self::PALETTE_FADE_FRAMES) {
+            self.palette_fade = None;
+        }
 
-        if draw_large_sprite {
-            n = 16;
-            columns = 16;
+        self.presented = self.frame_buffer;
+        // Keep rendering until the fade is complete
+        self.video_dirty = self.palette_fade.is_some();
+        video::render(&self.presented, Self::SCREEN_WIDTH, palette.rgb565(1), palette.rgb565(0), self.video_filter, &mut self.video_frame);
+    }
+
+    /// Emulate a single video frame with the given keypad state: update the timers,
+    /// execute the instructions of the frame and produce the video and audio output.
+    pub fn run_frame(&mut self, input: KeypadState) -> FrameOutput<'_> {
+        self.run_frame_polled(|| input)
+    }
+
+    /// Emulate a single video frame like `run_frame`, reading the keypad state with `poll`
+    /// right before the instructions of the frame run. If mid-frame polling is enabled,
+    /// the keypad is polled again halfway through the instructions, reducing input
+    /// latency.
+    pub fn run_frame_polled(&mut self, mut poll: impl FnMut() -> KeypadState) -> FrameOutput<'_> {
+        if self.paused {
+            self.present();
+            let (width, height) = self.video_filter.output_size(Self::SCREEN_WIDTH, Self::SCREEN_HEIGHT);
+            return FrameOutput { video: self.video_frame.as_slice(), width, height, pitch: 2 * width, audio: &[] };
         }
 
-        let mut x_val = self.cpu.registers[x] as usize;
-        if !self.high_resolution { x_val *= 2; }
-        x_val %= Self::SCREEN_WIDTH;
+        self.counters.frames += 1;
 
-        let mut y_val = self.cpu.registers[y] as usize;
-        if !self.high_resolution { y_val *= 2; }
-        y_val %= Self::SCREEN_HEIGHT;
+        // Update timers, which tick once per frame at normal speed
+        let speed_percent = self.effective_speed_percent();
+        self.timer_credit += speed_percent;
+        let ticks = (self.timer_credit / 100).min(u8::MAX as usize) as u8;
+        self.timer_credit %= 100;
 
-        /* In low resolution mode, equal to 0x01 if a white pixel was set to black when drawing the sprite.
-           In  resolution mode, equal to the number of sprite rows where this occurred or that were clipped
-           by the bottom of the screen, assuming the "collision quirk" is active. */
-        let mut black = 0x00;
-        let mut row_black;
+        let delay_timer = &mut self.cpu.delay_timer;
+        let sound_timer = &mut self.cpu.sound_timer;
 
-        let height = usize::min(n, (Self::SCREEN_HEIGHT - y_val) / scaling_factor);
-        for i in 0..height {
-            row_black = false;
+        *delay_timer = delay_timer.saturating_sub(ticks);
+        *sound_timer = sound_timer.saturating_sub(ticks);
 
-            let addr = self.cpu.i_register as usize + i * addr_scaling_factor;
-            let sprite_data = u16::from_be_bytes(
-                if draw_large_sprite {
-                    self.cpu.memory[addr..=addr + 1].try_into().unwrap()
-                }
-                else {
-                    [self.cpu.memory[addr], 0x00]
-                }
-            );
+        self.instruction_credit += self.instructions_per_frame * speed_percent;
+        let instructions = self.instruction_credit / 100;
+        self.instruction_credit %= 100;
 
-            for offset_i in 0..scaling_factor {
-                let row = &mut self.frame_buffer[y_val + i * scaling_factor + offset_i];
-                let width = usize::min(columns, (Self::SCREEN_WIDTH - x_val) / scaling_factor);
+        self.frame_draws = 0;
+        self.draw_stalled = false;
+
+        self.apply_input(poll());
+        let last_key = self.cpu.last_keypress;
+        self.cpu.last_keypress = last_key.and_then(|k| if self.keypad_state[k] { last_key } else { None });
+
+        // Number of remaining instructions at which the keypad is polled again
+        let mut mid_frame_poll = self.mid_frame_poll.then_some(instructions / 2);
+        let start = (self.timings.is_some() || self.frame_budget.is_some()).then(Instant::now);
+        // Instructions executed since the watchdog last checked the time
+        let mut unchecked = 0;
+
+        // Whether the sound timer was running at the end of each chunk of audio, and the
+        // number of remaining instructions at which a chunk ends
+        let chunks = self.audio_chunks;
+        let mut sound = [false; Self::MAX_AUDIO_CHUNKS];
+        let mut chunk = 0;
+        let chunk_end = |chunk: usize| instructions - instructions * (chunk + 1) / chunks;
+
+        // Waiting for a keypress consumes cycles of the frame budget like any other
+        // instruction, so that execution resumes at the same pace once a key is pressed.
+        let mut remaining = instructions;
+        while remaining > 0 && !self.halted && !self.draw_stalled {
+            while chunk + 1 < chunks && remaining <= chunk_end(chunk) {
+                sound[chunk] = self.cpu.sound_timer != 0;
+                chunk += 1;
+            }
 
-                for j in 0..width {
-                    let sprite_bit = *sprite_data.view_bits::<Msb0>().get(j).unwrap();
+            if mid_frame_poll.is_some_and(|at| remaining <= at) {
+                self.apply_input(poll());
+                mid_frame_poll = None;
+            }
 
-                    for offset_j in 0..scaling_factor {
-                        let screen_bit_ref = &mut row[x_val + j * scaling_factor + offset_j];
+            if self.cpu.store_keypress.is_some() {
+                self.poll_keypress(last_key);
+                remaining -= 1;
+            }
+            else {
+                let max = (remaining - mid_frame_poll.unwrap_or(0)).min(remaining - chunk_end(chunk));
+                let executed = self.execute(max.min(Self::WATCHDOG_INTERVAL - unchecked));
+                remaining -= executed;
+                unchecked += executed;
+            }
 
-                        row_black |= *screen_bit_ref && sprite_bit;
-                        *screen_bit_ref ^= sprite_bit;
+            if unchecked >= Self::WATCHDOG_INTERVAL {
+                unchecked = 0;
+                if let (Some(budget), Some(start)) = (self.frame_budget, start) {
+                    if start.elapsed() > budget && remaining > 0 {
+                        self.clamp_frame(budget, instructions, remaining);
+                        remaining = 0;
                     }
                 }
             }
+        }
+        sound[chunk..chunks].fill(self.cpu.sound_timer != 0);
+        self.boot_frames += 1;
 
-            if self.high_resolution && self.quirk_collision {
-                black += row_black as u8;
+        let depth = self.cpu.stack.len();
+        if let Some(warning) = self.stack_monitor.as_mut().and_then(|m| m.end_frame(depth)) {
+            self.log_warning(&warning.to_string());
+        }
+
+        let executed = start.map(|start| Instant::now() - start);
+        self.present();
+
+        if let (Some(timings), Some(executed)) = (&mut self.timings, executed) {
+            timings.frames += 1;
+            timings.execute += executed;
+            timings.render += Instant::now() - start.unwrap() - executed;
+        }
+        let (width, height) = self.video_filter.output_size(Self::SCREEN_WIDTH, Self::SCREEN_HEIGHT);
+
+        self.write_trace();
+
+        let idx = self.wave_idx * Self::AUDIO_FRAME_SIZE;
+        self.wave_idx += 1;
+        self.wave_idx %= Self::MAX_WAVE_IDX;
+
+        let muted = self.fast_forward && self.fast_forward_mute;
+        let sound = &sound[..chunks];
+        let audio: &[i16] = if muted || !sound.contains(&true) {
+            &[]
+        }
+        else if !sound.contains(&false) {
+            &self.wave[idx..idx + Self::AUDIO_FRAME_SIZE]
+        }
+        else {
+            let size = Self::AUDIO_FRAME_SIZE / chunks;
+            for (i, &on) in sound.iter().enumerate() {
+                let part = &mut self.audio_frame[i * size..(i + 1) * size];
+                if on {
+                    part.copy_from_slice(&self.wave[idx + i * size..idx + (i + 1) * size]);
+                }
+                else {
+                    part.fill(0);
+                }
+            }
+            &self.audio_frame
+        };
+
+        if let Some(output) = &mut self.audio_output {
+            if audio.is_empty() {
+                output.push_silence(Self::AUDIO_FRAME_SIZE);
             }
             else {
-                black |= row_black as u8;
+                output.push(audio);
             }
         }
 
-        if self.quirk_collision {
-            black += (n - height) as u8;
+        self.register_page.update(&self.cpu);
+
+        FrameOutput {
+            video: self.video_frame.as_slice(),
+            width,
+            height,
+            pitch: 2 * width,
+            audio,
         }
-        self.cpu.registers[0xF] = black;
     }
 
-    /// Set `VX` to random number with mask `NN`.
-    fn rand(&mut self, args: HashMap<&'static str, u16>) {
+    /// Skip the remaining instructions of a frame that exceeded its time budget, warning
+    /// the first time since the program was loaded.
+    fn clamp_frame(&mut self, budget: Duration, instructions: usize, remaining: usize) {
+        self.counters.clamped_frames += 1;
+        if self.watchdog_warned {
+            return;
+        }
+
+        self.watchdog_warned = true;
+        self.log_warning(&format!(
+            "frame took over {} ms, {} of its {} instructions skipped; lower the instructions per frame or the speed",
+            budget.as_millis(), remaining, instructions,
+        ));
+        self.pending_message = Some("Emulation too slow, frames are cut short; lower the instructions per frame".to_string());
+    }
+
+    /// Complete a pending `KEY` instruction if a key is pressed, ignoring the key stored
+    /// by the previous `KEY` instruction until it is released. If `key_edge` is enabled,
+    /// keys that were already held when the wait started are also ignored until released.
+    /// When several keys qualify, the one pressed most recently is stored.
+    ///
+    /// If the "key release" quirk is active, the instruction instead completes when a key
+    /// pressed during the wait is released, storing the key released most recently.
+    fn poll_keypress(&mut self, last_key: Option<usize>) {
+        for (held, &pressed) in self.key_wait_held.iter_mut().zip(&self.keypad_state) {
+            *held &= pressed;
+        }
+
+        let Some(reg) = self.cpu.store_keypress else { return };
+        let key_edge = self.key_edge;
+        let held = self.key_wait_held;
+
+        let key = if self.quirks.key_release {
+            for (key, wait_pressed) in self.key_wait_pressed.iter_mut().enumerate() {
+                *wait_pressed |= self.keypad_state[key] && !(key_edge && held[key]);
+            }
+
+            (0..Self::KEYPAD_SIZE)
+                .filter(|&key| self.key_wait_pressed[key] && !self.keypad_state[key])
+                .max_by_key(|&key| self.key_tracker.release_order(key))
+        }
+        else {
+            (0..Self::KEYPAD_SIZE)
+                .filter(|&key| self.keypad_state[key] && last_key != Some(key) && !(key_edge && held[key]))
+                .max_by_key(|&key| self.key_tracker.press_order(key))
+        };
+
+        if let Some(key) = key {
+            self.cpu.registers[reg] = key as u8;
+            self.cpu.store_keypress = None;
+            self.cpu.last_keypress = if self.quirks.key_release { None } else { Some(key) };
+        }
+    }
+
+    /// Whether an address holds a font or the loaded program.
+    fn is_protected(&self, addr: usize) -> bool {
+        let layout = self.cpu.layout;
+        let small = layout.font_addr as usize..layout.font_addr as usize + cpu::font::SMALL_SIZE;
+        let large = layout.large_font_addr as usize..layout.large_font_addr as usize + cpu::font::LARGE_SIZE;
+        let program = layout.program_addr as usize..layout.program_addr as usize + self.program.len();
+
+        small.contains(&addr) || large.contains(&addr) || program.contains(&addr)
+    }
+
+    /// Write a byte to memory on behalf of an instruction, wrapping around at the end
+    /// of memory and applying memory protection. Returns `false` if the write was blocked.
+    fn write_memory(&mut self, addr: usize, value: u8) -> bool {
+        let addr = addr % self.cpu.memory.len();
+
+        if self.memory_protection != MemoryProtection::Disabled && self.is_protected(addr) {
+            let pc = self.cpu.pc.wrapping_sub(2);
+            if self.protection_warnings.insert(pc) {
+                self.log_warning(&format!("instruction at 0x{:03X} writes to protected address 0x{:03X} (I = 0x{:03X})",
+                    pc, addr, self.cpu.i_register));
+            }
+
+            if self.memory_protection == MemoryProtection::Fault {
+                self.halted = true;
+                self.fatal_error = Some(format!("instruction at 0x{:03X} wrote to protected address 0x{:03X}", pc, addr));
+                return false;
+            }
+        }
+
+        if self.executed[addr] {
+            self.code_writes.writes += 1;
+            *self.code_writes.addresses.entry(addr as u16).or_default() += 1;
+        }
+
+        if let Some(heatmap) = &mut self.heatmap {
+            heatmap.record_write(addr);
+        }
+        self.initialized[addr] = true;
+
+        self.cpu.memory[addr] = value;
+        true
+    }
+
+    /// Record a read of memory by an instruction, for the heatmap and for reporting
+    /// reads of uninitialized memory (to standard error and the trace file, once per
+    /// instruction).
+    fn record_read(&mut self, addr: usize) {
+        let addr = addr % Self::MEMORY_SIZE;
+
+        if let Some(heatmap) = &mut self.heatmap {
+            heatmap.record_read(addr);
+        }
+
+        if self.uninit_warnings && !self.initialized[addr] {
+            let pc = self.cpu.pc.wrapping_sub(2);
+            if self.uninit_read_warnings.insert(pc) {
+                self.log_warning(&format!("instruction at 0x{:03X} reads uninitialized address 0x{:03X} (I = 0x{:03X})",
+                    pc, addr, self.cpu.i_register));
+            }
+        }
+    }
+
+    /// Log a problem found in the program or its state to standard error and the trace file.
+    fn log_warning(&mut self, message: &str) {
+        eprintln!("oxid-8: {}", message);
+
+        if let Some(trace) = &mut self.trace {
+            let _ = writeln!(trace, "# {}", message);
+        }
+    }
+
+    /// Report a call to a machine code routine (`0NNN`), which ran native code of the RCA
+    /// 1802 processor on the COSMAC VIP and cannot be emulated. The call is ignored, so the
+    /// program will likely misbehave, which the user is told on screen the first time.
+    fn report_machine_code_call(&mut self, routine: u16) {
+        if !self.machine_code_calls.insert(routine) {
+            return;
+        }
+
+        self.log_warning(&format!("program calls machine code routine at 0x{:03X} (from 0x{:03X}), which is not supported and is ignored",
+            routine, self.cpu.pc.wrapping_sub(2)));
+        if self.machine_code_calls.len() == 1 {
+            self.pending_message = Some("Program uses 1802 machine code, which is not supported".to_string());
+        }
+    }
+
+    /// No operation.
+    fn nop(&mut self, _args: Args) {
+
+    }
+
+    /// Clear the screen.
+    fn cls(&mut self, _args: Args) {
+        self.frame_buffer.fill(0);
+    }
+
+    /// Jump to address `NNN`.
+    fn jmp(&mut self, args: Args) {
+        let n = *args.get("N").unwrap();
+
+        self.cpu.pc = n;
+    }
+
+    /// Execute subroutine starting at address `NNN`.
+    fn call(&mut self, args: Args) {
+        let n = *args.get("N").unwrap();
+
+        let from = self.cpu.pc.wrapping_sub(2);
+        if let Some(warning) = self.stack_monitor.as_mut().and_then(|m| m.call(from, n)) {
+            self.log_warning(&warning.to_string());
+        }
+
+        self.cpu.stack.push(self.cpu.pc);
+        self.cpu.pc = n;
+    }
+
+    /// Return from a subroutine.
+    fn ret(&mut self, _args: Args) {
+        if let Some(stack_top) = self.cpu.stack.pop() {
+            self.cpu.pc = stack_top;
+
+            if let Some(monitor) = &mut self.stack_monitor {
+                monitor.ret();
+            }
+        }
+    }
+
+    /// Number of frame buffer pixels corresponding to one pixel of scrolling. In
+    /// low-resolution mode, a logical pixel spans two frame buffer pixels, unless the
+    /// "scroll" quirk is active, in which case scrolling moves by half a logical pixel.
+    fn scroll_scale(&self) -> usize {
+        if self.high_resolution || self.quirks.scroll { 1 } else { 2 }
+    }
+
+    /// Scroll display down by `N` pixels, or `N/2` pixels in low-resolution mode if the
+    /// "scroll" quirk is active. **SUPER-CHIP instruction.**
+    fn scd(&mut self, args: Args) {
+        let n = *args.get("N").unwrap() as usize * self.scroll_scale() % Self::SCREEN_HEIGHT;
+        if n == 0 {
+            return;
+        }
+
+        self.frame_buffer.copy_within(..Self::SCREEN_HEIGHT - n, n);
+        self.frame_buffer[..n].fill(0);
+    }
+
+    /// Scroll display right by 4 pixels, or 2 in low-resolution mode if the "scroll"
+    /// quirk is active. **SUPER-CHIP instruction.**
+    fn scr(&mut self, _args: Args) {
+        let pixels = 4 * self.scroll_scale();
+
+        for row in &mut self.frame_buffer {
+            *row >>= pixels;
+        }
+    }
+
+    /// Scroll display left by 4 pixels, or 2 in low-resolution mode if the "scroll"
+    /// quirk is active. **SUPER-CHIP instruction.**
+    fn scl(&mut self, _args: Args) {
+        let pixels = 4 * self.scroll_scale();
+
+        for row in &mut self.frame_buffer {
+            *row <<= pixels;
+        }
+    }
+
+    /// Exit the interpreter. Execution stops until the core is reset, while the last
+    /// frame remains visible. Depending on the configured exit action, the frontend is
+    /// also asked to unload the content. **SUPER-CHIP instruction.**
+    fn exit(&mut self, _args: Args) {
+        self.halted = true;
+    }
+
+    /// Disable high-resolution mode. **SUPER-CHIP instruction.**
+    fn lores(&mut self, _args: Args) {
+        self.high_resolution = false;
+        if self.quirks.resolution {
+            self.cls(Args::new());
+        }
+    }
+
+    /// Enable high-resolution mode. **SUPER-CHIP instruction.**
+    fn hires(&mut self, _args: Args) {
+        self.high_resolution = true;
+        if self.quirks.resolution {
+            self.cls(Args::new());
+        }
+    }
+    
+    /// Skip following instruction if value of register `VX` equals `NN`.
+    fn skpeq(&mut self, args: Args) {
+        let x = *args.get("X").unwrap() as usize;
+        let n = *args.get("N").unwrap() as u8;
+
+        let x_val = self.cpu.registers[x];
+
+        if x_val == n {
+            self.cpu.pc += 2;
+        }
+    }
+
+    /// Skip following instruction if value of register `VX` does not equals `NN`.
+    fn skpne(&mut self, args: Args) {
         let x = *args.get("X").unwrap() as usize;
         let n = *args.get("N").unwrap() as u8;
 
-        let rand: u8 = rand::thread_rng().gen();
+        let x_val = self.cpu.registers[x];
+
+        if x_val != n {
+            self.cpu.pc += 2;
+        }
+    }
+
+    /// Skip following instruction if value of register `VX` is equal to value of register `VY`.
+    fn skpeqr(&mut self, args: Args) {
+        let x = *args.get("X").unwrap() as usize;
+        let y = *args.get("Y").unwrap() as usize;
+
+        let x_val = self.cpu.registers[x];
+        let y_val = self.cpu.registers[y];
+
+        if x_val == y_val {
+            self.cpu.pc += 2;
+        }
+    }
+
+    /// Skip following instruction if value of register `VX` is not equal to `VY`.
+    fn skpner(&mut self, args: Args) {
+        let x = *args.get("X").unwrap() as usize;
+        let y = *args.get("Y").unwrap() as usize;
+
+        let x_val = self.cpu.registers[x];
+        let y_val = self.cpu.registers[y];
+
+        if x_val != y_val {
+            self.cpu.pc += 2;
+        }
+    }
+
+    /// Jump to address `NNN + V0`.
+    fn jmpr(&mut self, args: Args) {
+        let n = *args.get("N").unwrap();
+        let reg_val = self.cpu.registers[0x0] as u16;
+        let mem_size = self.cpu.memory.len() as u16;
+
+        self.cpu.pc = (n + reg_val) % mem_size;
+    }
+
+    /// Add value of register `VY` to register `VX`. Set `VF` to `01` if carry
+    /// occurs, `00` otherwise.
+    fn addr(&mut self, args: Args) {
+        let x = *args.get("X").unwrap() as usize;
+        let y = *args.get("Y").unwrap() as usize;
+
+        let x_val = self.cpu.registers[x];
+        let y_val = self.cpu.registers[y];
+
+        let (result, carry) = x_val.overflowing_add(y_val);
+
+        self.cpu.registers[x] = result;
+        self.cpu.registers[0xF] = carry as u8;
+    }
+
+    /// Subtract value of register `VY` from register `VX`. Set `VF` to `00` if a borrow
+    /// occurs, `01` otherwise.
+    fn subr(&mut self, args: Args) {
+        let x = *args.get("X").unwrap() as usize;
+        let y = *args.get("Y").unwrap() as usize;
+
+        let x_val = self.cpu.registers[x];
+        let y_val = self.cpu.registers[y];
+
+        let (result, borrow) = x_val.overflowing_sub(y_val);
+
+        self.cpu.registers[x] = result;
+        self.cpu.registers[0xF] = !borrow as u8;
+    }
+
+    /// Set `VX` to value of `VY` minus `VX`. Set `VF` to `00` if a borrow
+    /// occurs, `01` otherwise.
+    fn rsubr(&mut self, args: Args) {
+        let x = *args.get("X").unwrap() as usize;
+        let y = *args.get("Y").unwrap() as usize;
+
+        let x_val = self.cpu.registers[x];
+        let y_val = self.cpu.registers[y];
+
+        let (result, borrow) = y_val.overflowing_sub(x_val);
+
+        self.cpu.registers[x] = result;
+        self.cpu.registers[0xF] = !borrow as u8;
+    }
+
+    /// Store `NN` in register `VX`.
+    fn mov(&mut self, args: Args) {
+        let x = *args.get("X").unwrap() as usize;
+        let n = *args.get("N").unwrap() as u8;
+
+        self.cpu.registers[x] = n;
+    }
+
+    /// Add `NN` to register `VX`.
+    fn add(&mut self, args: Args) {
+        let x = *args.get("X").unwrap() as usize;
+        let n = *args.get("N").unwrap() as u8;
+
+        let x_val = self.cpu.registers[x];
+
+        self.cpu.registers[x] = x_val.wrapping_add(n);
+    }
+
+    /// Store value of register `VY` in register `VX`.
+    fn movr(&mut self, args: Args) {
+        let x = *args.get("X").unwrap() as usize;
+        let y = *args.get("Y").unwrap() as usize;
+
+        self.cpu.registers[x] = self.cpu.registers[y];
+    }
+
+    /// Store memory address `NNN` in register `I`.
+    fn movi(&mut self, args: Args) {
+        let n = *args.get("N").unwrap();
+
+        self.cpu.i_register = n;
+    }
+
+    /// Set sound timer to value of register `VX`. Values too small to be heard on the
+    /// platform (see `Platform::min_sound_timer`) leave the timer stopped.
+    fn sndr(&mut self, args: Args) {
+        let x = *args.get("X").unwrap() as usize;
+        let value = self.cpu.registers[x];
+
+        self.cpu.sound_timer = if value < self.platform.min_sound_timer() { 0 } else { value };
+    }
+
+    /// Store current value of delay timer in register `VX`.
+    fn timr(&mut self, args: Args) {
+        let x = *args.get("X").unwrap() as usize;
+
+        self.cpu.registers[x] = self.cpu.delay_timer;
+    }
+
+    /// Set delay timer to value of register `VX`.
+    fn delr(&mut self, args: Args) {
+        let x = *args.get("X").unwrap() as usize;
+
+        self.cpu.delay_timer = self.cpu.registers[x];
+    }
+
+    /// Set `I` to memory address of 5-byte sprite data corresponding to hex digit stored in register `VX`.
+    fn digit(&mut self, args: Args) {
+        let x = *args.get("X").unwrap() as usize;
+
+        let x_val = self.cpu.registers[x] as usize % Self::KEYPAD_SIZE;
+        self.cpu.i_register = (self.cpu.layout.font_addr as usize + x_val * Self::DIGIT_SIZE) as u16;
+    }
+
+    /// Set `I` to memory address of 10-byte sprite data corresponding to  hex digit stored in register `VX`.
+    /// Digits `A-F` wrap around to `0-5` if the large font only covers decimal digits.
+    /// **SUPER-CHIP instruction.**
+    fn ldigit(&mut self, args: Args) {
+        let x = *args.get("X").unwrap() as usize;
+
+        let digits = if self.cpu.layout.large_font_hex { Self::KEYPAD_SIZE } else { 10 };
+        let x_val = self.cpu.registers[x] as usize % Self::KEYPAD_SIZE % digits;
+        self.cpu.i_register = (self.cpu.layout.large_font_addr as usize + x_val * Self::LARGE_DIGIT_SIZE) as u16;
+    }
+
+    /// Add value of register `VX` to register `I`.
+    fn addi(&mut self, args: Args) {
+        let x = *args.get("X").unwrap() as usize;
+
+        let x_val = self.cpu.registers[x] as u16;
+        let i_val = self.cpu.i_register;
+
+        self.cpu.i_register = i_val.wrapping_add(x_val);
+    }
+
+    /// Wait for keypress and store result in register `VX`. If a key is set to be pressed
+    /// automatically, it is stored without waiting.
+    fn key(&mut self, args: Args) {
+        let x = *args.get("X").unwrap() as usize;
+
+        if let Some(key) = self.auto_key {
+            self.cpu.registers[x] = key;
+            return;
+        }
+
+        self.cpu.store_keypress = Some(x);
+        self.key_wait_held = self.keypad_state;
+        self.key_wait_pressed = [false; Self::KEYPAD_SIZE];
+    }
+
+    /// Skip following instruction if key corresponding to hex value in `VX` is pressed.
+    fn skpk(&mut self, args: Args) {
+        let x = *args.get("X").unwrap() as usize;
+
+        let x_val = self.cpu.registers[x] as usize % Self::KEYPAD_SIZE;
+        
+        if self.keypad_state[x_val] {
+            self.cpu.pc += 2;
+        }
+    }
+
+    /// Skip following instruction if key corresponding to hex value in `VX` is not pressed.
+    fn skpnk(&mut self, args: Args) {
+        let x = *args.get("X").unwrap() as usize;
+
+        let x_val = self.cpu.registers[x] as usize % Self::KEYPAD_SIZE;
+        
+        if !self.keypad_state[x_val] {
+            self.cpu.pc += 2;
+        }
+    }
+
+    /// Store value of `VY` in `VX` shifted right one bit. Set `VF` to least
+    /// significant bit prior to shift. `VX` is shifted instead if the "shift" quirk is active.
+    fn shr(&mut self, args: Args) {
+        let x = *args.get("X").unwrap() as usize;
+        let y = *args.get("Y").unwrap() as usize;
+
+        let y_val = if self.quirks.shift { self.cpu.registers[x] } else { self.cpu.registers[y] };
+
+        // Store least significant bit in VF
+        self.cpu.registers[0xF] = y_val & 0x01;
+        self.cpu.registers[x] = y_val >> 1;
+    }
+
+    /// Store value of `VY` in `VX` shifted left one bit. Set `VF` to most
+    /// significant bit prior to shift. `VX` is shifted instead if the "shift" quirk is active.
+    fn shl(&mut self, args: Args) {
+        let x = *args.get("X").unwrap() as usize;
+        let y = *args.get("Y").unwrap() as usize;
+
+        let y_val = if self.quirks.shift { self.cpu.registers[x] } else { self.cpu.registers[y] };
+
+        // Store most significant bit in VF
+        self.cpu.registers[0xF] = (y_val & 0x80) >> 7;
+        self.cpu.registers[x] = y_val << 1;
+    }
+
+    /// Set 'VX' to 'VX' OR 'VY'.
+    fn or(&mut self, args: Args) {
+        let x: usize = *args.get("X").unwrap() as usize;
+        let y = *args.get("Y").unwrap() as usize;
+
+        self.cpu.registers[x] |= self.cpu.registers[y];
+    }
+
+    /// Set `VX` to `VX` AND `VY`.
+    fn and(&mut self, args: Args) {
+        let x = *args.get("X").unwrap() as usize;
+        let y = *args.get("Y").unwrap() as usize;
+
+        self.cpu.registers[x] &= self.cpu.registers[y];
+    }
+
+    /// Set `VX` to `VX` XOR `VY`.
+    fn xor(&mut self, args: Args) {
+        let x = *args.get("X").unwrap() as usize;
+        let y = *args.get("Y").unwrap() as usize;
+
+        self.cpu.registers[x] ^= self.cpu.registers[y];
+    }
+
+    /// Draw a sprite at `(VX, VY)` with `N` bytes of sprite data starting at
+    /// address stored in `I`. Set `VF` to `01` if any pixels are set to black,
+    /// `00` otherwise. If the per-frame draw limit has been reached, execution stalls
+    /// until the next frame, where the instruction is retried.
+    fn draw(&mut self, args: Args) {
+        if self.draw_limit != 0 && self.frame_draws >= self.draw_limit {
+            self.cpu.pc = self.cpu.pc.wrapping_sub(2);
+            self.draw_stalled = true;
+            return;
+        }
+
+        let x = *args.get("X").unwrap() as usize;
+        let y = *args.get("Y").unwrap() as usize;
+        let mut n = *args.get("N").unwrap() as usize;
+
+        self.counters.draws += 1;
+        self.frame_draws += 1;
+
+        let scaling_factor = !self.high_resolution as usize + 1;
+
+        let mut columns = 8;
+        let lores_dxy0 = self.quirks.lores_dxy0();
+        let draw_large_sprite = (self.high_resolution || lores_dxy0 == LoresDxy0::Large) && n == 0;
+        let addr_scaling_factor = draw_large_sprite as usize + 1;
+
+        if draw_large_sprite {
+            n = 16;
+            columns = 16;
+        }
+        else if n == 0 && !self.high_resolution && lores_dxy0 == LoresDxy0::Tall {
+            n = 16;
+        }
+
+        // Coordinates wrap around and sprites are clipped at the logical resolution, in
+        // which each pixel spans `scaling_factor` columns and rows of the frame buffer
+        let (width, height) = if self.high_resolution { (HIRES_WIDTH, HIRES_HEIGHT) } else { (LORES_WIDTH, LORES_HEIGHT) };
+        let x_val = self.cpu.registers[x] as usize % width;
+        let y_val = self.cpu.registers[y] as usize % height;
+        let rows = usize::min(n, height - y_val);
+
+        /* In low resolution mode, equal to 0x01 if a white pixel was set to black when drawing the sprite.
+           In  resolution mode, equal to the number of sprite rows where this occurred or that were clipped
+           by the bottom of the screen, assuming the "collision quirk" is active. */
+        let mut black = 0x00;
+        let mut row_black;
+
+        let sprite_width = columns * scaling_factor;
+        for i in 0..rows {
+            row_black = false;
+
+            // Sprite data wraps around at the end of memory, like other memory accesses
+            let addr = (self.cpu.i_register as usize + i * addr_scaling_factor) % Self::MEMORY_SIZE;
+            let next = (addr + 1) % Self::MEMORY_SIZE;
+            self.record_read(addr);
+            if draw_large_sprite { self.record_read(next) }
+
+            let sprite_data = if draw_large_sprite {
+                u16::from_be_bytes([self.cpu.memory[addr], self.cpu.memory[next]])
+            }
+            else {
+                self.cpu.memory[addr] as u16
+            };
+            let sprite_bits = if self.high_resolution { sprite_data as u128 } else { double_bits(sprite_data) as u128 };
+
+            // Align the sprite row to the left edge, then move it into place. Pixels past
+            // the right edge of the screen are shifted out, clipping the sprite.
+            let sprite_row = (sprite_bits << (u128::BITS as usize - sprite_width)) >> (x_val * scaling_factor);
+
+            for offset_i in 0..scaling_factor {
+                let row = &mut self.frame_buffer[(y_val + i) * scaling_factor + offset_i];
+
+                row_black |= *row & sprite_row != 0;
+                *row ^= sprite_row;
+            }
+
+            if self.high_resolution && self.quirks.collision {
+                black += row_black as u8;
+            }
+            else {
+                black |= row_black as u8;
+            }
+        }
+
+        if self.quirks.collision {
+            black += (n - rows) as u8;
+        }
+        self.cpu.registers[0xF] = black;
+    }
+
+    /// Set `VX` to random number with mask `NN`.
+    fn rand(&mut self, args: Args) {
+        let x = *args.get("X").unwrap() as usize;
+        let n = *args.get("N").unwrap() as u8;
+
+        let rand: u8 = match &mut self.random_source {
+            Some(source) => source.next_byte(),
+            None => self.rng.gen(),
+        };
+
+        self.cpu.registers[x] = rand & n;
+    }
+
+    /// Store BCD equivalent of value stored in register `VX` in memory at
+    /// addresses `I` to `I + 2`.
+    fn bcd(&mut self, args: Args) {
+        let x = *args.get("X").unwrap() as usize;
+
+        let x_val = self.cpu.registers[x];
+
+        for i in 0..=2 {
+            let addr = self.cpu.i_register as usize + i;
+            let digit = (x_val / u8::pow(10, 2 - i as u32)) % 10;
+
+            if !self.write_memory(addr, digit) { return; }
+        }
+    }
+
+    /// Store values of registers `V0` to `VX` in memory starting at address `I`,
+    /// which is set to `I + X + 1` after operation (unless the "memory" quirk is active).
+    fn save(&mut self, args: Args) {
+        let x = *args.get("X").unwrap() as usize;
+
+        for reg in 0..=x {
+            if !self.write_memory(self.cpu.i_register as usize + reg, self.cpu.registers[reg]) { return; }
+        }
+
+        if !self.quirks.memory {
+            self.cpu.i_register = self.cpu.i_register.wrapping_add(x as u16 + 1);
+        }
+    }
+
+    /// Fill registers `V0` to `VX` with memory values starting at address I,
+    /// which is set to `I + X + 1` after operation (unless the "memory" quirk is active).
+    fn load(&mut self, args: Args) {
+        let x = *args.get("X").unwrap() as usize;
+
+        for reg in 0..=x {
+            self.record_read(self.cpu.i_register as usize + reg);
+        }
+
+        let cpu = &mut self.cpu;
+
+        for reg in 0..=x {
+            cpu.registers[reg] = cpu.memory[(cpu.i_register as usize + reg) % cpu.memory.len()];
+        }
+
+        if !self.quirks.memory {
+            cpu.i_register = cpu.i_register.wrapping_add(x as u16 + 1);
+        }
+    }
+
+    /// Store values of register `V0` to `VX` in RPL user flags (persistent memory).
+    /// `X` must be less than or equal to 7. **SUPER-CHIP instruction.**
+    fn savef(&mut self, args: Args) {
+        let x = *args.get("X").unwrap() as usize;
+        if x > 7 { return; }
+
+        self.flags[0..=x].copy_from_slice(&self.cpu.registers[0..=x]);
+        if let Some(Ok(mut file)) = self.flags_path.as_ref().map(File::create) {
+            let _ = file.write_all(&self.cpu.registers[0..=x]);
+        }
+    }
+
+    /// Load values of registers `V0` to `VX` from RPL user flags (persistent memory).
+    /// `X` must be less than or equal to 7. **SUPER-CHIP instruction.**
+    fn loadf(&mut self, args: Args) {
+        let x = *args.get("X").unwrap() as usize;
+        if x > 7 { return; }
+
+        match &self.flags_path {
+            Some(path) => if let Ok(mut file) = File::open(path) {
+                let _ = file.read_exact(self.cpu.registers[0..=x].as_mut());
+            },
+            None => self.cpu.registers[0..=x].copy_from_slice(&self.flags[0..=x]),
+        }
+    }
+}
+
+impl Chip8Core {
+    /// Store the core options changed by the user since they were last read as
+    /// overrides for the current program. Button combinations are not specific to a
+    /// program and are never stored. The first call only records the initial values.
+    fn record_overrides(&mut self, env: &mut RetroEnvironment) {
+        let initial = self.variable_values.is_empty();
+        let mut changed = false;
+
+        for key in CoreOptions::keys().filter(|key| !key.ends_with("_combo")) {
+            let Some(value) = env.get_variable(key).map(str::to_string) else { continue };
+
+            if self.variable_values.get(key) != Some(&value) {
+                if !initial {
+                    self.overrides.set(key, &value);
+                    changed = true;
+                }
+                self.variable_values.insert(key, value);
+            }
+        }
+
+        if let (true, Some(path)) = (changed, &self.overrides_path) {
+            let _ = self.overrides.save(path);
+        }
+    }
+}
+
+impl RetroCore for Chip8Core {
+    fn get_system_info() -> RetroSystemInfo {
+        // Programs are small and loaded from memory, so the frontend is free to extract
+        // them from archives
+        RetroSystemInfo::new("CHIP-8 Emulator", "0.1.0")
+            .with_valid_extensions(Self::EXTENSIONS)
+            .with_need_full_path(false)
+            .with_block_extract(false)
+    }
+
+    fn reset(&mut self, _env: &mut RetroEnvironment) {
+        self.soft_reset();
+    }
+
+    fn run(&mut self, env: &mut RetroEnvironment, runtime: &RetroRuntime) {
+        let port = 0;
+
+        if env.get_variable_update() {
+            self.options.apply_combos(env);
+            self.record_overrides(env);
+
+            let mut options = self.base_options.clone();
+            options.apply_variables(env, &self.overrides);
+            self.apply_runtime_options(&options);
+        }
+
+        self.fast_forward = env.get_fastforwarding().unwrap_or(false);
+
+        // Bit 0 of the audio/video flags is clear when the frontend does not display the
+        // frame, for example while its window is in the background
+        if self.pause_hidden {
+            self.paused = env.get_audio_video_enable().is_some_and(|flags| flags & 1 == 0);
+        }
+
+        // Core-level reset hotkeys, triggered once when the combination is first held
+        let reset = self.options.reset_combo.is_pressed(runtime, RetroDevicePort::new(port));
+        let reset_apply = self.options.reset_apply_combo.is_pressed(runtime, RetroDevicePort::new(port));
+
+        if reset_apply && !self.reset_apply_held {
+            let mut options = self.base_options.clone();
+            options.apply_variables(env, &self.overrides);
+            self.apply_options(options);
+            self.soft_reset();
+        }
+        else if reset && !self.reset_held {
+            self.soft_reset();
+        }
+
+        self.reset_held = reset;
+        self.reset_apply_held = reset_apply;
+
+        // Palette and speed hotkeys, for handhelds where opening the menu is slow
+        let next_palette = self.options.palette_combo.is_pressed(runtime, RetroDevicePort::new(port));
+        let next_speed = self.options.speed_combo.is_pressed(runtime, RetroDevicePort::new(port));
+
+        if next_palette && !self.palette_held {
+            let (name, palette) = self.palette.next_preset();
+            self.set_palette(palette, self.options.palette_fade);
+            env.set_message(&format!("Palette: {}", name), Self::HOTKEY_MESSAGE_FRAMES);
+        }
+        if next_speed && !self.speed_held {
+            self.set_speed_percent(CoreOptions::next_speed_preset(self.speed_percent));
+            env.set_message(&format!("Speed: {}x", self.speed_percent as f64 / 100.0), Self::HOTKEY_MESSAGE_FRAMES);
+        }
+
+        self.palette_held = next_palette;
+        self.speed_held = next_speed;
+
+        // Obtain user input right before it is used, unless it is reported through
+        // keyboard events
+        let keyboard_callback = self.keyboard_callback;
+        let key_map = self.options.key_map;
+        let output = self.run_frame_polled(|| {
+            let mut keypad = KeypadState::default();
+            if !keyboard_callback {
+                for key in 0..Self::KEYPAD_SIZE {
+                    keypad.set(key, key_map.keycodes(key as u8).any(|keycode| runtime.is_keyboard_key_pressed(
+                        RetroDevicePort::new(port),
+                        keycode
+                    )));
+                }
+            }
+            keypad
+        });
+
+        runtime.upload_video_frame(output.video, output.width as u32, output.height as u32, output.pitch);
+
+        if !output.audio.is_empty() {
+            runtime.upload_audio_frame(output.audio);
+        }
+
+        if let Some(message) = self.pending_message.take() {
+            env.set_message(&message, Self::WARNING_MESSAGE_FRAMES);
+        }
+
+        // Write a crash dump once, so that bug reports can include it
+        if let (Some(reason), false) = (&self.fatal_error, self.crash_dumped) {
+            self.crash_dumped = true;
+            let message = match self.write_crash_dump(reason, &self.crash_dump_path) {
+                Ok(()) => format!("Emulation stopped: {}. Crash dump written to {}", reason, self.crash_dump_path.display()),
+                Err(e) => format!("Emulation stopped: {}. Cannot write crash dump: {}", reason, e),
+            };
+            self.log_warning(&message);
+            env.set_message(&message, Self::WARNING_MESSAGE_FRAMES);
+        }
+
+        if self.halted && self.exit_action == ExitAction::Shutdown && !self.shutdown_requested {
+            self.shutdown_requested = env.shutdown();
+        }
+    }
+
+    fn load_game(env: &mut RetroEnvironment, game: RetroGame) -> RetroLoadGameResult<Self> {
+        let program_data;
+        let game_meta;
+        match game {
+            RetroGame::None { meta: _ } => return RetroLoadGameResult::Failure,
+            RetroGame::Data { meta, data, path: _ } => {
+                program_data = data;
+                game_meta = meta;
+            },
+            RetroGame::Path { meta, path } => {
+                game_meta = meta;
+                if let Ok(data) = read(path) {
+                    program_data = data;
+                } else {
+                    return RetroLoadGameResult::Failure;
+                }
+            },
+        }
+
+        // Options in the meta string apply to this game only and take precedence over the
+        // command line
+        let mut args = game_meta.map(CoreOptions::meta_args).unwrap_or_default();
+        args.extend(env::args());
+
+        // Keep RPL flags and option overrides per program, so that separate instances do
+        // not share a file
+        let program_hash: [u8; 20] = Sha1::digest(&program_data).into();
+        let hash_hex = database::hash_hex(&program_hash);
+        let save_dir = env.get_save_directory().map(PathBuf::from).unwrap_or_default();
+        let overrides_path = save_dir.join(hash_hex.clone() + ".toml");
+        let overrides = GameOverrides::load(&overrides_path);
+
+        let base_options = CoreOptions::from_args(&args);
+        env.set_variables(&CoreOptions::localized_variables(base_options.language));
+        let mut options = base_options.clone();
+        options.apply_variables(env, &overrides);
+
+        let detection = detect::detect_platform(&program_data);
+        let mut platform_source = "selected";
+
+        if options.platform_auto {
+            let database = env.get_system_directory()
+                .map(|dir| Database::load(&Path::new(dir).join(Database::FILE_NAME)))
+                .unwrap_or_default();
+
+            // Fall back to scanning the program for platform-specific instructions
+            if let Some(entry) = database.get(&program_hash) {
+                options.platform = entry.platform;
+                eprintln!("oxid-8: platform {}: \"{}\" found in program database", entry.platform.name(), entry.name);
+                platform_source = "program database";
+            }
+            else {
+                options.platform = detection.platform;
+                eprintln!("oxid-8: {}", detection.summary());
+                platform_source = "detected";
+            }
+        }
+
+        // Show why the game runs as it does, so that misdetections are noticed and can be
+        // reported with the relevant details. A compatibility warning replaces the summary
+        // on screen, since it matters more.
+        env.set_message(&options.summary(platform_source), Self::SUMMARY_MESSAGE_FRAMES);
+        let compatibility_warning = detect::compatibility_warning(options.platform, &detection);
+        if let Some(warning) = &compatibility_warning {
+            env.set_message(&format!("Warning: {}", warning), Self::WARNING_MESSAGE_FRAMES);
+        }
+
+        // Files that are clearly something else would only run as garbage
+        if !options.permissive && !MemorySnapshot::is_snapshot(&program_data) {
+            if let Err(e) = validate::validate(&program_data) {
+                eprintln!("oxid-8: {} (use the permissive option to load it anyway)", e);
+                env.set_message(&format!("Cannot load: {}", e), Self::WARNING_MESSAGE_FRAMES);
+                return RetroLoadGameResult::Failure;
+            }
+        }
+
+        let mut core = Chip8Core::with_options(options);
+        core.base_options = base_options;
+        core.keyboard_callback = env.enable_keyboard_callback();
+        core.crash_dump_path = save_dir.join(hash_hex.clone() + ".crash.txt");
+        core.flags_path = Some(save_dir.join(hash_hex + ".rpl"));
+        core.overrides = overrides;
+        core.overrides_path = Some(overrides_path);
+        core.record_overrides(env);
+        if let Some(warning) = &compatibility_warning {
+            core.log_warning(&format!("warning: {}", warning));
+        }
+
+        if MemorySnapshot::is_snapshot(&program_data) {
+            if let Err(e) = core.load_snapshot(&program_data) {
+                eprintln!("oxid-8: {}", e);
+                return RetroLoadGameResult::Failure;
+            }
+        }
+        else if program_data.len() > core.max_program_size() && !core.truncate {
+            let message = format!("Program is too large ({} bytes, at most {} fit in memory)", program_data.len(), core.max_program_size());
+            eprintln!("oxid-8: {}", message);
+            env.set_message(&message, Self::WARNING_MESSAGE_FRAMES);
+            return RetroLoadGameResult::Failure;
+        }
+        else {
+            core.load_program(program_data.as_slice());
+        }
+        core.register_page.update(&core.cpu);
+
+        let descriptors = memory_map::descriptors(&mut core.cpu.memory, &mut core.register_page);
+        env.set_memory_maps(&descriptors);
+
+        // The memory map is all RetroAchievements needs to evaluate achievements.
+        // Savestate determinism is not declared: the bindings have no
+        // serialization callbacks, so frontends cannot save state or run ahead
+        env.set_support_achievements(true);
+
+        let (width, height) = core.video_filter.output_size(Self::SCREEN_WIDTH, Self::SCREEN_HEIGHT);
+        let aspect_ratio = core.video_filter.aspect_ratio(Self::SCREEN_WIDTH, Self::SCREEN_HEIGHT);
+
+        RetroLoadGameResult::Success {
+            region: RetroRegion::NTSC,
+            audio: RetroAudioInfo::new(Self::SAMPLE_RATE),
+            video: RetroVideoInfo::new(Self::FRAME_RATE, width as u32, height as u32)
+                .with_aspect_ratio(aspect_ratio)
+                .with_pixel_format(RetroPixelFormat::RGB565),
+            core,
+        }
+    }
+
+    fn keyboard_event(&mut self, _env: &mut RetroEnvironment, down: bool, keycode: u32, _character: u32, _modifiers: u16) {
+        self.keyboard_key_event(keycode, down);
+    }
+
+    fn get_memory_data(&mut self, _env: &mut RetroEnvironment, id: RetroMemoryType) -> Option<&mut [u8]> {
+        match id {
+            RetroMemoryType::SystemRam => Some(self.cpu.memory.as_mut_slice()),
+            _ => None,
+        }
+    }
+
+    fn get_memory_size(&self, _env: &mut RetroEnvironment, id: RetroMemoryType) -> usize {
+        match id {
+            RetroMemoryType::SystemRam => self.cpu.memory.len(),
+            _ => 0,
+        }
+    }
+}
+
+libretro_core!(Chip8Core);
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pixel(core: &Chip8Core, x: usize, y: usize) -> bool {
+        video::pixel(core.frame_buffer[y], x)
+    }
+
+    #[test]
+    fn instruction_patterns() {
+        let cpu = Cpu::new();
+        let instructions = cpu.instructions();
+        assert_eq!(instructions.len(), 44);
+
+        // Every pattern decodes to its instruction, whatever the values of its arguments
+        for instruction in instructions {
+            for digit in ["1", "5"] {
+                let opcode = u16::from_str_radix(&instruction.pattern().replace(['X', 'Y', 'N'], digit), 16).unwrap();
+                assert_eq!(cpu.decode_instruction(opcode).name(), instruction.name(), "{}", instruction.pattern());
+            }
+        }
+    }
+
+    #[test]
+    fn audio_chunks() {
+        // MOV V1 0 (x5), MOV V0 10, SNDR V0, JMP 0x20E
+        let mut program = [0x61, 0x00].repeat(5);
+        program.extend_from_slice(&[0x60, 0x0A, 0xF0, 0x18, 0x12, 0x0E]);
+
+        let mut whole = Chip8Core::new();
+        whole.load_program(&program);
+        let wave = whole.run_frame(KeypadState::default()).audio.to_vec();
+        assert_eq!(wave.len(), Chip8Core::AUDIO_FRAME_SIZE);
+
+        // The sound timer is set by the 7th of 10 instructions, during the third chunk
+        let mut core = Chip8Core::new();
+        core.set_audio_chunks(3);
+        assert_eq!(core.audio_chunks(), 4);
+        core.load_program(&program);
+
+        let audio = core.run_frame(KeypadState::default()).audio.to_vec();
+        let half = Chip8Core::AUDIO_FRAME_SIZE / 2;
+        assert!(audio[..half].iter().all(|&s| s == 0));
+        assert_eq!(audio[half..], wave[half..]);
+
+        // Chunks only differ within the frame where the sound starts
+        assert_eq!(core.run_frame(KeypadState::default()).audio, whole.run_frame(KeypadState::default()).audio);
+    }
+
+    #[test]
+    fn audio_output() {
+        let mut core = Chip8Core::new();
+        let (producer, mut consumer) = audio::ring(4 * Chip8Core::AUDIO_FRAME_SIZE);
+        core.set_audio_output(Some(producer));
+
+        // MOV V0 0x05, SNDR V0, JMP 0x204
+        core.load_program(&[0x60, 0x05, 0xF0, 0x18, 0x12, 0x04]);
+        let audio = core.run_frame(KeypadState::default()).audio.to_vec();
+        assert!(!audio.is_empty());
+
+        // Frames that do not fit in the ring are dropped until the consumer catches up
+        for _ in 0..9 {
+            core.run_frame(KeypadState::default());
+        }
+        assert_eq!(consumer.available(), 4 * Chip8Core::AUDIO_FRAME_SIZE);
+
+        let mut out = vec![0; Chip8Core::AUDIO_FRAME_SIZE];
+        consumer.pull(&mut out);
+        assert_eq!(out, audio);
+
+        // Frames without sound are pushed as silence
+        for _ in 0..3 {
+            consumer.pull(&mut out);
+        }
+        core.run_frame(KeypadState::default());
+        assert_eq!(consumer.pull(&mut out), Chip8Core::AUDIO_FRAME_SIZE);
+        assert!(out.iter().all(|&s| s == 0));
+    }
+
+    #[test]
+    fn oversized_program() {
+        let mut core = Chip8Core::new();
+        let mut program = vec![0x12, 0x00];
+        program.resize(core.max_program_size() + 100, 0xFF);
+
+        core.load_program(&program);
+        assert_eq!(core.status().program_size, 0);
+        assert_eq!(core.cpu.memory[0x200], 0x00);
+
+        core.truncate = true;
+        core.load_program(&program);
+        assert_eq!(core.status().program_size, 0xE00);
+        assert_eq!(core.cpu.memory[0x200..0x202], [0x12, 0x00]);
+        assert_eq!(core.cpu.memory[0xFFF], 0xFF);
+        let hash: [u8; 20] = Sha1::digest(&program).into();
+        assert_eq!(core.program_hash(), hash);
+    }
+
+    #[test]
+    fn start_address() {
+        let mut core = Chip8Core::with_options(CoreOptions::from_args(&["platform=eti660".to_string()]));
+
+        // MOV V0 0x2A, JMP 0x602
+        core.load_program(&[0x60, 0x2A, 0x16, 0x02]);
+        assert_eq!(core.pc(), 0x600);
+        assert_eq!(core.cpu.memory[0x200], 0x00);
+
+        core.run_frame(KeypadState::default());
+        assert_eq!(core.register(0x0), 0x2A);
+        assert_eq!(core.coverage().ranges(), vec![(0x600..0x604, true)]);
+
+        core.soft_reset();
+        assert_eq!(core.pc(), 0x600);
+    }
+
+    #[test]
+    fn square_wave() {
+        let core = Chip8Core::new();
+        assert_eq!([core.wave[0], core.wave[95], core.wave[96], core.wave[191], core.wave[192]], [1200, 1200, -1200, -1200, 1200]);
+        assert_eq!(core.wave[95_999], -1200);
+    }
+
+    #[test]
+    fn resolution() {
+        assert_eq!((Resolution::Low.width(), Resolution::Low.height()), (LORES_WIDTH, LORES_HEIGHT));
+        assert_eq!((Resolution::High.width(), Resolution::High.height()), (128, 64));
+
+        let mut core = Chip8Core::new();
+        core.high_resolution = true;
+        assert_eq!(core.status().resolution.width(), HIRES_WIDTH);
+    }
+
+    #[test]
+    fn add() {
+        let mut core = Chip8Core::new();
+
+        core.cpu.registers[0x2] = 200;
+        
+        core.add(Args::from([("X", 0x2), ("N", 100)]));
+
+        assert_eq!(core.cpu.registers[0x2], 44);
+    }
+
+    #[test]
+    fn addr() {
+        let mut core = Chip8Core::new();
+
+        core.cpu.registers[0x2] = 25;
+        core.cpu.registers[0x3] = 42;
+        core.cpu.registers[0xF] = 33;
+
+        core.addr(Args::from([("X", 0x2), ("Y", 0x3)]));
+
+        assert_eq!(core.cpu.registers[0x2], 67);
+        assert_eq!(core.cpu.registers[0xF], 0);
+
+        core.cpu.registers[0x2] = 255;
+        core.cpu.registers[0x3] = 20;
+
+        core.addr(Args::from([("X", 0x2), ("Y", 0x3)]));
+
+        assert_eq!(core.cpu.registers[0x2], 19);
+        assert_eq!(core.cpu.registers[0xF], 1);
+    }
+
+    #[test]
+    fn movi() {
+        let mut core = Chip8Core::new();
+        let addr = 0x34E;
+
+        core.movi(Args::from([("N", addr)]));
+
+        assert_eq!(core.cpu.i_register, addr);
+    }
+
+    #[test]
+    fn rsubr() {
+        let mut core = Chip8Core::new();
+
+        core.cpu.registers[0x2] = 31;
+        core.cpu.registers[0x3] = 65;
+        core.cpu.registers[0xF] = 33;
+
+        core.rsubr(Args::from([("X", 0x2), ("Y", 0x3)]));
+
+        assert_eq!(core.cpu.registers[0x2], 34);
+        assert_eq!(core.cpu.registers[0xF], 1);
+
+        core.cpu.registers[0x2] = 31;
+        core.cpu.registers[0x3] = 20;
+
+        core.rsubr(Args::from([("X", 0x2), ("Y", 0x3)]));
+
+        assert_eq!(core.cpu.registers[0x2], 245);
+        assert_eq!(core.cpu.registers[0xF], 0);
+    }
+
+    #[test]
+    fn shl() {
+        let mut core = Chip8Core::new();
+
+        core.cpu.registers[0x2] = 0x01;
+        core.cpu.registers[0xF] = 33;
+
+        core.shl(Args::from([("X", 0x1), ("Y", 0x2)]));
+
+        assert_eq!(core.cpu.registers[0x1], 0x2);
+        assert_eq!(core.cpu.registers[0xF], 0x0);
+
+        core.cpu.registers[0x2] = 0x81;
+
+        core.shl(Args::from([("X", 0x1), ("Y", 0x2)]));
+
+        assert_eq!(core.cpu.registers[0x1], 0x2);
+        assert_eq!(core.cpu.registers[0xF], 0x1);
+    }
+
+    #[test]
+    fn jmpr() {
+        let mut core = Chip8Core::new();
+
+        core.cpu.registers[0x0] = 0x40;
+
+        core.jmpr(Args::from([("N", 0x300)]));
+
+        assert_eq!(core.cpu.pc, 0x340);
+    }
+
+    #[test]
+    fn call_ret() {
+        let mut core = Chip8Core::new();
+
+        let pc = 0x432;
+        let addr = 0x6A2;
+
+        core.cpu.pc = pc;
+        core.call(Args::from([("N", addr)]));
+
+        assert_eq!(core.cpu.pc, addr);
+        assert_eq!(core.cpu.stack, vec![pc]);
+
+        core.ret(Args::new());
+
+        assert_eq!(core.cpu.pc, pc);
+        assert_eq!(core.cpu.stack, Vec::new());
+    }
+
+    #[test]
+    fn skpeqr() {
+        let mut core = Chip8Core::new();
+
+        let pc = 0x3A0;
+        core.cpu.pc = pc;
+
+        let v = vec![0x42, 0x34, 0x42];
+        core.cpu.registers[0x0] = v[0];
+        core.cpu.registers[0x1] = v[1];
+        core.cpu.registers[0x2] = v[2];
+
+        core.skpeqr(Args::from([("X", 0x0), ("Y", 0x1)]));
+        assert_eq!(core.cpu.pc, pc);
+
+        core.skpeqr(Args::from([("X", 0x0), ("Y", 0x2)]));
+        assert_eq!(core.cpu.pc, pc + 2);
+    }
+
+    #[test]
+    fn skpk() {
+        let mut core = Chip8Core::new();
+
+        let pc = 0x3A0;
+        core.cpu.pc = pc;
+        
+        let key = 0xB;
+        core.keypad_state[key] = true;
+
+        core.cpu.registers[0x0] = 0x8;
+        core.skpk(Args::from([("X", 0x0)]));
+        assert_eq!(core.cpu.pc, pc);
+
+        core.cpu.registers[0x0] = 0xB;
+        core.skpk(Args::from([("X", 0x0)]));
+        assert_eq!(core.cpu.pc, pc + 2);
+    }
+
+    #[test]
+    fn timr() {
+        let mut core = Chip8Core::new();
+
+        let val = 0x7A;
+        core.cpu.delay_timer = val;
+
+        core.timr(Args::from([("X", 0x2)]));
+        assert_eq!(core.cpu.registers[0x2], val);
+    }
+
+    #[test]
+    fn bcd() {
+        let mut core = Chip8Core::new();
+
+        let i = 0x400 as usize;
+        core.cpu.i_register = i as u16;
+
+        core.cpu.registers[0x4] = 159;
+
+        core.bcd(Args::from([("X", 0x4)]));
+
+        assert_eq!(core.cpu.memory[i], 1);
+        assert_eq!(core.cpu.memory[i + 1], 5);
+        assert_eq!(core.cpu.memory[i + 2], 9);
+    }
+
+    #[test]
+    fn save() {
+        let mut core = Chip8Core::new();
+
+        let i = 0x400 as usize;
+        let v = vec![0x41, 0x9B, 0xEE];
+
+        core.cpu.i_register = i as u16;
+
+        core.cpu.registers[0x0] = v[0];
+        core.cpu.registers[0x1] = v[1];
+        core.cpu.registers[0x2] = v[2];
+
+        core.save(Args::from([("X", 0x2)]));
+
+        assert_eq!(core.cpu.memory[i], v[0]);
+        assert_eq!(core.cpu.memory[i + 1], v[1]);
+        assert_eq!(core.cpu.memory[i + 2], v[2]);
+
+        assert_eq!(core.cpu.i_register, (i + 3) as u16);
+    }
+
+    #[test]
+    fn load() {
+        let mut core = Chip8Core::new();
+
+        let i = 0x400 as usize;
+        let v = vec![0x20, 0x45, 0xAF];
+
+        core.cpu.i_register = i as u16;
+
+        core.cpu.memory[i] = v[0];
+        core.cpu.memory[i + 1] = v[1];
+        core.cpu.memory[i + 2] = v[2];
+
+        core.load(Args::from([("X", 0x2)]));
+
+        assert_eq!(core.cpu.registers[0x0], v[0]);
+        assert_eq!(core.cpu.registers[0x1], v[1]);
+        assert_eq!(core.cpu.registers[0x2], v[2]);
+
+        assert_eq!(core.cpu.i_register, (i + 3) as u16);
+    }
+
+    #[test]
+    fn bcd_wrap() {
+        let mut core = Chip8Core::new();
+
+        core.cpu.i_register = 0xFFE;
+        core.cpu.registers[0x0] = 255;
+        core.bcd(Args::from([("X", 0x0)]));
+
+        assert_eq!(core.cpu.memory[0xFFE..], [2, 5]);
+        assert_eq!(core.cpu.memory[0x000], 5);
+    }
+
+    #[test]
+    fn save_wrap() {
+        let mut core = Chip8Core::new();
+
+        core.cpu.i_register = 0xFFF;
+        core.cpu.registers[0x0] = 0xAB;
+        core.cpu.registers[0x1] = 0xCD;
+        core.save(Args::from([("X", 0x1)]));
+
+        assert_eq!(core.cpu.memory[0xFFF], 0xAB);
+        assert_eq!(core.cpu.memory[0x000], 0xCD);
+        assert_eq!(core.cpu.i_register, 0x1001);
+
+        core.cpu.registers = [0; 16];
+        core.cpu.i_register = 0xFFF;
+        core.load(Args::from([("X", 0x1)]));
+
+        assert_eq!(core.cpu.registers[0x0..=0x1], [0xAB, 0xCD]);
+    }
+
+    #[test]
+    fn screen_text() {
+        let mut core = Chip8Core::new();
+
+        // MOVI 0x000 (digit 0), DRAW V0 V0 5
+        core.load_program(&[0xA0, 0x00, 0xD0, 0x05]);
+        core.execute_instruction();
+        core.execute_instruction();
+
+        let text = core.screen_text(TextStyle::Ascii);
+        let lines: Vec<&str> = text.lines().collect();
+
+        assert_eq!(lines.len(), 32);
+        assert_eq!(&lines[0][..6], "####..");
+        assert_eq!(&lines[1][..6], "#..#..");
+        assert_eq!(core.screen_text(TextStyle::Braille).lines().count(), 8);
+    }
+
+    #[test]
+    fn framebuffer_hash() {
+        let mut core = Chip8Core::new();
+        let blank = core.framebuffer_hash();
+
+        // MOVI 0x000 (digit 0), DRAW V0 V0 5, DRAW V0 V0 5
+        core.load_program(&[0xA0, 0x00, 0xD0, 0x05, 0xD0, 0x05]);
+        core.execute_instruction();
+        core.execute_instruction();
+        assert_ne!(core.framebuffer_hash(), blank, "{}", core.screen_text(TextStyle::Ascii));
+
+        core.execute_instruction();
+        assert_eq!(core.framebuffer_hash(), blank, "{}", core.screen_text(TextStyle::Ascii));
+
+        core.high_resolution = true;
+        assert_ne!(core.framebuffer_hash(), blank);
+    }
+
+    #[test]
+    fn dump_memory() {
+        let mut core = Chip8Core::new();
+
+        // CALL 0x204, JMP 0x200, MOV V3 0x7F
+        core.load_program(&[0x22, 0x04, 0x12, 0x00, 0x63, 0x7F]);
+        core.execute_instruction();
+        core.execute_instruction();
+
+        let snapshot = core.dump_memory();
+        assert_eq!(snapshot.pc, 0x206);
+        assert_eq!(snapshot.stack, [0x202]);
+        assert_eq!(snapshot.registers[0x3], 0x7F);
+        assert_eq!(snapshot.memory[0x200..0x206], core.cpu.memory[0x200..0x206]);
+    }
+
+    #[test]
+    fn load_snapshot() {
+        let mut core = Chip8Core::new();
+
+        // MOV V0 0x01, ADD V0 0x01, JMP 0x202
+        core.load_program(&[0x60, 0x01, 0x70, 0x01, 0x12, 0x02]);
+        for _ in 0..4 {
+            core.execute_instruction();
+        }
+        let data = core.dump_memory().to_bytes();
+
+        let mut loaded = Chip8Core::new();
+        assert_eq!(loaded.load_snapshot(&data), Ok(()));
+        assert_eq!(loaded.cpu.registers[0x0], 0x03);
+        assert_eq!(loaded.cpu.pc, 0x204);
+
+        loaded.execute_instruction();
+        loaded.soft_reset();
+        assert_eq!(loaded.cpu.registers[0x0], 0x03);
+        assert_eq!(loaded.dump_memory(), core.dump_memory());
+
+        assert_eq!(loaded.load_snapshot(&data[..32]), Err(SnapshotError::InvalidFormat));
+    }
+
+    #[test]
+    fn code_writes() {
+        let mut core = Chip8Core::new();
+
+        // MOVI 0x206, MOV V0 0x12, SAVE V0, JMP 0x200
+        core.load_program(&[0xA2, 0x06, 0x60, 0x12, 0xF0, 0x55, 0x12, 0x00]);
+        for _ in 0..3 {
+            core.execute_instruction();
+        }
+        assert_eq!(core.code_writes().writes, 0);
+
+        // Executed twice, overwriting its own first byte with the same value
+        core.execute_instruction();
+        for _ in 0..3 {
+            core.execute_instruction();
+        }
+
+        assert_eq!(core.code_writes().writes, 1);
+        assert_eq!(core.code_writes().addresses, BTreeMap::from([(0x206, 1)]));
+    }
+
+    #[test]
+    fn memory_protection() {
+        let mut core = Chip8Core::new();
+        core.memory_protection = MemoryProtection::Fault;
+
+        // MOVI 0x202, SAVE V0
+        core.load_program(&[0xA2, 0x02, 0xF0, 0x55]);
+        core.execute_instruction();
+        core.execute_instruction();
+
+        assert!(core.halted);
+        assert_eq!(core.cpu.memory[0x202], 0xF0);
+        assert_eq!(core.protection_warnings, HashSet::from([0x202]));
+
+        // Writes past the program are allowed
+        core.cpu.i_register = 0x204;
+        core.save(Args::from([("X", 0x0)]));
+        assert_eq!(core.cpu.i_register, 0x205);
+    }
+
+    #[test]
+    fn crash_dump() {
+        let mut core = Chip8Core::new();
+        core.memory_protection = MemoryProtection::Fault;
+
+        // MOVI 0x202, SAVE V0
+        core.load_program(&[0xA2, 0x02, 0xF0, 0x55]);
+        core.run_frame(KeypadState::default());
+        assert_eq!(core.fatal_error(), Some("instruction at 0x202 wrote to protected address 0x202"));
+
+        let path = env::temp_dir().join("oxid8_crash_dump.txt");
+        core.write_crash_dump(core.fatal_error().unwrap(), &path).unwrap();
+        let report = std::fs::read_to_string(&path).unwrap();
+        let snapshot = MemorySnapshot::from_bytes(&std::fs::read(path.with_extension("ox8d")).unwrap()).unwrap();
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(path.with_extension("ox8d"));
+
+        assert!(report.contains("Snapshot: oxid8_crash_dump.ox8d"));
+        assert!(report.contains("Last instructions executed:\n  0x200  A202  MOVI [N = 0x202]\n  0x202  F055  SAVE [X = 0x0]\n"));
+        assert!(report.contains("> 0x202  F055  SAVE"));
+        assert_eq!(snapshot.pc, 0x204);
+
+        core.soft_reset();
+        assert_eq!(core.fatal_error(), None);
+    }
+
+    #[test]
+    fn soft_reset() {
+        let mut core = Chip8Core::new();
+        let program = [0x60, 0x42, 0x12, 0x00];
+
+        core.load_program(&program);
+        core.execute_instruction();
+        core.frame_buffer[0] = 1 << 127;
+        core.cpu.memory[0x200] = 0x00;
+
+        core.soft_reset();
+
+        assert_eq!(core.cpu.pc, 0x200);
+        assert_eq!(core.cpu.registers[0x0], 0);
+        assert_eq!(core.cpu.memory[0x200..0x204], program);
+        assert!(!pixel(&core, 0, 0));
+    }
+
+    #[test]
+    fn counters() {
+        let mut core = Chip8Core::new();
+
+        // MOVI 0x000, DRAW V0 V0 1
+        core.load_program(&[0xA0, 0x00, 0xD0, 0x01]);
+        core.execute_instruction();
+        core.execute_instruction();
+
+        let counters = core.counters();
+        assert_eq!(counters.instructions, 2);
+        assert_eq!(counters.draws, 1);
+        assert_eq!(counters.frames, 0);
+    }
+
+    #[test]
+    fn status() {
+        let mut core = Chip8Core::new();
+
+        // HIRES, EXIT
+        core.load_program(&[0x00, 0xFF, 0x00, 0xFD]);
+        assert_eq!(core.status().resolution, Resolution::Low);
+
+        core.execute_instruction();
+        core.execute_instruction();
+
+        let status = core.status();
+        assert_eq!(status.resolution, Resolution::High);
+        assert_eq!(status.platform, Platform::Chip8);
+        assert!(status.halted);
+        assert!(!status.waiting_for_key);
+    }
+
+    #[test]
+    fn flags_per_instance() {
+        let dir = env::temp_dir();
+        let mut a = Chip8Core::new();
+        let mut b = Chip8Core::new();
+        a.set_flags_path(Some(dir.join("oxid8_flags_a.rpl")));
+        b.set_flags_path(Some(dir.join("oxid8_flags_b.rpl")));
+
+        a.cpu.registers[..2].copy_from_slice(&[0x12, 0x34]);
+        b.cpu.registers[..2].copy_from_slice(&[0x56, 0x78]);
+        a.savef(Args::from([("X", 0x1)]));
+        b.savef(Args::from([("X", 0x1)]));
+
+        a.cpu.registers[..2].fill(0);
+        a.loadf(Args::from([("X", 0x1)]));
+        assert_eq!(a.cpu.registers[..2], [0x12, 0x34]);
+
+        let _ = std::fs::remove_file(a.flags_path().unwrap());
+        let _ = std::fs::remove_file(b.flags_path().unwrap());
+
+        // Kept in memory without a flags file
+        let mut core = Chip8Core::new();
+        assert_eq!(core.flags_path(), None);
+        core.cpu.registers[..2].copy_from_slice(&[0x9A, 0xBC]);
+        core.savef(Args::from([("X", 0x1)]));
+        core.cpu.registers[..2].fill(0);
+        core.loadf(Args::from([("X", 0x1)]));
+        assert_eq!(core.cpu.registers[..2], [0x9A, 0xBC]);
+    }
+
+    #[test]
+    fn trace() {
+        let path = env::temp_dir().join("oxid8_trace.txt");
+        let _ = std::fs::remove_file(&path);
+
+        let mut core = Chip8Core::new();
+        core.set_trace_file(&path).unwrap();
+
+        // MOVI 0x000, DRAW V0 V0 5, ADD V0 0x01, JMP 0x202
+        core.load_program(&[0xA0, 0x00, 0xD0, 0x05, 0x70, 0x01, 0x12, 0x02]);
+        core.run_frame(KeypadState::default());
+        core.run_frame(KeypadState::default());
+        core.trace = None;
+
+        let trace = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = trace.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[1].starts_with("2 "));
+        assert_eq!(lines[1], format!("2 {:016x} {:016x}", core.framebuffer_hash(), core.registers_hash()));
+        assert_ne!(lines[0][2..], lines[1][2..]);
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Chip8Core>();
+    }
+
+    #[test]
+    fn run_frame() {
+        let mut core = Chip8Core::new();
+
+        // MOVI 0x000, DRAW V0 V0 1, MOV V1 0x02, SNDR V1, KEY V2
+        core.load_program(&[0xA0, 0x00, 0xD0, 0x01, 0x61, 0x02, 0xF1, 0x18, 0xF2, 0x0A]);
+
+        let output = core.run_frame(KeypadState::default());
+        assert_eq!(output.video.len(), output.pitch * output.height);
+        assert_eq!(output.video[..2], Palette::default().rgb565(1).to_le_bytes());
+        assert!(!output.audio.is_empty());
+        assert!(core.status().waiting_for_key);
+
+        core.run_frame(KeypadState(1 << 0x7));
+        assert_eq!(core.cpu.registers[0x2], 0x7);
+    }
+
+    #[test]
+    fn draw_limit() {
+        let mut core = Chip8Core::new();
+        core.draw_limit = 2;
+
+        // DRAW V0 V0 1, JMP 0x200
+        core.load_program(&[0xD0, 0x01, 0x12, 0x00]);
+
+        core.run_frame(KeypadState::default());
+        assert_eq!(core.counters().draws, 2);
+        assert_eq!(core.cpu.pc, 0x200);
+
+        core.run_frame(KeypadState::default());
+        assert_eq!(core.counters().draws, 4);
+    }
+
+    #[test]
+    fn scroll_lores() {
+        let mut core = Chip8Core::new();
+        core.frame_buffer[0] = 1 << 127;
+
+        core.scd(Args::from([("N", 0x1)]));
+        assert!(pixel(&core, 0, 2));
+
+        core.scr(Args::new());
+        assert!(pixel(&core, 8, 2));
+
+        core.quirks.scroll = true;
+
+        core.scd(Args::from([("N", 0x1)]));
+        assert!(pixel(&core, 8, 3));
+
+        core.scl(Args::new());
+        assert!(pixel(&core, 4, 3));
+    }
+
+    #[test]
+    fn draw_clip() {
+        let mut core = Chip8Core::new();
+        core.cpu.i_register = 0x300;
+        core.cpu.memory[0x300] = 0b1100_0011;
+        core.cpu.registers[0x0] = 60;
+
+        // Low-resolution pixels span two columns, and the right half is clipped
+        core.draw(Args::from([("X", 0x0), ("Y", 0x1), ("N", 0x1)]));
+        assert_eq!(core.frame_buffer[0], 0xF << 4);
+        assert_eq!(core.frame_buffer[1], 0xF << 4);
+        assert_eq!(core.cpu.registers[0xF], 0);
+
+        core.draw(Args::from([("X", 0x0), ("Y", 0x1), ("N", 0x1)]));
+        assert_eq!(core.frame_buffer[0], 0);
+        assert_eq!(core.cpu.registers[0xF], 1);
+
+        core.high_resolution = true;
+        core.cpu.registers[0x0] = 124;
+        core.draw(Args::from([("X", 0x0), ("Y", 0x1), ("N", 0x1)]));
+        assert_eq!(core.frame_buffer[0], 0b1100);
+    }
+
+    #[test]
+    fn draw_edges() {
+        let lores_dxy0 = [(false, false), (true, false), (false, true)];
+
+        for high_resolution in [false, true] {
+            for (lores16, lores_tall) in lores_dxy0 {
+                for collision in [false, true] {
+                    for n in [0, 5] {
+                        let mut core = Chip8Core::new();
+                        core.quirks = Quirks { lores16, lores_tall, collision, ..Quirks::default() };
+                        core.high_resolution = high_resolution;
+                        core.cpu.memory[0x300..0x320].fill(0xFF);
+                        core.cpu.i_register = 0x300;
+
+                        // Coordinates wrap around to the bottom right pixel
+                        core.cpu.registers[0x0] = 0xFF;
+                        core.cpu.registers[0x1] = 0xFF;
+                        core.draw(Args::from([("X", 0x0), ("Y", 0x1), ("N", n)]));
+
+                        let case = format!("high {} lores16 {} tall {} collision {} n {}", high_resolution, lores16, lores_tall, collision, n);
+                        let drawn = n != 0 || high_resolution || lores16 || lores_tall;
+                        let rows = if n == 0 { 16 } else { n as u8 };
+
+                        let mut expected = [0; Chip8Core::SCREEN_HEIGHT];
+                        if drawn && high_resolution {
+                            expected[63] = 0b1;
+                        }
+                        else if drawn {
+                            expected[62..].fill(0b11);
+                        }
+                        assert_eq!(core.frame_buffer, expected, "{}", case);
+
+                        // Clipped rows are counted with the collision quirk in both resolutions
+                        let clipped = if drawn && collision { rows - 1 } else { 0 };
+                        assert_eq!(core.cpu.registers[0xF], clipped, "{}", case);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn draw_memory_end() {
+        let mut core = Chip8Core::new();
+        core.cpu.memory[0xFFF] = 0xFF;
+        core.cpu.memory[0x000] = 0x81;
+
+        // The second byte of a 16x16 sprite row is read from the start of memory
+        core.high_resolution = true;
+        core.cpu.i_register = 0xFFF;
+        core.draw(Args::from([("X", 0x0), ("Y", 0x0), ("N", 0x0)]));
+        assert_eq!(core.frame_buffer[0] >> 112, 0xFF81);
+
+        // I past the end of memory
+        core.high_resolution = false;
+        core.frame_buffer.fill(0);
+        core.cpu.i_register = 0xFFFF;
+        core.draw(Args::from([("X", 0x0), ("Y", 0x0), ("N", 0x2)]));
+        assert_eq!(core.frame_buffer[0] >> 112, 0xFFFF);
+        assert_eq!(core.frame_buffer[2] >> 112, 0xC003);
+    }
+
+    #[test]
+    fn scroll_hires() {
+        let mut core = Chip8Core::new();
+        core.high_resolution = true;
+        core.frame_buffer[0] = 1 << 127 | 1;
+        core.frame_buffer[63] = 1;
+
+        core.scd(Args::from([("N", 0x3)]));
+        assert_eq!(core.frame_buffer[..4], [0, 0, 0, 1 << 127 | 1]);
+
+        core.scr(Args::new());
+        assert_eq!(core.frame_buffer[3], 1 << 123);
 
-        self.cpu.registers[x] = rand & n;
+        core.scl(Args::new());
+        core.scl(Args::new());
+        assert_eq!(core.frame_buffer[3], 0);
     }
 
-    /// Store BCD equivalent of value stored in register `VX` in memory at
-    /// addresses `I` to `I + 2`.
-    fn bcd(&mut self, args: HashMap<&'static str, u16>) {
-        let x = *args.get("X").unwrap() as usize;
+    #[test]
+    fn draw_lores_dxy0() {
+        let draw = |quirks: Quirks| {
+            let mut core = Chip8Core::with_quirks(quirks);
+            core.cpu.i_register = 0x300;
+            core.cpu.memory[0x300..0x320].fill(0xFF);
+            core.draw(Args::from([("X", 0x0), ("Y", 0x0), ("N", 0x0)]));
+            core
+        };
 
-        let cpu = &mut self.cpu;
-        let x_val = cpu.registers[x];
+        let core = draw(Quirks::default());
+        assert!(!pixel(&core, 0, 0));
 
-        for i in 0..=2 {
-            let addr = cpu.i_register as usize + i;
-            let digit = (x_val / u8::pow(10, 2 - i as u32)) % 10;
+        let core = draw(Quirks { lores_tall: true, ..Quirks::default() });
+        assert!(pixel(&core, 15, 31));
+        assert!(!pixel(&core, 16, 31));
+        assert!(!pixel(&core, 0, 32));
 
-            cpu.memory[addr] = digit;
-        }
+        let core = draw(Quirks { lores16: true, ..Quirks::default() });
+        assert!(pixel(&core, 31, 31));
+        assert!(!pixel(&core, 32, 31));
+        assert!(!pixel(&core, 0, 32));
     }
 
-    /// Store values of registers `V0` to `VX` in memory starting at address `I`,
-    /// which is set to `I + X + 1` after operation (unless the "memory" quirk is active).
-    fn save(&mut self, args: HashMap<&'static str, u16>) {
-        let x = *args.get("X").unwrap() as usize;
+    #[test]
+    fn key_wait_budget() {
+        let mut core = Chip8Core::new();
 
-        let cpu = &mut self.cpu;
+        // KEY V0, ADD V1 0x01, JMP 0x202
+        core.load_program(&[0xF0, 0x0A, 0x71, 0x01, 0x12, 0x02]);
+        core.cpu.sound_timer = 10;
 
-        for reg in 0..=x {
-            cpu.memory[cpu.i_register as usize + reg] = cpu.registers[reg];
-        }
+        core.run_frame(KeypadState::default());
+        assert!(core.status().waiting_for_key);
+        assert_eq!(core.cpu.sound_timer, 9);
 
-        if !self.quirk_memory {
-            cpu.i_register = cpu.i_register.wrapping_add(x as u16 + 1);
-        }
+        // The wait completes on the first cycle, leaving 9 cycles for the loop
+        core.run_frame(KeypadState(1 << 0x5));
+        assert_eq!(core.cpu.registers[0x0], 0x5);
+        assert_eq!(core.cpu.registers[0x1], 5);
     }
 
-    /// Fill registers `V0` to `VX` with memory values starting at address I,
-    /// which is set to `I + X + 1` after operation (unless the "memory" quirk is active).
-    fn load(&mut self, args: HashMap<&'static str, u16>) {
-        let x = *args.get("X").unwrap() as usize;
+    #[test]
+    fn speed() {
+        let mut core = Chip8Core::new();
+        core.set_speed_percent(25);
 
-        let cpu = &mut self.cpu;
+        // ADD V0 0x01, JMP 0x200
+        core.load_program(&[0x70, 0x01, 0x12, 0x00]);
+        core.cpu.delay_timer = 10;
 
-        for reg in 0..=x {
-            cpu.registers[reg] = cpu.memory[cpu.i_register as usize + reg];
-        }
+        // 2.5 instructions and a quarter of a timer tick per frame
+        core.run_frame(KeypadState::default());
+        core.run_frame(KeypadState::default());
+        assert_eq!(core.counters().instructions, 5);
+        assert_eq!(core.cpu.delay_timer, 10);
 
-        if !self.quirk_memory {
-            cpu.i_register = cpu.i_register.wrapping_add(x as u16 + 1);
-        }
-    }
+        core.run_frame(KeypadState::default());
+        core.run_frame(KeypadState::default());
+        assert_eq!(core.cpu.delay_timer, 9);
 
-    /// Store values of register `V0` to `VX` in RPL user flags (persistent memory).
-    /// `X` must be less than or equal to 7. **SUPER-CHIP instruction.**
-    fn savef(&mut self, args: HashMap<&'static str, u16>) {
-        let x = *args.get("X").unwrap() as usize;
-        if x > 7 { return; }
+        core.set_speed_percent(1000);
+        assert_eq!(core.speed_percent(), 800);
 
-        if let Ok(mut file) = File::create(Self::FLAGS_FILE) {
-            let _ = file.write_all(&self.cpu.registers[0..=x]);
-        }
+        core.run_frame(KeypadState::default());
+        assert_eq!(core.counters().instructions, 10 + 80);
+        assert_eq!(core.cpu.delay_timer, 1);
     }
 
-    /// Load values of registers `V0` to `VX` from RPL user flags (persistent memory).
-    /// `X` must be less than or equal to 7. **SUPER-CHIP instruction.**
-    fn loadf(&mut self, args: HashMap<&'static str, u16>) {
-        let x = *args.get("X").unwrap() as usize;
-        if x > 7 { return; }
+    #[test]
+    fn fast_forward() {
+        let mut core = Chip8Core::new();
+        core.fast_forward_percent = 400;
+        core.fast_forward_mute = true;
 
-        if let Ok(mut file) = File::open(Self::FLAGS_FILE) {
-            let _ = file.read_exact(self.cpu.registers[0..=x].as_mut());
-        }
-    }
-}
+        // ADD V0 0x01, MOV V1 0x20, SNDR V1, JMP 0x200
+        core.load_program(&[0x70, 0x01, 0x61, 0x20, 0xF1, 0x18, 0x12, 0x00]);
 
-impl RetroCore for Chip8Core {
-    fn get_system_info() -> RetroSystemInfo {
-        RetroSystemInfo::new("CHIP-8 Emulator", "0.1.0")
-    }
+        assert!(!core.run_frame(KeypadState::default()).audio.is_empty());
+        assert_eq!(core.counters().instructions, 10);
 
-    fn reset(&mut self, _env: &mut RetroEnvironment) {
+        core.set_fast_forward(true);
+        assert!(core.run_frame(KeypadState::default()).audio.is_empty());
+        assert_eq!(core.counters().instructions, 10 + 40);
 
+        core.set_fast_forward(false);
+        core.run_frame(KeypadState::default());
+        assert_eq!(core.counters().instructions, 60);
     }
 
-    fn run(&mut self, _env: &mut RetroEnvironment, runtime: &RetroRuntime) {
-        let port = 0;
-
-        // Obtain user input
-        for (i, key) in Chip8Key::iter().enumerate() {
-            self.keypad_state[i] = runtime.is_keyboard_key_pressed(
-                RetroDevicePort::new(port),
-                key as u32
-            );
-        }
+    #[test]
+    fn auto_key() {
+        let mut core = Chip8Core::new();
+        core.set_auto_key(Some(0xB));
 
-        let last_key = self.cpu.last_keypress;
-        self.cpu.last_keypress = last_key.and_then(|k| if self.keypad_state[k] { last_key } else { None });
+        // KEY V0, ADD V1 0x01, JMP 0x200
+        core.load_program(&[0xF0, 0x0A, 0x71, 0x01, 0x12, 0x00]);
+        core.run_frame(KeypadState::default());
 
-        // Update timers
-        let delay_timer = &mut self.cpu.delay_timer;
-        let sound_timer = &mut self.cpu.sound_timer;
+        assert!(!core.status().waiting_for_key);
+        assert_eq!(core.cpu.registers[0x0], 0xB);
+        assert!(core.cpu.registers[0x1] > 0);
+    }
 
-        *delay_timer = delay_timer.saturating_sub(1);
-        *sound_timer = sound_timer.saturating_sub(1);
+    #[test]
+    fn introspection() {
+        let mut core = Chip8Core::new();
 
-        for _ in 0..self.instructions_per_frame {
-            if self.cpu.store_keypress.is_some() {
-                break;
-            }
-            self.execute_instruction();
-        }
+        // MOV V3 0x2A, KEY V5
+        core.load_program(&[0x63, 0x2A, 0xF5, 0x0A]);
+        core.set_pc(0x1200);
+        assert_eq!(core.pc(), 0x200);
 
-        if let Some(reg) = self.cpu.store_keypress {
-            if let Some(key) = self.keypad_state.iter().enumerate().position(|(key, pressed)| *pressed && last_key != Some(key)) {
-                self.cpu.registers[reg] = key as u8;
-                self.cpu.store_keypress = None;
-                self.cpu.last_keypress = Some(key);
-            }
-        }
+        core.set_i_register(0x300);
+        core.set_delay_timer(10);
+        core.set_sound_timer(20);
+        core.execute_instruction();
+        core.execute_instruction();
 
-        let mut frame = [0; 2 * Self::SCREEN_WIDTH * Self::SCREEN_HEIGHT];
-        let mut i = 0;
+        assert_eq!(core.register(0x3), 0x2A);
+        assert_eq!(core.i_register(), 0x300);
+        assert_eq!((core.delay_timer(), core.sound_timer()), (10, 20));
+        assert_eq!(core.key_wait_register(), Some(0x5));
 
-        for row in &self.frame_buffer {
-            for bit in row {
-                if *bit {
-                    frame[i..=i + 1].clone_from_slice(&Self::WHITE_COLOR.to_le_bytes());
-                }
-                else {
-                    frame[i..=i + 1].clone_from_slice(&Self::BLACK_COLOR.to_le_bytes());
-                }
-                i += 2;
-            }
-        }
+        core.set_register(0x3, 0);
+        assert_eq!(core.cpu.registers[0x3], 0);
+    }
 
-        runtime.upload_video_frame(&frame, Self::SCREEN_WIDTH as u32,
-            Self::SCREEN_HEIGHT as u32, 2 * Self::SCREEN_WIDTH);
+    #[test]
+    fn coverage() {
+        let mut core = Chip8Core::new();
 
-        let idx = self.wave_idx * Self::AUDIO_FRAME_SIZE;
-        self.wave_idx += 1;
-        self.wave_idx %= Self::MAX_WAVE_IDX;
+        // JMP 0x204, unreachable, JMP 0x204
+        core.load_program(&[0x12, 0x04, 0x00, 0x00, 0x12, 0x04]);
+        core.run_frame(KeypadState::default());
 
-        if self.cpu.sound_timer != 0 {
-            let audio_frame = &self.wave[idx..idx + Self::AUDIO_FRAME_SIZE];
-            runtime.upload_audio_frame(audio_frame);
-        }
+        let coverage = core.coverage();
+        assert_eq!(coverage.len(), 6);
+        assert_eq!(coverage.ranges(), vec![(0x200..0x202, true), (0x202..0x204, false), (0x204..0x206, true)]);
     }
 
-    fn load_game(_env: &mut RetroEnvironment, game: RetroGame) -> RetroLoadGameResult<Self> {
-        let args: Vec<String> = env::args().collect();
+    #[test]
+    fn heatmap() {
+        let mut core = Chip8Core::new();
+        assert!(core.heatmap().is_none());
+        core.set_heatmap(true);
 
-        // Quirks
-        let memory = args.iter().any(|s| s == "quirk-memory");
-        let shift = args.iter().any(|s| s == "quirk-shift");
-        let collision = args.iter().any(|s| s == "quirk-collision");
-        let resolution = args.iter().any(|s| s == "quirk-resolution");
-        let lores16 = args.iter().any(|s| s == "quirk-lores16");
+        // MOVI 0x300, SAVE V1, LOAD V0, DRAW V0 V0 1
+        core.load_program(&[0xA3, 0x00, 0xF1, 0x55, 0xA3, 0x00, 0xF0, 0x65, 0xD0, 0x01]);
+        for _ in 0..5 {
+            core.execute_instruction();
+        }
 
-        let mut core = Chip8Core::with_quirks(memory, shift, collision, resolution, lores16);
+        let heatmap = core.heatmap().unwrap();
+        assert_eq!((heatmap.reads(0x300), heatmap.writes(0x300)), (1, 1));
+        assert_eq!((heatmap.reads(0x301), heatmap.writes(0x301)), (1, 1));
+        assert_eq!(heatmap.writes(0x302), 0);
+        assert_eq!(heatmap.reads(0x200), 0);
+    }
 
-        if let Some(ipf_str) = args.iter().find(|s| s.starts_with("ipf=")) {
-            if let Ok(ipf) = ipf_str.split("=").skip(1).next().unwrap().parse() {
-                core.set_instructions_per_frame(ipf);
-            }
-        }
+    #[test]
+    fn reload_rom() {
+        let mut core = Chip8Core::new();
+        let flags_path = env::temp_dir().join("oxid8_reload.rpl");
+        core.set_flags_path(Some(flags_path.clone()));
 
-        let program_data;
-        match game {
-            RetroGame::None { meta: _ } => return RetroLoadGameResult::Failure,
-            RetroGame::Data { meta: _, data, path: _ } => program_data = data,
-            RetroGame::Path { meta: _, path } => {
-                if let Ok(data) = read(path) {
-                    program_data = data;
-                } else {
-                    return RetroLoadGameResult::Failure;
-                }
-            },
-        }
+        // MOVI 0x000, DRAW V0 V0 5, JMP 0x204
+        core.load_program(&[0xA0, 0x00, 0xD0, 0x05, 0x12, 0x04]);
+        core.run_frame(KeypadState::default());
+        let hash = core.framebuffer_hash();
 
-        core.cpu.load_program(program_data.as_slice());
+        // JMP 0x200
+        core.reload_rom(&[0x12, 0x00], true);
+        assert_eq!(core.cpu.pc, 0x200);
+        assert_eq!(core.cpu.memory[0x200..0x204], [0x12, 0x00, 0x00, 0x00]);
+        assert_eq!(core.framebuffer_hash(), hash);
+        assert_eq!(core.flags_path(), Some(flags_path.as_path()));
 
-        RetroLoadGameResult::Success {
-            region: RetroRegion::NTSC,
-            audio: RetroAudioInfo::new(Self::SAMPLE_RATE),
-            video: RetroVideoInfo::new(Self::FRAME_RATE, Self::SCREEN_WIDTH as u32, Self::SCREEN_HEIGHT as u32)
-                .with_pixel_format(RetroPixelFormat::RGB565),
-            core,
-        }
+        core.reload_rom(&[0x12, 0x00], false);
+        assert_ne!(core.framebuffer_hash(), hash);
     }
-}
 
-libretro_core!(Chip8Core);
+    #[test]
+    fn mid_frame_poll() {
+        let mut core = Chip8Core::new();
+        core.mid_frame_poll = true;
 
+        // ADD V1 0x01, SKPK V0, JMP 0x200, ADD V2 0x01, JMP 0x200
+        core.load_program(&[0x71, 0x01, 0xE0, 0x9E, 0x12, 0x00, 0x72, 0x01, 0x12, 0x00]);
+        core.set_instructions_per_frame(12);
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        let mut polls = Vec::new();
+        core.run_frame_polled(|| {
+            polls.push(());
+            KeypadState(if polls.len() == 2 { 1 } else { 0 })
+        });
+
+        // The key is seen after 6 instructions, by the third SKPK
+        assert_eq!(polls.len(), 2);
+        assert_eq!(core.counters().instructions, 12);
+        assert_eq!(core.cpu.registers[0x2], 1);
+    }
 
     #[test]
-    fn add() {
+    fn paused() {
         let mut core = Chip8Core::new();
 
-        core.cpu.registers[0x2] = 200;
-        
-        core.add(HashMap::from([("X", 0x2), ("N", 100)]));
-
-        assert_eq!(core.cpu.registers[0x2], 44);
+        // MOV V0 0x05, SNDR V0, JMP 0x204
+        core.load_program(&[0x60, 0x05, 0xF0, 0x18, 0x12, 0x04]);
+        core.run_frame(KeypadState::default());
+        let pc = core.cpu.pc;
+
+        core.set_paused(true);
+        let output = core.run_frame(KeypadState::default());
+        assert!(output.audio.is_empty());
+        assert!(!output.video.is_empty());
+        assert_eq!(core.cpu.sound_timer, 5);
+        assert_eq!(core.counters().frames, 1);
+
+        core.set_paused(false);
+        assert!(!core.run_frame(KeypadState::default()).audio.is_empty());
+        assert_eq!(core.cpu.sound_timer, 4);
+        assert_eq!(core.cpu.pc, pc);
     }
 
     #[test]
-    fn addr() {
+    fn timings() {
         let mut core = Chip8Core::new();
+        core.load_program(&[0x12, 0x00]);
+        core.run_frame(KeypadState::default());
+        assert!(core.timings().is_none());
 
-        core.cpu.registers[0x2] = 25;
-        core.cpu.registers[0x3] = 42;
-        core.cpu.registers[0xF] = 33;
+        core.set_timing(true);
+        core.run_frame(KeypadState::default());
+        core.run_frame(KeypadState::default());
+        assert_eq!(core.timings().unwrap().frames, 2);
+    }
 
-        core.addr(HashMap::from([("X", 0x2), ("Y", 0x3)]));
+    #[test]
+    fn frame_budget() {
+        let mut core = Chip8Core::new();
+        core.load_program(&[0x12, 0x00]);
+        core.set_instructions_per_frame(10 * Chip8Core::WATCHDOG_INTERVAL);
+        core.run_frame(KeypadState::default());
+        assert_eq!(core.counters().instructions, 10 * Chip8Core::WATCHDOG_INTERVAL as u64);
 
-        assert_eq!(core.cpu.registers[0x2], 67);
-        assert_eq!(core.cpu.registers[0xF], 0);
+        // Any frame exceeds a budget of zero, and stops at the first check
+        core.set_frame_budget(Some(Duration::ZERO));
+        core.run_frame(KeypadState::default());
+        core.run_frame(KeypadState::default());
+        assert_eq!(core.counters().instructions, 12 * Chip8Core::WATCHDOG_INTERVAL as u64);
+        assert_eq!(core.counters().clamped_frames, 2);
+        assert!(core.pending_message.is_some());
 
-        core.cpu.registers[0x2] = 255;
-        core.cpu.registers[0x3] = 20;
+        // Frames that fit in the budget are not checked
+        core.set_instructions_per_frame(Chip8Core::WATCHDOG_INTERVAL - 1);
+        core.run_frame(KeypadState::default());
+        assert_eq!(core.counters().clamped_frames, 2);
+    }
 
-        core.addr(HashMap::from([("X", 0x2), ("Y", 0x3)]));
+    #[test]
+    fn rand_mask() {
+        let mut core = Chip8Core::new();
+        core.set_random_source(Some(Box::new(random::Counter::new(0))));
+
+        // Every mask with every random byte
+        for n in 0..=0xFF {
+            core.load_program(&[0xC0, n]);
+            for value in 0..=0xFF {
+                core.cpu.pc = 0x200;
+                core.execute_instruction();
+                assert_eq!(core.cpu.registers[0x0], value & n);
+            }
+        }
 
-        assert_eq!(core.cpu.registers[0x2], 19);
-        assert_eq!(core.cpu.registers[0xF], 1);
+        core.set_random_source(Some(Box::new(random::FixedSequence::new(&[0xAB]))));
+        core.load_program(&[0xC0, 0x0F]);
+        core.soft_reset();
+        core.execute_instruction();
+        assert_eq!(core.cpu.registers[0x0], 0x0B);
     }
 
     #[test]
-    fn movi() {
+    fn input_script() {
         let mut core = Chip8Core::new();
-        let addr = 0x34E;
+        core.set_input_script(InputScript::parse("7@2+1").unwrap());
 
-        core.movi(HashMap::from([("N", addr)]));
+        // SKPK V0, JMP 0x200, ADD V1 0x01, JMP 0x200
+        core.load_program(&[0xE0, 0x9E, 0x12, 0x00, 0x71, 0x01, 0x12, 0x00]);
+        core.cpu.registers[0x0] = 0x7;
 
-        assert_eq!(core.cpu.i_register, addr);
+        core.run_frame(KeypadState::default());
+        core.run_frame(KeypadState::default());
+        assert_eq!(core.cpu.registers[0x1], 0);
+
+        core.run_frame(KeypadState::default());
+        assert!(core.cpu.registers[0x1] > 0);
+
+        // The script runs again after a reset
+        core.soft_reset();
+        core.cpu.registers[0x0] = 0x7;
+        for _ in 0..3 {
+            core.run_frame(KeypadState::default());
+        }
+        assert!(core.cpu.registers[0x1] > 0);
     }
 
     #[test]
-    fn rsubr() {
+    fn present() {
         let mut core = Chip8Core::new();
+        let off = Palette::default().rgb565(0).to_le_bytes();
+        let on = Palette::default().rgb565(1).to_le_bytes();
 
-        core.cpu.registers[0x2] = 31;
-        core.cpu.registers[0x3] = 65;
-        core.cpu.registers[0xF] = 33;
-
-        core.rsubr(HashMap::from([("X", 0x2), ("Y", 0x3)]));
+        // MOVI 0x000, DRAW V0 V0 1, JMP 0x204
+        core.load_program(&[0xA0, 0x00, 0xD0, 0x01, 0x12, 0x04]);
+        core.set_instructions_per_frame(1);
 
-        assert_eq!(core.cpu.registers[0x2], 34);
-        assert_eq!(core.cpu.registers[0xF], 1);
+        assert_eq!(core.run_frame(KeypadState::default()).video[..2], off);
 
-        core.cpu.registers[0x2] = 31;
-        core.cpu.registers[0x3] = 20;
+        // Drawing only affects the working buffer until the frame is presented
+        core.execute_instruction();
+        assert!(pixel(&core, 0, 0));
+        assert_eq!(core.video_frame[..2], off);
 
-        core.rsubr(HashMap::from([("X", 0x2), ("Y", 0x3)]));
+        assert_eq!(core.run_frame(KeypadState::default()).video[..2], on);
 
-        assert_eq!(core.cpu.registers[0x2], 245);
-        assert_eq!(core.cpu.registers[0xF], 0);
+        core.apply_options(CoreOptions { palette: Palette::MONO, ..CoreOptions::default() });
+        assert_eq!(core.run_frame(KeypadState::default()).video[..2], Palette::MONO.rgb565(1).to_le_bytes());
     }
 
     #[test]
-    fn shl() {
+    fn palette_fade() {
         let mut core = Chip8Core::new();
+        let frames = Chip8Core::PALETTE_FADE_FRAMES;
+        core.run_frame(KeypadState::default());
 
-        core.cpu.registers[0x2] = 0x01;
-        core.cpu.registers[0xF] = 33;
+        // Shown at once while paused, without fading
+        core.set_paused(true);
+        core.set_palette(Palette::MONO, false);
+        assert_eq!(core.run_frame(KeypadState::default()).video[..2], Palette::MONO.rgb565(0).to_le_bytes());
 
-        core.shl(HashMap::from([("X", 0x1), ("Y", 0x2)]));
+        core.set_palette(Palette::OCTO, true);
+        let halfway = Palette::MONO.blend(&Palette::OCTO, frames / 2, frames);
+        let video: Vec<_> = (0..frames).map(|_| core.run_frame(KeypadState::default()).video[..2].to_vec()).collect();
+        assert_eq!(video[frames as usize / 2 - 1], halfway.rgb565(0).to_le_bytes());
+        assert_eq!(video[frames as usize - 1], Palette::OCTO.rgb565(0).to_le_bytes());
+        assert_eq!(core.palette_fade, None);
+    }
 
-        assert_eq!(core.cpu.registers[0x1], 0x2);
-        assert_eq!(core.cpu.registers[0xF], 0x0);
+    #[test]
+    fn runtime_options() {
+        let mut core = Chip8Core::with_options(CoreOptions::from_args(&[]));
+        let frames = Chip8Core::PALETTE_FADE_FRAMES;
+
+        // FONT V0, DRAW V0 V0 5, JMP 0x204
+        core.load_program(&[0xF0, 0x29, 0xD0, 0x05, 0x12, 0x04]);
+        core.run_frame(KeypadState::default());
+        let frame_buffer = core.frame_buffer;
+        core.set_paused(true);
+
+        // Changed in the frontend's menu while paused
+        let values = [
+            ("oxid8_palette", "octo"), ("oxid8_palette_fade", "enabled"), ("oxid8_draw_limit", "2"),
+            ("oxid8_speed", "0.5x"), ("oxid8_fast_forward_speed", "4x"),
+        ];
+        let mut options = CoreOptions::from_args(&[]);
+        options.apply_values(|key| values.iter().find(|(k, _)| *k == key).map(|(_, v)| v.to_string()));
+        core.apply_runtime_options(&options);
+
+        assert_eq!(core.draw_limit, 2);
+        assert_eq!((core.speed_percent(), core.fast_forward_percent), (50, 400));
+        assert!(core.palette_fade.is_some());
+        let video: Vec<_> = (0..frames).map(|_| core.run_frame(KeypadState::default()).video[..2].to_vec()).collect();
+        // The top left pixel is drawn
+        assert_ne!(video[0], Palette::OCTO.rgb565(1).to_le_bytes());
+        assert_eq!(video[frames as usize - 1], Palette::OCTO.rgb565(1).to_le_bytes());
+
+        // The screen is redrawn, not reset
+        assert_eq!(core.frame_buffer, frame_buffer);
+        assert_ne!(frame_buffer, [0; Chip8Core::SCREEN_HEIGHT]);
+    }
 
-        core.cpu.registers[0x2] = 0x81;
+    #[test]
+    fn key_edge() {
+        let mut core = Chip8Core::new();
+        core.key_edge = true;
 
-        core.shl(HashMap::from([("X", 0x1), ("Y", 0x2)]));
+        // KEY V0, JMP 0x202
+        core.load_program(&[0xF0, 0x0A, 0x12, 0x02]);
 
-        assert_eq!(core.cpu.registers[0x1], 0x2);
-        assert_eq!(core.cpu.registers[0xF], 0x1);
+        core.run_frame(KeypadState(1 << 0x3));
+        core.run_frame(KeypadState(1 << 0x3));
+        assert!(core.status().waiting_for_key);
+
+        core.run_frame(KeypadState::default());
+        core.run_frame(KeypadState(1 << 0x3));
+        assert!(!core.status().waiting_for_key);
+        assert_eq!(core.cpu.registers[0x0], 0x3);
     }
 
     #[test]
-    fn jmpr() {
+    fn key_events() {
         let mut core = Chip8Core::new();
 
-        core.cpu.registers[0x0] = 0x40;
+        // KEY V0, JMP 0x202
+        core.load_program(&[0xF0, 0x0A, 0x12, 0x02]);
+        core.run_frame(KeypadState::default());
 
-        core.jmpr(HashMap::from([("N", 0x300)]));
+        // Presses shorter than a frame, the most recent one is stored
+        core.key_event(0xC, true);
+        core.key_event(0x1, true);
+        core.key_event(0xC, false);
+        core.key_event(0x1, false);
+        core.run_frame(KeypadState::default());
 
-        assert_eq!(core.cpu.pc, 0x340);
+        assert!(!core.status().waiting_for_key);
+        assert_eq!(core.cpu.registers[0x0], 0x1);
     }
 
     #[test]
-    fn call_ret() {
-        let mut core = Chip8Core::new();
+    fn alternate_keys() {
+        let args = vec!["key-5=w,up".to_string()];
+        let mut core = Chip8Core::with_options(CoreOptions::from_args(&args));
+        core.load_program(&[0x12, 0x00]);
+        let (w, up) = (input::keycode("w").unwrap(), input::keycode("up").unwrap());
 
-        let pc = 0x432;
-        let addr = 0x6A2;
+        core.keyboard_key_event(w, true);
+        core.keyboard_key_event(up, true);
+        core.run_frame(KeypadState::default());
+        assert!(core.keypad_state[0x5]);
 
-        core.cpu.pc = pc;
-        core.call(HashMap::from([("N", addr)]));
+        // Held while either key is
+        core.keyboard_key_event(w, false);
+        core.run_frame(KeypadState::default());
+        assert!(core.keypad_state[0x5]);
 
-        assert_eq!(core.cpu.pc, addr);
-        assert_eq!(core.cpu.stack, vec![pc]);
+        core.keyboard_key_event(up, false);
+        core.run_frame(KeypadState::default());
+        assert!(!core.keypad_state[0x5]);
+    }
 
-        core.ret(HashMap::new());
+    #[test]
+    fn font_layout() {
+        let args: Vec<String> = ["font-addr=0x50", "fill-interpreter-area"].iter().map(|s| s.to_string()).collect();
+        let mut core = Chip8Core::with_options(CoreOptions::from_args(&args));
 
-        assert_eq!(core.cpu.pc, pc);
-        assert_eq!(core.cpu.stack, Vec::new());
+        core.cpu.registers[0x2] = 0xA;
+        core.digit(Args::from([("X", 0x2)]));
+
+        assert_eq!(core.cpu.i_register, 0x50 + 0xA * 5);
+        assert_eq!(core.cpu.memory[0x50], 0xF0);
+        assert_ne!(core.cpu.memory[0x00], 0x00);
     }
 
     #[test]
-    fn skpeqr() {
+    fn large_font_digits() {
         let mut core = Chip8Core::new();
+        core.cpu.registers[0x1] = 0xB;
 
-        let pc = 0x3A0;
-        core.cpu.pc = pc;
+        core.ldigit(Args::from([("X", 0x1)]));
+        assert_eq!(core.cpu.i_register, 0x80 + 0xB * 10);
+        assert_eq!(core.cpu.memory[0x80 + 0xB * 10], 0xFC);
 
-        let v = vec![0x42, 0x34, 0x42];
-        core.cpu.registers[0x0] = v[0];
-        core.cpu.registers[0x1] = v[1];
-        core.cpu.registers[0x2] = v[2];
+        let args: Vec<String> = ["platform=schip-legacy"].iter().map(|s| s.to_string()).collect();
+        let mut core = Chip8Core::with_options(CoreOptions::from_args(&args));
+        core.cpu.registers[0x1] = 0xB;
 
-        core.skpeqr(HashMap::from([("X", 0x0), ("Y", 0x1)]));
-        assert_eq!(core.cpu.pc, pc);
+        core.ldigit(Args::from([("X", 0x1)]));
+        assert_eq!(core.cpu.i_register, 0x80 + 10);
+        assert_eq!(core.cpu.memory[0x80 + 100], 0x00);
+    }
 
-        core.skpeqr(HashMap::from([("X", 0x0), ("Y", 0x2)]));
-        assert_eq!(core.cpu.pc, pc + 2);
+    #[test]
+    fn font_set() {
+        let args: Vec<String> = ["font=vip"].iter().map(|s| s.to_string()).collect();
+        let core = Chip8Core::with_options(CoreOptions::from_args(&args));
+
+        // Digit 1
+        assert_eq!(core.cpu.memory[5..10], [0x60, 0x20, 0x20, 0x20, 0x70]);
     }
 
     #[test]
-    fn skpk() {
+    fn opcode_warnings() {
         let mut core = Chip8Core::new();
+        core.opcode_warnings = true;
 
-        let pc = 0x3A0;
-        core.cpu.pc = pc;
-        
-        let key = 0xB;
-        core.keypad_state[key] = true;
-
-        core.cpu.registers[0x0] = 0x8;
-        core.skpk(HashMap::from([("X", 0x0)]));
-        assert_eq!(core.cpu.pc, pc);
+        // PLANE 1, invalid, PLANE 1
+        core.load_program(&[0xF1, 0x01, 0xE0, 0x00, 0xF1, 0x01]);
+        for _ in 0..3 {
+            core.execute_instruction();
+        }
 
-        core.cpu.registers[0x0] = 0xB;
-        core.skpk(HashMap::from([("X", 0x0)]));
-        assert_eq!(core.cpu.pc, pc + 2);
+        assert_eq!(core.warned_opcodes, HashSet::from([0xF101, 0xE000]));
+        assert_eq!(core.unsupported_opcodes(), [0xE000, 0xF101]);
     }
 
     #[test]
-    fn timr() {
+    fn machine_code_calls() {
         let mut core = Chip8Core::new();
 
-        let val = 0x7A;
-        core.cpu.delay_timer = val;
+        // SYS 0x250, SYS 0x250, SYS 0x000, SYS 0x300
+        core.load_program(&[0x02, 0x50, 0x02, 0x50, 0x00, 0x00, 0x03, 0x00]);
+        core.execute_instruction();
+        assert_eq!(core.pending_message.take().as_deref(), Some("Program uses 1802 machine code, which is not supported"));
 
-        core.timr(HashMap::from([("X", 0x2)]));
-        assert_eq!(core.cpu.registers[0x2], val);
+        for _ in 0..3 {
+            core.execute_instruction();
+        }
+        assert_eq!(core.machine_code_calls, HashSet::from([0x250, 0x300]));
+        assert_eq!(core.pending_message, None);
+        assert_eq!(core.pc(), 0x208);
     }
 
     #[test]
-    fn bcd() {
-        let mut core = Chip8Core::new();
-
-        let i = 0x400 as usize;
-        core.cpu.i_register = i as u16;
+    fn stack_monitor() {
+        let mut core = Chip8Core::with_options(CoreOptions::from_args(&["warn-recursion=4".to_string()]));
 
-        core.cpu.registers[0x4] = 159;
+        // CALL 0x200
+        core.load_program(&[0x22, 0x00]);
+        for _ in 0..5 {
+            core.execute_instruction();
+        }
+        assert_eq!(core.stack_monitor().unwrap().warnings(), [stack::StackWarning::Recursion { target: 0x200, from: 0x200, depth: 5 }]);
 
-        core.bcd(HashMap::from([("X", 0x4)]));
+        for _ in 0..180 {
+            core.run_frame(KeypadState::default());
+        }
+        assert!(matches!(core.stack_monitor().unwrap().warnings()[1], stack::StackWarning::Imbalance { returns: 0, .. }));
 
-        assert_eq!(core.cpu.memory[i], 1);
-        assert_eq!(core.cpu.memory[i + 1], 5);
-        assert_eq!(core.cpu.memory[i + 2], 9);
+        core.soft_reset();
+        assert!(core.stack_monitor().unwrap().warnings().is_empty());
     }
 
     #[test]
-    fn save() {
+    fn uninit_warnings() {
         let mut core = Chip8Core::new();
+        core.uninit_warnings = true;
 
-        let i = 0x400 as usize;
-        let v = vec![0x41, 0x9B, 0xEE];
+        // MOVI 0x300, LOAD V0, MOVI 0x300, SAVE V0, MOVI 0x300, LOAD V0, MOVI 0x200, LOAD V0
+        core.load_program(&[0xA3, 0x00, 0xF0, 0x65, 0xA3, 0x00, 0xF0, 0x55, 0xA3, 0x00, 0xF0, 0x65, 0xA2, 0x00, 0xF0, 0x65]);
+        for _ in 0..8 {
+            core.execute_instruction();
+        }
 
-        core.cpu.i_register = i as u16;
+        // Only the read before the write is reported, since the program is initialized
+        assert_eq!(core.uninit_read_warnings, HashSet::from([0x202]));
+    }
 
-        core.cpu.registers[0x0] = v[0];
-        core.cpu.registers[0x1] = v[1];
-        core.cpu.registers[0x2] = v[2];
+    #[test]
+    fn sound_timer_latch() {
+        let mut core = Chip8Core::new();
 
-        core.save(HashMap::from([("X", 0x2)]));
+        // MOV V0 0x01, SNDR V0, MOV V0 0x02, SNDR V0
+        core.load_program(&[0x60, 0x01, 0xF0, 0x18, 0x60, 0x02, 0xF0, 0x18]);
+        core.execute_instruction();
+        core.execute_instruction();
+        assert_eq!(core.cpu.sound_timer, 0);
 
-        assert_eq!(core.cpu.memory[i], v[0]);
-        assert_eq!(core.cpu.memory[i + 1], v[1]);
-        assert_eq!(core.cpu.memory[i + 2], v[2]);
+        core.execute_instruction();
+        core.execute_instruction();
+        assert_eq!(core.cpu.sound_timer, 2);
 
-        assert_eq!(core.cpu.i_register, (i + 3) as u16);
+        core.platform = Platform::SuperChip;
+        core.soft_reset();
+        core.execute_instruction();
+        core.execute_instruction();
+        assert_eq!(core.cpu.sound_timer, 1);
     }
 
     #[test]
-    fn load() {
+    fn extension_not_machine_code() {
         let mut core = Chip8Core::new();
+        core.opcode_warnings = true;
 
-        let i = 0x400 as usize;
-        let v = vec![0x20, 0x45, 0xAF];
+        // SCU 1 (XO-CHIP)
+        core.load_program(&[0x00, 0xD1]);
+        core.execute_instruction();
+        assert!(core.machine_code_calls.is_empty());
+        assert_eq!(core.pending_message, None);
+        assert_eq!(core.warned_opcodes, HashSet::from([0x00D1]));
+    }
 
-        core.cpu.i_register = i as u16;
+    #[test]
+    fn strict_register_skips() {
+        let mut core = Chip8Core::new();
+        core.opcode_warnings = true;
 
-        core.cpu.memory[i] = v[0];
-        core.cpu.memory[i + 1] = v[1];
-        core.cpu.memory[i + 2] = v[2];
+        // SKPEQR V0 V1, SAVER V0-V1 (XO-CHIP), invalid, SKPNER V0 V1
+        core.load_program(&[0x50, 0x10, 0x50, 0x12, 0x90, 0x1F, 0x90, 0x10]);
+        core.execute_instruction();
+        assert_eq!(core.cpu.pc, 0x204);
 
-        core.load(HashMap::from([("X", 0x2)]));
+        core.execute_instruction();
+        core.execute_instruction();
+        assert_eq!(core.cpu.pc, 0x208);
+        assert_eq!(core.warned_opcodes, HashSet::from([0x901F]));
 
-        assert_eq!(core.cpu.registers[0x0], v[0]);
-        assert_eq!(core.cpu.registers[0x1], v[1]);
-        assert_eq!(core.cpu.registers[0x2], v[2]);
+        core.cpu.pc = 0x202;
+        core.execute_instruction();
+        assert_eq!(core.warned_opcodes, HashSet::from([0x901F, 0x5012]));
+    }
 
-        assert_eq!(core.cpu.i_register, (i + 3) as u16);
+    #[test]
+    fn key_release() {
+        let mut core = Chip8Core::with_quirks(Quirks { key_release: true, ..Quirks::default() });
+
+        // KEY V0, JMP 0x202
+        core.load_program(&[0xF0, 0x0A, 0x12, 0x02]);
+
+        core.run_frame(KeypadState((1 << 0x2) | (1 << 0x9)));
+        assert!(core.status().waiting_for_key);
+
+        core.run_frame(KeypadState(1 << 0x2));
+        assert!(!core.status().waiting_for_key);
+        assert_eq!(core.cpu.registers[0x0], 0x9);
     }
 }
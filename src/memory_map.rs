@@ -0,0 +1,116 @@
+use libretro_rs::{RetroMemoryDescriptor, RETRO_MEMDESC_BIGENDIAN, RETRO_MEMDESC_CONST, RETRO_MEMDESC_SYSTEM_RAM};
+
+use crate::cpu::Cpu;
+
+/// Start of the emulated RAM in the exposed address space.
+pub const RAM_START: usize = 0x0000;
+/// Size of the emulated RAM (4 KiB).
+pub const RAM_SIZE: usize = 0x1000;
+
+/// Start of the virtual register page, placed directly after RAM.
+pub const REGISTERS_START: usize = 0x1000;
+/// Size of the virtual register page.
+pub const REGISTERS_SIZE: usize = 0x20;
+
+/// Offset of registers `V0` to `VF` within the register page.
+pub const V_OFFSET: usize = 0x00;
+/// Offset of the `I` register (big endian) within the register page.
+pub const I_OFFSET: usize = 0x10;
+/// Offset of the delay timer within the register page.
+pub const DELAY_TIMER_OFFSET: usize = 0x12;
+/// Offset of the sound timer within the register page.
+pub const SOUND_TIMER_OFFSET: usize = 0x13;
+/// Offset of the program counter (big endian) within the register page.
+pub const PC_OFFSET: usize = 0x14;
+/// Offset of the stack pointer (number of entries on the stack) within the register page.
+pub const SP_OFFSET: usize = 0x16;
+
+/// Mirror of the CPU registers laid out contiguously, so that they can be
+/// exposed to the frontend (e.g. for RetroAchievements) alongside RAM. The page is
+/// refreshed once per frame and is read-only; writes made by the frontend are not
+/// propagated back. The stack pointer saturates at 255 entries.
+pub struct RegisterPage {
+    data: [u8; REGISTERS_SIZE],
+}
+
+impl RegisterPage {
+    pub fn new() -> Self {
+        Self { data: [0; REGISTERS_SIZE] }
+    }
+
+    /// Copy the current register values from the CPU into the page.
+    pub fn update(&mut self, cpu: &Cpu) {
+        self.data[V_OFFSET..V_OFFSET + 16].copy_from_slice(&cpu.registers);
+        self.data[I_OFFSET..I_OFFSET + 2].copy_from_slice(&cpu.i_register.to_be_bytes());
+        self.data[DELAY_TIMER_OFFSET] = cpu.delay_timer;
+        self.data[SOUND_TIMER_OFFSET] = cpu.sound_timer;
+        self.data[PC_OFFSET..PC_OFFSET + 2].copy_from_slice(&cpu.pc.to_be_bytes());
+        self.data[SP_OFFSET] = cpu.stack.len().min(u8::MAX as usize) as u8;
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+impl Default for RegisterPage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Build the memory descriptors for RAM and the register page. Both regions must be
+/// heap-allocated so that the pointers remain valid after the core is moved.
+pub fn descriptors(memory: &mut [u8; RAM_SIZE], registers: &mut RegisterPage) -> Vec<RetroMemoryDescriptor> {
+    vec![
+        RetroMemoryDescriptor {
+            flags: RETRO_MEMDESC_SYSTEM_RAM,
+            ptr: memory.as_mut_ptr(),
+            offset: 0,
+            start: RAM_START,
+            select: 0,
+            disconnect: 0,
+            len: RAM_SIZE,
+            addrspace: None,
+        },
+        RetroMemoryDescriptor {
+            flags: RETRO_MEMDESC_BIGENDIAN | RETRO_MEMDESC_CONST,
+            ptr: registers.data.as_mut_ptr(),
+            offset: 0,
+            start: REGISTERS_START,
+            select: 0,
+            disconnect: 0,
+            len: REGISTERS_SIZE,
+            addrspace: None,
+        },
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn register_page_layout() {
+        let mut cpu = Cpu::new();
+        cpu.registers[0x3] = 0x42;
+        cpu.registers[0xF] = 0x01;
+        cpu.i_register = 0x0ABC;
+        cpu.delay_timer = 0x10;
+        cpu.sound_timer = 0x20;
+        cpu.pc = 0x0246;
+        cpu.stack = vec![0x202, 0x230];
+
+        let mut page = RegisterPage::new();
+        page.update(&cpu);
+
+        let data = page.as_slice();
+        assert_eq!(data[V_OFFSET + 0x3], 0x42);
+        assert_eq!(data[V_OFFSET + 0xF], 0x01);
+        assert_eq!(data[I_OFFSET..I_OFFSET + 2], [0x0A, 0xBC]);
+        assert_eq!(data[DELAY_TIMER_OFFSET], 0x10);
+        assert_eq!(data[SOUND_TIMER_OFFSET], 0x20);
+        assert_eq!(data[PC_OFFSET..PC_OFFSET + 2], [0x02, 0x46]);
+        assert_eq!(data[SP_OFFSET], 2);
+    }
+}
@@ -0,0 +1,402 @@
+use std::fmt;
+
+use crate::Chip8Core;
+use crate::input::KeypadState;
+use crate::platform::{Platform, Quirks};
+
+/// Error returned when a movie cannot be parsed or does not match the current setup.
+#[derive(Debug, PartialEq, Eq)]
+pub enum MovieError {
+    InvalidFormat,
+    UnsupportedVersion(u8),
+    CoreVersionMismatch { movie: String, core: String },
+    RomMismatch,
+    PlatformMismatch { movie: Platform, core: Platform },
+    QuirksMismatch { movie: Quirks, core: Quirks },
+    SpeedMismatch { movie: u32, core: u32 },
+    FrameOutOfRange { frame: usize, frames: usize },
+    /// The movie has no screen hashes to verify playback against.
+    MissingHashes,
+    /// The screen after `frame` (counted from 0) differs from the one recorded.
+    Divergence { frame: usize, expected: u64, actual: u64 },
+}
+
+impl fmt::Display for MovieError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidFormat => write!(f, "not a valid movie file"),
+            Self::UnsupportedVersion(v) => write!(f, "unsupported movie format version {}", v),
+            Self::CoreVersionMismatch { movie, core } =>
+                write!(f, "movie was recorded with core version {}, current version is {}", movie, core),
+            Self::RomMismatch => write!(f, "movie was recorded with a different ROM"),
+            Self::FrameOutOfRange { frame, frames } =>
+                write!(f, "frame {} is past the end of the movie ({} frames)", frame, frames),
+            Self::MissingHashes => write!(f, "movie was recorded without screen hashes"),
+            Self::Divergence { frame, expected, actual } =>
+                write!(f, "playback diverges at frame {} (screen hash {:016X}, recorded {:016X})", frame, actual, expected),
+            Self::PlatformMismatch { movie, core } =>
+                write!(f, "movie was recorded on platform {}, current platform is {}", movie.name(), core.name()),
+            Self::QuirksMismatch { movie, core } =>
+                write!(f, "movie was recorded with quirks {:?}, current quirks are {:?}", movie, core),
+            Self::SpeedMismatch { movie, core } =>
+                write!(f, "movie was recorded at {} instructions per frame, current speed is {}", movie, core),
+        }
+    }
+}
+
+impl std::error::Error for MovieError {}
+
+/// Emulator setup a movie was recorded with. Playback is refused if it differs from the
+/// current setup, since the recorded inputs would otherwise silently desync.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MovieHeader {
+    pub core_version: String,
+    pub rom_hash: [u8; 20],
+    pub seed: u64,
+    pub platform: Platform,
+    pub quirks: Quirks,
+    pub instructions_per_frame: u32,
+}
+
+impl MovieHeader {
+    /// Capture the current setup of a core.
+    pub fn from_core(core: &Chip8Core) -> Self {
+        Self {
+            core_version: Movie::CORE_VERSION.to_string(),
+            rom_hash: core.program_hash(),
+            seed: core.seed(),
+            platform: core.platform(),
+            quirks: core.quirks(),
+            instructions_per_frame: core.instructions_per_frame() as u32,
+        }
+    }
+
+    /// Check that a core is set up identically to the one the movie was recorded with.
+    /// The RNG seed is not checked, since it is restored when playback starts.
+    pub fn verify(&self, core: &Chip8Core) -> Result<(), MovieError> {
+        let current = Self::from_core(core);
+
+        if self.core_version != current.core_version {
+            return Err(MovieError::CoreVersionMismatch { movie: self.core_version.clone(), core: current.core_version });
+        }
+        if self.rom_hash != current.rom_hash {
+            return Err(MovieError::RomMismatch);
+        }
+        if self.platform != current.platform {
+            return Err(MovieError::PlatformMismatch { movie: self.platform, core: current.platform });
+        }
+        if self.quirks != current.quirks {
+            return Err(MovieError::QuirksMismatch { movie: self.quirks, core: current.quirks });
+        }
+        if self.instructions_per_frame != current.instructions_per_frame {
+            return Err(MovieError::SpeedMismatch { movie: self.instructions_per_frame, core: current.instructions_per_frame });
+        }
+
+        Ok(())
+    }
+}
+
+/// Input recording: the setup it was recorded with, followed by the keypad state of
+/// every frame and optionally the hash of the screen after every frame, against which
+/// playback can be verified.
+///
+/// Binary layout (little endian):
+///
+/// | Size | Field                                     |
+/// |------|-------------------------------------------|
+/// | 4    | Magic (`OX8M`)                            |
+/// | 1    | Format version                            |
+/// | 1    | Length `L` of the core version            |
+/// | L    | Core version (UTF-8)                      |
+/// | 20   | ROM SHA-1                                 |
+/// | 8    | RNG seed                                  |
+/// | 1    | Platform identifier                       |
+/// | 1    | Quirks bit field                          |
+/// | 4    | Instructions per frame                    |
+/// | 4    | Number of frames `N`                      |
+/// | 2N   | Keypad bit field of each frame            |
+/// | 1    | Screen hashes follow (1) or not (0)       |
+/// | 8N   | Screen hash after each frame, if present  |
+///
+/// Version 1 files end after the keypad states, with no screen hashes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Movie {
+    pub header: MovieHeader,
+    pub inputs: Vec<u16>,
+    /// Hash of the screen after each frame (see `Chip8Core::framebuffer_hash`), or empty
+    /// if the movie was recorded without them.
+    pub hashes: Vec<u64>,
+}
+
+impl Movie {
+    const MAGIC: &'static [u8; 4] = b"OX8M";
+    const VERSION: u8 = 2;
+    const CORE_VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+    pub fn new(header: MovieHeader) -> Self {
+        Self { header, inputs: Vec::new(), hashes: Vec::new() }
+    }
+
+    /// Start recording a movie from the current setup of a core.
+    pub fn record(core: &Chip8Core) -> Self {
+        Self::new(MovieHeader::from_core(core))
+    }
+
+    /// Append the keypad state of a frame.
+    pub fn record_frame(&mut self, keypad: u16) {
+        self.inputs.push(keypad);
+    }
+
+    /// Append the hash of the screen after the last recorded frame, so that playback can
+    /// be verified. Hashes must be recorded for every frame or for none.
+    pub fn record_hash(&mut self, hash: u64) {
+        self.hashes.push(hash);
+    }
+
+    /// Whether a screen hash was recorded for every frame.
+    pub fn has_hashes(&self) -> bool {
+        self.hashes.len() == self.inputs.len()
+    }
+
+    /// Verify the setup of a core and restore the recorded RNG seed, resetting the core
+    /// so that playback starts from the power-on state. The frame time budget is removed,
+    /// since frames cut short by it depend on the speed of the host and would make
+    /// playback diverge.
+    pub fn start_playback(&self, core: &mut Chip8Core) -> Result<(), MovieError> {
+        self.header.verify(core)?;
+        core.set_frame_budget(None);
+        core.set_seed(self.header.seed);
+        core.soft_reset();
+        Ok(())
+    }
+
+    /// Bring a core to the state it was in right before `frame` (counted from 0) during
+    /// recording, by restarting playback and running the recorded inputs of the earlier
+    /// frames. Since execution is deterministic, the state is exact, for jumping back and
+    /// forth in time while debugging.
+    pub fn seek(&self, core: &mut Chip8Core, frame: usize) -> Result<(), MovieError> {
+        if frame > self.inputs.len() {
+            return Err(MovieError::FrameOutOfRange { frame, frames: self.inputs.len() });
+        }
+
+        self.start_playback(core)?;
+        for &keypad in &self.inputs[..frame] {
+            core.run_frame(KeypadState(keypad));
+        }
+        Ok(())
+    }
+
+    /// Play the movie back from the start, comparing the screen after every frame against
+    /// the recorded hash, and stop at the first frame that differs. Returns the number of
+    /// frames verified, for checking that a new release still runs a program identically.
+    pub fn verify_playback(&self, core: &mut Chip8Core) -> Result<usize, MovieError> {
+        if !self.has_hashes() {
+            return Err(MovieError::MissingHashes);
+        }
+
+        self.start_playback(core)?;
+        for (frame, (&keypad, &expected)) in self.inputs.iter().zip(&self.hashes).enumerate() {
+            core.run_frame(KeypadState(keypad));
+
+            let actual = core.framebuffer_hash();
+            if actual != expected {
+                return Err(MovieError::Divergence { frame, expected, actual });
+            }
+        }
+        Ok(self.inputs.len())
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let header = &self.header;
+        let version = header.core_version.as_bytes();
+
+        let mut data = Vec::with_capacity(45 + version.len() + 10 * self.inputs.len());
+        data.extend_from_slice(Self::MAGIC);
+        data.push(Self::VERSION);
+        data.push(version.len() as u8);
+        data.extend_from_slice(version);
+        data.extend_from_slice(&header.rom_hash);
+        data.extend_from_slice(&header.seed.to_le_bytes());
+        data.push(header.platform.id());
+        data.push(header.quirks.to_bits());
+        data.extend_from_slice(&header.instructions_per_frame.to_le_bytes());
+        data.extend_from_slice(&(self.inputs.len() as u32).to_le_bytes());
+
+        for keypad in &self.inputs {
+            data.extend_from_slice(&keypad.to_le_bytes());
+        }
+
+        // Incomplete hashes could not be verified, so they are left out
+        let hashes = !self.inputs.is_empty() && self.has_hashes();
+        data.push(hashes as u8);
+        if hashes {
+            for hash in &self.hashes {
+                data.extend_from_slice(&hash.to_le_bytes());
+            }
+        }
+
+        data
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, MovieError> {
+        let mut reader = Reader { data };
+
+        if reader.take(4)? != Self::MAGIC {
+            return Err(MovieError::InvalidFormat);
+        }
+
+        let version = reader.take(1)?[0];
+        if version != 1 && version != Self::VERSION {
+            return Err(MovieError::UnsupportedVersion(version));
+        }
+
+        let version_len = reader.take(1)?[0] as usize;
+        let core_version = String::from_utf8(reader.take(version_len)?.to_vec())
+            .map_err(|_| MovieError::InvalidFormat)?;
+        let rom_hash = reader.take(20)?.try_into().unwrap();
+        let seed = u64::from_le_bytes(reader.take(8)?.try_into().unwrap());
+        let platform = Platform::from_id(reader.take(1)?[0]).ok_or(MovieError::InvalidFormat)?;
+        let quirks = Quirks::from_bits(reader.take(1)?[0]);
+        let instructions_per_frame = u32::from_le_bytes(reader.take(4)?.try_into().unwrap());
+        let frames = u32::from_le_bytes(reader.take(4)?.try_into().unwrap()) as usize;
+
+        let inputs = reader.take(2 * frames)?
+            .chunks_exact(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .collect();
+
+        let hashes = if version >= 2 && reader.take(1)?[0] != 0 {
+            reader.take(8 * frames)?
+                .chunks_exact(8)
+                .map(|c| u64::from_le_bytes(c.try_into().unwrap()))
+                .collect()
+        }
+        else {
+            Vec::new()
+        };
+
+        Ok(Self {
+            header: MovieHeader { core_version, rom_hash, seed, platform, quirks, instructions_per_frame },
+            inputs,
+            hashes,
+        })
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], MovieError> {
+        if self.data.len() < n {
+            return Err(MovieError::InvalidFormat);
+        }
+
+        let (head, tail) = self.data.split_at(n);
+        self.data = tail;
+        Ok(head)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn roundtrip() {
+        let mut core = Chip8Core::new();
+        core.load_program(&[0x12, 0x00]);
+
+        let mut movie = Movie::record(&core);
+        movie.record_frame(0x0000);
+        movie.record_frame(0x8001);
+        assert_eq!(Movie::from_bytes(&movie.to_bytes()), Ok(movie.clone()));
+
+        movie.record_hash(1);
+        movie.record_hash(u64::MAX);
+        assert_eq!(Movie::from_bytes(&movie.to_bytes()), Ok(movie.clone()));
+
+        // Version 1 files have no hashes
+        let mut data = movie.to_bytes();
+        data[4] = 1;
+        data.truncate(data.len() - 17);
+        movie.hashes.clear();
+        assert_eq!(Movie::from_bytes(&data), Ok(movie));
+    }
+
+    #[test]
+    fn verify_playback() {
+        let mut core = Chip8Core::new();
+
+        // KEY V0, FONT V0, CLS, DRAW V1 V1 5, JMP 0x200
+        core.load_program(&[0xF0, 0x0A, 0xF0, 0x29, 0x00, 0xE0, 0xD1, 0x15, 0x12, 0x00]);
+        let mut movie = Movie::record(&core);
+        assert_eq!(movie.verify_playback(&mut core), Ok(0));
+
+        movie.start_playback(&mut core).unwrap();
+        for frame in 0..10 {
+            let keypad = if frame % 4 == 1 { 1 << frame } else { 0 };
+            movie.record_frame(keypad);
+            core.run_frame(KeypadState(keypad));
+            movie.record_hash(core.framebuffer_hash());
+        }
+        assert_eq!(movie.verify_playback(&mut core), Ok(10));
+
+        // A frame time budget would make playback depend on the speed of the host
+        core.set_frame_budget(Some(Duration::ZERO));
+        assert_eq!(movie.verify_playback(&mut core), Ok(10));
+        assert_eq!(core.frame_budget(), None);
+
+        // A different key drawn at frame 5
+        movie.inputs[5] = 1 << 4;
+        assert!(matches!(movie.verify_playback(&mut core), Err(MovieError::Divergence { frame: 5, .. })));
+
+        movie.hashes.pop();
+        assert_eq!(movie.verify_playback(&mut core), Err(MovieError::MissingHashes));
+    }
+
+    #[test]
+    fn seek() {
+        let mut core = Chip8Core::new();
+
+        // MOV V1 0x05, SKNP V1, RAND V0 0xFF, JMP 0x202
+        core.load_program(&[0x61, 0x05, 0xE1, 0xA1, 0xC0, 0xFF, 0x12, 0x02]);
+        let mut movie = Movie::record(&core);
+        movie.start_playback(&mut core).unwrap();
+
+        let mut states = Vec::new();
+        for frame in 0..8 {
+            states.push(core.dump_memory());
+            let keypad = if frame % 3 == 0 { 1 << 5 } else { 0 };
+            movie.record_frame(keypad);
+            core.run_frame(KeypadState(keypad));
+        }
+
+        for frame in [5, 2, 0] {
+            movie.seek(&mut core, frame).unwrap();
+            assert_eq!(core.dump_memory(), states[frame]);
+        }
+        assert_eq!(movie.seek(&mut core, 9), Err(MovieError::FrameOutOfRange { frame: 9, frames: 8 }));
+    }
+
+    #[test]
+    fn verify_mismatch() {
+        let mut core = Chip8Core::new();
+        core.load_program(&[0x12, 0x00]);
+        let movie = Movie::record(&core);
+
+        core.load_program(&[0x12, 0x02]);
+        assert_eq!(movie.start_playback(&mut core), Err(MovieError::RomMismatch));
+
+        core.load_program(&[0x12, 0x00]);
+        core.set_instructions_per_frame(20);
+        assert!(matches!(movie.start_playback(&mut core), Err(MovieError::SpeedMismatch { .. })));
+
+        core.set_instructions_per_frame(movie.header.instructions_per_frame as usize);
+        core.set_seed(0);
+        assert_eq!(movie.start_playback(&mut core), Ok(()));
+        assert_eq!(core.seed(), movie.header.seed);
+    }
+}
@@ -0,0 +1,699 @@
+use std::path::{Path, PathBuf};
+
+use libretro_rs::{RetroDevicePort, RetroEnvironment, RetroJoypadButton, RetroRuntime, RetroVariable};
+
+use crate::cpu::MemoryLayout;
+use crate::cpu::font::FontSet;
+use crate::i18n::{self, Language};
+use crate::input::{self, InputScript, KeyMap};
+use crate::overrides::GameOverrides;
+use crate::palette::Palette;
+use crate::platform::{Platform, Quirks};
+use crate::video::{AspectRatio, Rotation, VideoFilter};
+
+/// RetroPad button combination bound to a core-level action.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ButtonCombo {
+    Disabled,
+    L1,
+    R1,
+    SelectStart,
+    L1R1,
+    L3R3,
+}
+
+impl ButtonCombo {
+    fn parse(value: &str) -> Self {
+        match value {
+            "l" => Self::L1,
+            "r" => Self::R1,
+            "select+start" => Self::SelectStart,
+            "l+r" => Self::L1R1,
+            "l3+r3" => Self::L3R3,
+            _ => Self::Disabled,
+        }
+    }
+
+    fn buttons(&self) -> &'static [RetroJoypadButton] {
+        match self {
+            Self::Disabled => &[],
+            Self::L1 => &[RetroJoypadButton::L1],
+            Self::R1 => &[RetroJoypadButton::R1],
+            Self::SelectStart => &[RetroJoypadButton::Select, RetroJoypadButton::Start],
+            Self::L1R1 => &[RetroJoypadButton::L1, RetroJoypadButton::R1],
+            Self::L3R3 => &[RetroJoypadButton::L3, RetroJoypadButton::R3],
+        }
+    }
+
+    /// Whether all buttons of the combination are currently held down.
+    pub fn is_pressed(&self, runtime: &RetroRuntime, port: RetroDevicePort) -> bool {
+        let buttons = self.buttons();
+        !buttons.is_empty() && buttons.iter().all(|&b| runtime.is_joypad_button_pressed(port, b))
+    }
+}
+
+/// Action taken when a program executes the `EXIT` instruction.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExitAction {
+    /// Stop execution, keeping the last frame on screen.
+    #[default]
+    Halt,
+    /// Stop execution and request the frontend to unload the content.
+    Shutdown,
+}
+
+/// Handling of writes by `FX33` and `FX55` to the fonts or the loaded program, which
+/// usually indicate that `I` was set incorrectly.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MemoryProtection {
+    /// Allow all writes.
+    #[default]
+    Disabled,
+    /// Allow the write, logging the address of the instruction.
+    Log,
+    /// Block the write and halt execution.
+    Fault,
+}
+
+impl MemoryProtection {
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "disabled" => Some(Self::Disabled),
+            "log" => Some(Self::Log),
+            "fault" => Some(Self::Fault),
+            _ => None,
+        }
+    }
+}
+
+/// Emulator configuration, obtained from the command line and the frontend's core options.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CoreOptions {
+    pub instructions_per_frame: usize,
+    /// Emulation speed in percent, scaling both the instructions run per frame and the
+    /// timer tick rate, from `MIN_SPEED_PERCENT` to `MAX_SPEED_PERCENT`.
+    pub speed_percent: usize,
+    /// Speed in percent while the frontend is fast-forwarding, or 0 to keep the normal
+    /// speed (the frontend then only runs frames faster).
+    pub fast_forward_percent: usize,
+    /// Mute audio while the frontend is fast-forwarding.
+    pub fast_forward_mute: bool,
+    /// Number of parts each frame of audio is split into, each following the sound timer
+    /// after its share of the frame's instructions (see `Chip8Core::set_audio_chunks`).
+    pub audio_chunks: usize,
+    /// Time the instructions of a frame may take in milliseconds before the rest are
+    /// skipped (0 for unlimited), see `Chip8Core::set_frame_budget`.
+    pub frame_budget_ms: u64,
+    /// Maximum number of sprites drawn per frame (0 for unlimited). Execution stalls
+    /// until the next frame once the limit is reached, mimicking heavy-load slowdown.
+    pub draw_limit: usize,
+    pub platform: Platform,
+    /// The platform was not chosen explicitly, so it may be selected automatically.
+    pub platform_auto: bool,
+    /// Quirks enabled in addition to the platform defaults.
+    pub quirks: Quirks,
+    /// Integer upscaling factor of the video output.
+    pub scale: usize,
+    pub scanlines: bool,
+    /// Clockwise rotation of the video output.
+    pub rotation: Rotation,
+    pub aspect: AspectRatio,
+    pub palette: Palette,
+    /// Fade between palettes when the palette changes, instead of switching at once.
+    pub palette_fade: bool,
+    /// Load the palette from `Palette::FILE_NAME` in the frontend's system directory.
+    pub palette_file: bool,
+    pub exit_action: ExitAction,
+    pub memory_protection: MemoryProtection,
+    /// `FX0A` only accepts keys pressed after the instruction started waiting.
+    pub key_edge: bool,
+    /// Key stored immediately by `FX0A` instead of waiting for input, for automated runs.
+    pub auto_key: Option<u8>,
+    /// Key presses injected after boot (see `InputScript::parse`).
+    pub input_script: InputScript,
+    /// Keyboard key bound to each CHIP-8 key.
+    pub key_map: KeyMap,
+    /// Log instructions that are not supported when they are first executed.
+    pub opcode_warnings: bool,
+    /// Poll input a second time halfway through each frame, reducing input latency.
+    pub mid_frame_poll: bool,
+    /// Pause emulation while the frontend does not display video.
+    pub pause_hidden: bool,
+    /// Log reads of memory that was never written and holds neither a font nor the program.
+    pub uninit_warnings: bool,
+    /// Log subroutines with more calls active at once than this, and stacks that keep
+    /// growing.
+    pub max_recursion: Option<usize>,
+    /// Font set and addresses overriding the platform defaults.
+    pub font: Option<FontSet>,
+    pub font_addr: Option<u16>,
+    pub large_font_addr: Option<u16>,
+    /// Whether the large font covers digits `A` to `F`, overriding the platform default.
+    pub large_font_hex: Option<bool>,
+    pub fill_interpreter_area: bool,
+    /// Address programs are loaded at, overriding the platform default.
+    pub start_addr: Option<u16>,
+    /// Load only the part of programs too large for memory that fits, instead of
+    /// rejecting them.
+    pub truncate: bool,
+    /// Load files that do not look like CHIP-8 programs (see `validate::validate`).
+    pub permissive: bool,
+    /// File to which a line with the frame number, screen hash and register hash is
+    /// appended every frame, for finding the frame where two versions diverge.
+    pub trace_path: Option<PathBuf>,
+    /// Language of the core option labels.
+    pub language: Language,
+    pub reset_combo: ButtonCombo,
+    pub reset_apply_combo: ButtonCombo,
+    pub palette_combo: ButtonCombo,
+    pub speed_combo: ButtonCombo,
+}
+
+impl CoreOptions {
+    const INSTRUCTIONS_PER_FRAME: usize = 10;
+    pub const MIN_SPEED_PERCENT: usize = 25;
+    pub const MAX_SPEED_PERCENT: usize = 800;
+    /// Speeds selected in turn by the speed hotkey, in percent.
+    pub const SPEED_PRESETS: [usize; 6] = [25, 50, 100, 200, 400, 800];
+
+    /// Core option keys and descriptions registered with the frontend. The first value
+    /// of each option is its default.
+    pub const DEFINITIONS: [(&'static str, &'static str); 37] = [
+        ("oxid8_platform", "Platform; default|chip8|schip|schip-legacy|eti660"),
+        ("oxid8_ipf", "Instructions per frame; default|5|10|15|20|30|50|100|200"),
+        ("oxid8_speed", "Emulation speed; 1x|0.25x|0.5x|2x|4x|8x"),
+        ("oxid8_fast_forward_speed", "Emulation speed when fast-forwarding; disabled|2x|4x|8x"),
+        ("oxid8_fast_forward_mute", "Mute when fast-forwarding; disabled|enabled"),
+        ("oxid8_audio_chunks", "Audio chunks per frame (lower beep latency); 1|2|4|8"),
+        ("oxid8_frame_budget", "Frame time limit (watchdog); unlimited|25ms|50ms|100ms|200ms"),
+        ("oxid8_draw_limit", "Sprite draws per frame; default|unlimited|1|2|4|8|16"),
+        ("oxid8_quirk_memory", "Quirk: memory; disabled|enabled"),
+        ("oxid8_quirk_shift", "Quirk: shift; disabled|enabled"),
+        ("oxid8_quirk_collision", "Quirk: collision; disabled|enabled"),
+        ("oxid8_quirk_resolution", "Quirk: resolution; disabled|enabled"),
+        ("oxid8_quirk_lores16", "Quirk: lores16; disabled|enabled"),
+        ("oxid8_quirk_scroll", "Quirk: scroll; disabled|enabled"),
+        ("oxid8_quirk_lores_tall", "Quirk: lores tall sprites; disabled|enabled"),
+        ("oxid8_quirk_key_release", "Quirk: key wait on release; disabled|enabled"),
+        ("oxid8_scale", "Integer upscaling (restart required); default|1x|2x|3x"),
+        ("oxid8_scanlines", "Scanlines (with upscaling); disabled|enabled"),
+        ("oxid8_rotation", "Screen rotation, clockwise (restart required); 0|90|180|270"),
+        ("oxid8_aspect", "Aspect ratio (restart required); square|stretch|4:3|16:9|3:2"),
+        ("oxid8_palette", "Palette; default|lcd|octo|mono|file"),
+        ("oxid8_palette_fade", "Fade between palettes; disabled|enabled"),
+        ("oxid8_exit_action", "EXIT instruction; default|halt|shutdown"),
+        ("oxid8_memory_protection", "Font and program write protection; default|disabled|log|fault"),
+        ("oxid8_key_edge", "Key wait requires fresh press; disabled|enabled"),
+        ("oxid8_auto_key", "Key wait auto-press; disabled|0|1|2|3|4|5|6|7|8|9|A|B|C|D|E|F"),
+        ("oxid8_opcode_warnings", "Log unsupported instructions; disabled|enabled"),
+        ("oxid8_font", "Font (restart required); default|schip|vip|octo|dream6800"),
+        ("oxid8_large_font", "Large font digits (restart required); default|hex|decimal"),
+        ("oxid8_font_addr", "Font address (restart required); default|0x000|0x050"),
+        ("oxid8_fill_interpreter_area", "Fill interpreter area (restart required); disabled|enabled"),
+        ("oxid8_truncate", "Load programs too large for memory truncated (restart required); disabled|enabled"),
+        ("oxid8_validation", "Content validation (restart required); strict|permissive"),
+        ("oxid8_reset_combo", "Soft reset combo; disabled|select+start|l+r|l3+r3"),
+        ("oxid8_reset_apply_combo", "Reset and apply options combo; disabled|select+start|l+r|l3+r3"),
+        ("oxid8_palette_combo", "Next palette hotkey; disabled|l|r|select+start|l+r|l3+r3"),
+        ("oxid8_speed_combo", "Next speed hotkey; disabled|l|r|select+start|l+r|l3+r3"),
+    ];
+
+    /// Core option definitions in the form expected by the frontend.
+    pub fn variables() -> Vec<RetroVariable> {
+        Self::localized_variables(Language::English)
+    }
+
+    /// Core option definitions with their labels in a language, falling back to English
+    /// for options that are not translated.
+    pub fn localized_variables(language: Language) -> Vec<RetroVariable> {
+        Self::DEFINITIONS.iter()
+            .map(|&(key, value)| RetroVariable::new(key, i18n::translation(language, key).unwrap_or(value)))
+            .collect()
+    }
+
+    /// Keys of all core options.
+    pub fn keys() -> impl Iterator<Item = &'static str> {
+        Self::DEFINITIONS.iter().map(|&(key, _)| key)
+    }
+
+    /// Parse options from command-line arguments (e.g. `ipf=20`, `platform=schip` or
+    /// `quirk-memory`), as described in the README's Execution section.
+    pub fn from_args(args: &[String]) -> Self {
+        let flag = |name: &str| args.iter().any(|s| s == name);
+
+        let instructions_per_frame = args.iter()
+            .find_map(|s| s.strip_prefix("ipf="))
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(Self::INSTRUCTIONS_PER_FRAME);
+
+        let speed_percent = args.iter()
+            .find_map(|s| s.strip_prefix("speed="))
+            .and_then(parse_speed)
+            .unwrap_or(100);
+
+        let draw_limit = args.iter()
+            .find_map(|s| s.strip_prefix("draw-limit="))
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(0);
+
+        let audio_chunks = args.iter()
+            .find_map(|s| s.strip_prefix("audio-chunks="))
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(1);
+
+        let frame_budget_ms = args.iter()
+            .find_map(|s| s.strip_prefix("frame-budget="))
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(0);
+
+        let platform = args.iter()
+            .find_map(|s| s.strip_prefix("platform="))
+            .and_then(Platform::from_name);
+
+        let scale = args.iter()
+            .find_map(|s| s.strip_prefix("scale="))
+            .and_then(|v| v.parse().ok())
+            .map_or(1, |v: usize| v.clamp(1, VideoFilter::MAX_SCALE));
+
+        let rotation = args.iter()
+            .find_map(|s| s.strip_prefix("rotate="))
+            .and_then(|v| v.parse().ok())
+            .and_then(Rotation::from_degrees)
+            .unwrap_or_default();
+
+        let aspect = args.iter()
+            .find_map(|s| s.strip_prefix("aspect="))
+            .and_then(AspectRatio::parse)
+            .unwrap_or_default();
+
+        // A palette is either a preset or the path of a palette file
+        let palette = args.iter()
+            .find_map(|s| s.strip_prefix("palette="))
+            .and_then(|v| Palette::from_name(v).or_else(|| Palette::load(Path::new(v))))
+            .unwrap_or_default();
+
+        // Keyboard keys bound to CHIP-8 keys, written as `key-<hex digit>=<key names>` with
+        // alternate keys separated by commas
+        let mut key_map = KeyMap::default();
+        for (key, names) in args.iter().filter_map(|s| s.strip_prefix("key-")?.split_once('=')) {
+            let Some(key) = parse_key(key) else { continue };
+            for keycode in names.split(',').filter_map(input::keycode) {
+                // Options are parsed before there is a core (and trace) to log to
+                if !key_map.bind(key, keycode) {
+                    eprintln!("oxid-8: at most {} keyboard keys can be bound to CHIP-8 key {:X}", KeyMap::MAX_BINDINGS, key);
+                }
+            }
+        }
+
+        let fast_forward_percent = args.iter()
+            .find_map(|s| s.strip_prefix("ff-speed="))
+            .and_then(parse_speed)
+            .unwrap_or(0);
+
+        Self {
+            instructions_per_frame,
+            speed_percent,
+            fast_forward_percent,
+            fast_forward_mute: flag("ff-mute"),
+            audio_chunks,
+            frame_budget_ms,
+            draw_limit,
+            platform: platform.unwrap_or_default(),
+            platform_auto: platform.is_none(),
+            quirks: Quirks {
+                memory: flag("quirk-memory"),
+                shift: flag("quirk-shift"),
+                collision: flag("quirk-collision"),
+                resolution: flag("quirk-resolution"),
+                lores16: flag("quirk-lores16"),
+                scroll: flag("quirk-scroll"),
+                lores_tall: flag("quirk-lores-tall"),
+                key_release: flag("quirk-key-release"),
+            },
+            scale,
+            scanlines: flag("scanlines"),
+            rotation,
+            aspect,
+            palette_fade: flag("palette-fade"),
+            palette,
+            palette_file: false,
+            exit_action: if flag("exit=shutdown") { ExitAction::Shutdown } else { ExitAction::Halt },
+            memory_protection: args.iter()
+                .find_map(|s| s.strip_prefix("protect="))
+                .and_then(MemoryProtection::parse)
+                .unwrap_or_default(),
+            key_edge: flag("key-edge"),
+            auto_key: args.iter().find_map(|s| s.strip_prefix("auto-key=")).and_then(parse_key),
+            input_script: args.iter()
+                .find_map(|s| s.strip_prefix("input="))
+                .and_then(InputScript::parse)
+                .unwrap_or_default(),
+            key_map,
+            opcode_warnings: flag("warn-opcodes"),
+            uninit_warnings: flag("warn-uninit"),
+            max_recursion: args.iter().find_map(|s| s.strip_prefix("warn-recursion=")).and_then(|v| v.parse().ok()),
+            mid_frame_poll: flag("mid-frame-poll"),
+            pause_hidden: flag("pause-hidden"),
+            font: args.iter().find_map(|s| s.strip_prefix("font=")).and_then(FontSet::from_name),
+            large_font_hex: args.iter().find_map(|s| s.strip_prefix("large-font=")).and_then(parse_large_font),
+            font_addr: args.iter().find_map(|s| s.strip_prefix("font-addr=")).and_then(parse_addr),
+            large_font_addr: args.iter().find_map(|s| s.strip_prefix("large-font-addr=")).and_then(parse_addr),
+            fill_interpreter_area: flag("fill-interpreter-area"),
+            start_addr: args.iter().find_map(|s| s.strip_prefix("start-addr=")).and_then(parse_addr),
+            truncate: flag("truncate"),
+            permissive: flag("permissive"),
+            trace_path: args.iter().find_map(|s| s.strip_prefix("trace=")).map(PathBuf::from),
+            language: args.iter().find_map(|s| s.strip_prefix("lang=")).and_then(Language::from_name).unwrap_or_default(),
+            reset_combo: ButtonCombo::Disabled,
+            reset_apply_combo: ButtonCombo::Disabled,
+            palette_combo: ButtonCombo::Disabled,
+            speed_combo: ButtonCombo::Disabled,
+        }
+    }
+
+    /// Split the meta string of a game, given by the frontend for example from a playlist
+    /// entry, into arguments for `from_args`. Arguments are separated by `;`, e.g.
+    /// `platform=schip;speed=2x;palette=octo`.
+    pub fn meta_args(meta: &str) -> Vec<String> {
+        meta.split(';').map(str::trim).filter(|s| !s.is_empty()).map(str::to_string).collect()
+    }
+
+    /// Short description of the platform and speed in effect, along with where the
+    /// platform was chosen from, shown when a game starts.
+    pub fn summary(&self, platform_source: &str) -> String {
+        format!("Platform {} ({}), {} instructions per frame at {}x speed",
+            self.platform.name(), platform_source, self.instructions_per_frame, self.speed_percent as f64 / 100.0)
+    }
+
+    /// Override options with the values of the frontend's core options, or the per-game
+    /// overrides where present. Quirks enabled from the command line remain enabled.
+    pub fn apply_variables(&mut self, env: &mut RetroEnvironment, overrides: &GameOverrides) {
+        self.apply_values(|key| overrides.get(key).map(str::to_string)
+            .or_else(|| env.get_variable(key).map(str::to_string)));
+
+        if self.palette_file {
+            if let Some(palette) = env.get_system_directory().and_then(|dir| Palette::load(&Path::new(dir).join(Palette::FILE_NAME))) {
+                self.palette = palette;
+            }
+        }
+    }
+
+    /// Override options with core option values obtained from `lookup`.
+    pub fn apply_values(&mut self, mut lookup: impl FnMut(&str) -> Option<String>) {
+        if let Some(platform) = lookup("oxid8_platform").as_deref().and_then(Platform::from_name) {
+            self.platform = platform;
+            self.platform_auto = false;
+        }
+
+        if let Some(ipf) = lookup("oxid8_ipf").and_then(|v| v.parse().ok()) {
+            self.instructions_per_frame = ipf;
+        }
+
+        if let Some(speed) = lookup("oxid8_speed").as_deref().and_then(parse_speed) {
+            self.speed_percent = speed;
+        }
+
+        match lookup("oxid8_fast_forward_speed").as_deref() {
+            Some("disabled") => self.fast_forward_percent = 0,
+            Some(v) => if let Some(speed) = parse_speed(v) { self.fast_forward_percent = speed },
+            None => {},
+        }
+
+        if let Some(chunks) = lookup("oxid8_audio_chunks").and_then(|v| v.parse().ok()) {
+            self.audio_chunks = chunks;
+        }
+
+        match lookup("oxid8_frame_budget").as_deref() {
+            Some("unlimited") => self.frame_budget_ms = 0,
+            Some(v) => if let Some(ms) = v.strip_suffix("ms").and_then(|v| v.parse().ok()) { self.frame_budget_ms = ms },
+            None => {},
+        }
+
+        match lookup("oxid8_draw_limit").as_deref() {
+            Some("unlimited") => self.draw_limit = 0,
+            Some(v) => if let Ok(limit) = v.parse() { self.draw_limit = limit },
+            None => {},
+        }
+
+        match lookup("oxid8_palette").as_deref() {
+            Some("file") => self.palette_file = true,
+            Some(name) => if let Some(palette) = Palette::from_name(name) { self.palette = palette },
+            None => {},
+        }
+
+        match lookup("oxid8_exit_action").as_deref() {
+            Some("halt") => self.exit_action = ExitAction::Halt,
+            Some("shutdown") => self.exit_action = ExitAction::Shutdown,
+            _ => {},
+        }
+
+        match lookup("oxid8_auto_key").as_deref() {
+            Some("disabled") => self.auto_key = None,
+            Some(v) => if let Some(key) = parse_key(v) { self.auto_key = Some(key) },
+            None => {},
+        }
+
+        if let Some(protection) = lookup("oxid8_memory_protection").as_deref().and_then(MemoryProtection::parse) {
+            self.memory_protection = protection;
+        }
+
+        if let Some(scale) = lookup("oxid8_scale").and_then(|v| v.trim_end_matches('x').parse().ok()) {
+            self.scale = usize::clamp(scale, 1, VideoFilter::MAX_SCALE);
+        }
+
+        if let Some(rotation) = lookup("oxid8_rotation").and_then(|v| v.parse().ok()).and_then(Rotation::from_degrees) {
+            self.rotation = rotation;
+        }
+
+        if let Some(aspect) = lookup("oxid8_aspect").as_deref().and_then(AspectRatio::parse) {
+            self.aspect = aspect;
+        }
+
+        if let Some(font) = lookup("oxid8_font").as_deref().and_then(FontSet::from_name) {
+            self.font = Some(font);
+        }
+
+        if let Some(hex) = lookup("oxid8_large_font").as_deref().and_then(parse_large_font) {
+            self.large_font_hex = Some(hex);
+        }
+
+        if let Some(addr) = lookup("oxid8_font_addr").as_deref().and_then(parse_addr) {
+            self.font_addr = Some(addr);
+        }
+
+        self.reset_combo = lookup("oxid8_reset_combo").map_or(ButtonCombo::Disabled, |v| ButtonCombo::parse(&v));
+        self.reset_apply_combo = lookup("oxid8_reset_apply_combo").map_or(ButtonCombo::Disabled, |v| ButtonCombo::parse(&v));
+        self.palette_combo = lookup("oxid8_palette_combo").map_or(ButtonCombo::Disabled, |v| ButtonCombo::parse(&v));
+        self.speed_combo = lookup("oxid8_speed_combo").map_or(ButtonCombo::Disabled, |v| ButtonCombo::parse(&v));
+        self.permissive |= lookup("oxid8_validation").as_deref() == Some("permissive");
+
+        let mut enabled = |key: &str| lookup(key).as_deref() == Some("enabled");
+        self.scanlines |= enabled("oxid8_scanlines");
+        self.palette_fade |= enabled("oxid8_palette_fade");
+        self.key_edge |= enabled("oxid8_key_edge");
+        self.fast_forward_mute |= enabled("oxid8_fast_forward_mute");
+        self.opcode_warnings |= enabled("oxid8_opcode_warnings");
+        self.fill_interpreter_area |= enabled("oxid8_fill_interpreter_area");
+        self.truncate |= enabled("oxid8_truncate");
+        self.quirks.memory |= enabled("oxid8_quirk_memory");
+        self.quirks.shift |= enabled("oxid8_quirk_shift");
+        self.quirks.collision |= enabled("oxid8_quirk_collision");
+        self.quirks.resolution |= enabled("oxid8_quirk_resolution");
+        self.quirks.lores16 |= enabled("oxid8_quirk_lores16");
+        self.quirks.scroll |= enabled("oxid8_quirk_scroll");
+        self.quirks.lores_tall |= enabled("oxid8_quirk_lores_tall");
+        self.quirks.key_release |= enabled("oxid8_quirk_key_release");
+    }
+
+    /// Quirks in effect: the platform defaults plus any explicitly enabled quirks.
+    pub fn effective_quirks(&self) -> Quirks {
+        self.platform.quirks().union(self.quirks)
+    }
+
+    /// Fonts in effect: the platform defaults, with any explicitly chosen font set and
+    /// addresses. If only the small font is moved and the fonts would overlap, the large
+    /// font follows it. Falls back to the platform defaults if the fonts would not fit.
+    pub fn memory_layout(&self) -> MemoryLayout {
+        let default = self.platform.memory_layout();
+        let mut layout = MemoryLayout {
+            program_addr: self.start_addr.unwrap_or(default.program_addr),
+            font: self.font.unwrap_or(default.font),
+            font_addr: self.font_addr.unwrap_or(default.font_addr),
+            large_font_addr: self.large_font_addr.unwrap_or(default.large_font_addr),
+            large_font_hex: self.large_font_hex.unwrap_or(default.large_font_hex),
+            fill_interpreter_area: self.fill_interpreter_area || default.fill_interpreter_area,
+        };
+
+        if !layout.is_valid() && self.large_font_addr.is_none() {
+            layout.large_font_addr = layout.font_addr.saturating_add(MemoryLayout::FONT_SIZE as u16);
+        }
+
+        if layout.is_valid() {
+            layout
+        }
+        else {
+            MemoryLayout {
+                font: layout.font,
+                large_font_hex: layout.large_font_hex,
+                fill_interpreter_area: layout.fill_interpreter_area,
+                ..default
+            }
+        }
+    }
+
+    /// Update only the button combinations, which take effect immediately.
+    pub fn apply_combos(&mut self, env: &mut RetroEnvironment) {
+        self.reset_combo = env.get_variable("oxid8_reset_combo").map_or(ButtonCombo::Disabled, ButtonCombo::parse);
+        self.reset_apply_combo = env.get_variable("oxid8_reset_apply_combo").map_or(ButtonCombo::Disabled, ButtonCombo::parse);
+        self.palette_combo = env.get_variable("oxid8_palette_combo").map_or(ButtonCombo::Disabled, ButtonCombo::parse);
+        self.speed_combo = env.get_variable("oxid8_speed_combo").map_or(ButtonCombo::Disabled, ButtonCombo::parse);
+    }
+
+    /// Speed preset following `percent`, wrapping around to the slowest.
+    pub fn next_speed_preset(percent: usize) -> usize {
+        Self::SPEED_PRESETS.into_iter().find(|&p| p > percent).unwrap_or(Self::SPEED_PRESETS[0])
+    }
+}
+
+/// Parse an address in hexadecimal (`0x` prefix) or decimal.
+fn parse_addr(value: &str) -> Option<u16> {
+    match value.strip_prefix("0x") {
+        Some(hex) => u16::from_str_radix(hex, 16).ok(),
+        None => value.parse().ok(),
+    }
+}
+
+/// Parse a speed multiplier (e.g. `0.5x` or `2`) into a percentage, clamped to the
+/// supported range.
+fn parse_speed(value: &str) -> Option<usize> {
+    let multiplier: f64 = value.trim_end_matches('x').parse().ok().filter(|v: &f64| v.is_finite())?;
+    let percent = (multiplier * 100.0).round().max(0.0) as usize;
+    Some(percent.clamp(CoreOptions::MIN_SPEED_PERCENT, CoreOptions::MAX_SPEED_PERCENT))
+}
+
+/// Parse a key as a single hexadecimal digit.
+fn parse_key(value: &str) -> Option<u8> {
+    if value.len() == 1 { u8::from_str_radix(value, 16).ok() } else { None }
+}
+
+/// Parse whether the large font covers hexadecimal (`hex`) or only decimal digits.
+fn parse_large_font(value: &str) -> Option<bool> {
+    match value {
+        "hex" => Some(true),
+        "decimal" => Some(false),
+        _ => None,
+    }
+}
+
+impl Default for CoreOptions {
+    fn default() -> Self {
+        Self::from_args(&[])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_args() {
+        let args: Vec<String> = ["retroarch", "rom.ch8", "quirk-shift", "ipf=20", "platform=schip"]
+            .iter().map(|s| s.to_string()).collect();
+
+        let options = CoreOptions::from_args(&args);
+
+        assert_eq!(options.instructions_per_frame, 20);
+        assert_eq!(options.platform, Platform::SuperChip);
+        assert!(!options.platform_auto);
+        assert!(options.quirks.shift);
+        assert!(!options.quirks.memory);
+        assert!(options.effective_quirks().memory);
+        assert!(!options.key_map.is_custom());
+
+        let args: Vec<String> = ["key-5=Up", "key-a=kp0,a", "key-edge", "key-G=w"].iter().map(|s| s.to_string()).collect();
+        let key_map = CoreOptions::from_args(&args).key_map;
+        assert_eq!(key_map.keycodes(0x5).collect::<Vec<_>>(), [273]);
+        assert_eq!(key_map.keycodes(0xA).collect::<Vec<_>>(), [256, 97]);
+        assert_eq!(key_map.keycodes(0x6).collect::<Vec<_>>(), [101]);
+    }
+
+    #[test]
+    fn summary() {
+        let options = CoreOptions::from_args(&["platform=schip".to_string(), "speed=0.5x".to_string()]);
+        assert_eq!(options.summary("user"), "Platform schip (user), 10 instructions per frame at 0.5x speed");
+    }
+
+    #[test]
+    fn meta_args() {
+        let mut args = CoreOptions::meta_args("platform=schip; speed=2x;;quirk-scroll");
+        assert_eq!(args, ["platform=schip", "speed=2x", "quirk-scroll"]);
+
+        // Meta arguments come first, taking precedence over the command line
+        args.push("platform=chip8".to_string());
+        let options = CoreOptions::from_args(&args);
+        assert_eq!(options.platform, Platform::SuperChip);
+        assert_eq!(options.speed_percent, 200);
+        assert!(options.quirks.scroll);
+    }
+
+    #[test]
+    fn apply_values() {
+        let mut overrides = GameOverrides::default();
+        overrides.set("oxid8_ipf", "30");
+        overrides.set("oxid8_quirk_scroll", "enabled");
+
+        let mut options = CoreOptions::default();
+        options.apply_values(|key| overrides.get(key).map(str::to_string));
+
+        assert_eq!(options.instructions_per_frame, 30);
+        assert!(options.quirks.scroll);
+        assert!(!options.quirks.shift);
+    }
+
+    #[test]
+    fn memory_layout() {
+        let args: Vec<String> = ["font-addr=0x50", "large-font-addr=160"].iter().map(|s| s.to_string()).collect();
+        let layout = CoreOptions::from_args(&args).memory_layout();
+
+        assert_eq!((layout.font_addr, layout.large_font_addr), (0x50, 0xA0));
+
+        let args: Vec<String> = ["font-addr=0x50"].iter().map(|s| s.to_string()).collect();
+        assert_eq!(CoreOptions::from_args(&args).memory_layout().large_font_addr, 0xA0);
+
+        // Overlapping fonts
+        let args: Vec<String> = ["font-addr=0x90", "large-font-addr=0x80"].iter().map(|s| s.to_string()).collect();
+        assert_eq!(CoreOptions::from_args(&args).memory_layout(), MemoryLayout::default());
+
+        let args: Vec<String> = ["platform=eti660"].iter().map(|s| s.to_string()).collect();
+        assert_eq!(CoreOptions::from_args(&args).memory_layout().program_addr, 0x600);
+
+        // Programs cannot start below the fonts
+        let args: Vec<String> = ["start-addr=0x300"].iter().map(|s| s.to_string()).collect();
+        assert_eq!(CoreOptions::from_args(&args).memory_layout().program_addr, 0x300);
+        let args: Vec<String> = ["start-addr=0x40"].iter().map(|s| s.to_string()).collect();
+        assert_eq!(CoreOptions::from_args(&args).memory_layout(), MemoryLayout::default());
+    }
+
+    #[test]
+    fn speed_parse() {
+        assert_eq!(parse_speed("0.25x"), Some(25));
+        assert_eq!(parse_speed("2"), Some(200));
+        assert_eq!(parse_speed("100x"), Some(CoreOptions::MAX_SPEED_PERCENT));
+        assert_eq!(parse_speed("fast"), None);
+    }
+
+    #[test]
+    fn combo_parse() {
+        assert_eq!(ButtonCombo::parse("select+start"), ButtonCombo::SelectStart);
+        assert_eq!(ButtonCombo::parse("disabled"), ButtonCombo::Disabled);
+        assert_eq!(ButtonCombo::parse("bogus"), ButtonCombo::Disabled);
+        assert_eq!(ButtonCombo::parse("l"), ButtonCombo::L1);
+    }
+
+    #[test]
+    fn next_speed_preset() {
+        assert_eq!(CoreOptions::next_speed_preset(100), 200);
+        assert_eq!(CoreOptions::next_speed_preset(150), 200);
+        assert_eq!(CoreOptions::next_speed_preset(800), 25);
+    }
+}
@@ -0,0 +1,83 @@
+use std::{collections::BTreeMap, fs, io, path::Path};
+
+/// Core option values chosen by the user for a single program, stored next to its RPL
+/// flags so that they are reapplied whenever the program is loaded again. This works
+/// regardless of whether the frontend supports per-game option overrides itself.
+///
+/// The file is a flat TOML table of core option keys and values:
+///
+/// ```toml
+/// oxid8_ipf = "20"
+/// oxid8_quirk_shift = "enabled"
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GameOverrides {
+    values: BTreeMap<String, String>,
+}
+
+impl GameOverrides {
+    /// Read overrides from a file. A missing or malformed file yields no overrides.
+    pub fn load(path: &Path) -> Self {
+        fs::read_to_string(path).map(|text| Self::parse(&text)).unwrap_or_default()
+    }
+
+    /// Write overrides to a file, removing it if there are none.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if self.values.is_empty() {
+            return match fs::remove_file(path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+                _ => Ok(()),
+            };
+        }
+
+        fs::write(path, self.to_toml())
+    }
+
+    /// Parse `key = "value"` lines, ignoring blank lines, comments and anything else.
+    pub fn parse(text: &str) -> Self {
+        let values = text.lines()
+            .filter_map(|line| {
+                let (key, value) = line.split_once('=')?;
+                let value = value.trim().strip_prefix('"')?.strip_suffix('"')?;
+                Some((key.trim().to_string(), value.to_string()))
+            })
+            .filter(|(key, _)| !key.is_empty() && !key.starts_with('#'))
+            .collect();
+
+        Self { values }
+    }
+
+    pub fn to_toml(&self) -> String {
+        self.values.iter().map(|(key, value)| format!("{} = \"{}\"\n", key, value)).collect()
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(String::as_str)
+    }
+
+    pub fn set(&mut self, key: &str, value: &str) {
+        self.values.insert(key.to_string(), value.to_string());
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn toml_roundtrip() {
+        let mut overrides = GameOverrides::default();
+        overrides.set("oxid8_ipf", "20");
+        overrides.set("oxid8_quirk_shift", "enabled");
+
+        assert_eq!(GameOverrides::parse(&overrides.to_toml()), overrides);
+
+        let parsed = GameOverrides::parse("# comment\n\noxid8_scale = \"2x\"\nbogus\n");
+        assert_eq!(parsed.get("oxid8_scale"), Some("2x"));
+        assert_eq!(parsed.get("bogus"), None);
+    }
+}
@@ -0,0 +1,157 @@
+use std::{fs, path::Path};
+
+/// Display colors, in 24-bit RGB. Entry 0 is the background and entry 1 the color of set
+/// pixels. Entries 2 and 3 are reserved for the second XO-CHIP plane and for pixels set
+/// in both planes, following Octo's palette order, and entries up to 15 for MEGA-CHIP.
+/// Only the first two entries are used until multiple planes are supported.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Palette {
+    colors: [u32; Palette::MAX_COLORS],
+}
+
+impl Palette {
+    pub const MAX_COLORS: usize = 16;
+
+    /// Name of the palette file, looked for in the frontend's system directory.
+    pub const FILE_NAME: &'static str = "oxid8.pal";
+
+    /// Green LCD colors, the original colors of the core.
+    pub const LCD: Self = Self::new([0x103810, 0x98BC10, 0x306230, 0x8BAC0F]);
+    /// Octo's default palette.
+    pub const OCTO: Self = Self::new([0x996600, 0xFFCC00, 0xFF6600, 0x662200]);
+    pub const MONO: Self = Self::new([0x000000, 0xFFFFFF, 0xAAAAAA, 0x555555]);
+
+    const fn new(first: [u32; 4]) -> Self {
+        let mut colors = [0; Self::MAX_COLORS];
+        let mut i = 0;
+        while i < first.len() {
+            colors[i] = first[i];
+            i += 1;
+        }
+        Self { colors }
+    }
+
+    /// Preset palettes and their names, in the order they are cycled through.
+    pub const PRESETS: [(&'static str, Self); 3] = [("lcd", Self::LCD), ("octo", Self::OCTO), ("mono", Self::MONO)];
+
+    /// Preset palette by name (`lcd`, `octo` or `mono`).
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::PRESETS.into_iter().find(|&(n, _)| n == name).map(|(_, palette)| palette)
+    }
+
+    /// Preset following this palette, and its name. Custom palettes are followed by
+    /// the first preset.
+    pub fn next_preset(&self) -> (&'static str, Self) {
+        let index = Self::PRESETS.iter().position(|(_, palette)| palette == self).map_or(0, |i| i + 1);
+        Self::PRESETS[index % Self::PRESETS.len()]
+    }
+
+    /// Read a palette file. Returns `None` if the file is missing or invalid, which is
+    /// logged to standard error only, since palettes are loaded while parsing options,
+    /// independently of any core and its trace file.
+    pub fn load(path: &Path) -> Option<Self> {
+        let palette = fs::read_to_string(path).ok().and_then(|text| Self::parse(&text));
+        if palette.is_none() {
+            eprintln!("oxid-8: palette file {} missing or invalid", path.display());
+        }
+        palette
+    }
+
+    /// Parse a palette from 2 to 16 colors in hexadecimal (`#RRGGBB`, as in Octo),
+    /// separated by whitespace or commas. Lines starting with `;` are ignored. Missing
+    /// entries keep the colors of the default palette.
+    pub fn parse(text: &str) -> Option<Self> {
+        let colors: Vec<u32> = text.lines()
+            .filter(|line| !line.trim_start().starts_with(';'))
+            .flat_map(|line| line.split(|c: char| c.is_whitespace() || c == ','))
+            .filter(|token| !token.is_empty())
+            .map(|token| {
+                let hex = token.strip_prefix('#').unwrap_or(token);
+                if hex.len() == 6 { u32::from_str_radix(hex, 16).ok() } else { None }
+            })
+            .collect::<Option<_>>()?;
+
+        if !(2..=Self::MAX_COLORS).contains(&colors.len()) {
+            return None;
+        }
+
+        let mut palette = Self::default();
+        palette.colors[..colors.len()].copy_from_slice(&colors);
+        Some(palette)
+    }
+
+    pub fn color(&self, index: usize) -> u32 {
+        self.colors[index]
+    }
+
+    /// Palette between this one and `other`, `step` steps of `steps` of the way, for
+    /// fading from one palette to another.
+    pub fn blend(&self, other: &Self, step: u32, steps: u32) -> Self {
+        let mut colors = [0; Self::MAX_COLORS];
+
+        for (color, (&from, &to)) in colors.iter_mut().zip(self.colors.iter().zip(&other.colors)) {
+            for shift in [0, 8, 16] {
+                let (from, to) = ((from >> shift) & 0xFF, (to >> shift) & 0xFF);
+                let channel = (from * (steps - step) + to * step) / steps;
+                *color |= channel << shift;
+            }
+        }
+
+        Self { colors }
+    }
+
+    /// Color of an entry in RGB565, the pixel format of the video output.
+    pub fn rgb565(&self, index: usize) -> u16 {
+        let color = self.colors[index];
+        let (r, g, b) = ((color >> 16) & 0xFF, (color >> 8) & 0xFF, color & 0xFF);
+        ((r >> 3) << 11 | (g >> 2) << 5 | b >> 3) as u16
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self::LCD
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let palette = Palette::parse("; Octo\n#996600 #FFCC00\nFF6600, 662200\n").unwrap();
+        assert_eq!(palette, Palette::OCTO);
+
+        let palette = Palette::parse("#000000\n#FFFFFF\n").unwrap();
+        assert_eq!(palette.color(1), 0xFFFFFF);
+        assert_eq!(palette.color(2), Palette::LCD.color(2));
+
+        assert_eq!(Palette::parse("#000000"), None);
+        assert_eq!(Palette::parse("#000000 #GGGGGG"), None);
+    }
+
+    #[test]
+    fn next_preset() {
+        assert_eq!(Palette::LCD.next_preset(), ("octo", Palette::OCTO));
+        assert_eq!(Palette::MONO.next_preset(), ("lcd", Palette::LCD));
+        assert_eq!(Palette::parse("#000000 #FFFFFF").unwrap().next_preset().0, "lcd");
+    }
+
+    #[test]
+    fn blend() {
+        assert_eq!(Palette::MONO.blend(&Palette::OCTO, 0, 4), Palette::MONO);
+        assert_eq!(Palette::MONO.blend(&Palette::OCTO, 4, 4), Palette::OCTO);
+        assert_eq!(Palette::MONO.blend(&Palette::MONO, 1, 3), Palette::MONO);
+
+        let halfway = Palette::MONO.blend(&Palette::OCTO, 2, 4);
+        assert_eq!((halfway.color(0), halfway.color(1)), (0x4C3300, 0xFFE57F));
+    }
+
+    #[test]
+    fn rgb565() {
+        assert_eq!(Palette::LCD.rgb565(0), 0x11C2);
+        assert_eq!(Palette::LCD.rgb565(1), 0x9DE2);
+        assert_eq!(Palette::MONO.rgb565(1), 0xFFFF);
+    }
+}
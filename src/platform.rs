@@ -0,0 +1,185 @@
+use crate::cpu::MemoryLayout;
+
+/// Set of abnormal instruction behaviours ("quirks") that certain programs rely on.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Quirks {
+    /// Instructions that write to or read from RAM no longer increment `I`.
+    pub memory: bool,
+    /// Shift instructions shift `VX` directly instead of `VY`.
+    pub shift: bool,
+    /// `DXYN` stores the number of collided or clipped rows in `VF` (high-resolution mode).
+    pub collision: bool,
+    /// Switching between resolutions clears the frame buffer.
+    pub resolution: bool,
+    /// `DXY0` draws 16x16 sprites in low-resolution mode.
+    pub lores16: bool,
+    /// Scroll instructions move the display by half of a logical pixel in low-resolution mode.
+    pub scroll: bool,
+    /// `DXY0` draws 8x16 sprites in low-resolution mode. Ignored if `lores16` is active.
+    pub lores_tall: bool,
+    /// `FX0A` completes when a key is released instead of pressed, as on the COSMAC VIP.
+    pub key_release: bool,
+}
+
+impl Quirks {
+    const MEMORY: u8 = 1 << 0;
+    const SHIFT: u8 = 1 << 1;
+    const COLLISION: u8 = 1 << 2;
+    const RESOLUTION: u8 = 1 << 3;
+    const LORES16: u8 = 1 << 4;
+    const SCROLL: u8 = 1 << 5;
+    const LORES_TALL: u8 = 1 << 6;
+    const KEY_RELEASE: u8 = 1 << 7;
+
+    /// Pack the quirks into a bit field, used by movie and state files.
+    pub fn to_bits(&self) -> u8 {
+        let mut bits = 0;
+        if self.memory { bits |= Self::MEMORY; }
+        if self.shift { bits |= Self::SHIFT; }
+        if self.collision { bits |= Self::COLLISION; }
+        if self.resolution { bits |= Self::RESOLUTION; }
+        if self.lores16 { bits |= Self::LORES16; }
+        if self.scroll { bits |= Self::SCROLL; }
+        if self.lores_tall { bits |= Self::LORES_TALL; }
+        if self.key_release { bits |= Self::KEY_RELEASE; }
+        bits
+    }
+
+    pub fn from_bits(bits: u8) -> Self {
+        Self {
+            memory: bits & Self::MEMORY != 0,
+            shift: bits & Self::SHIFT != 0,
+            collision: bits & Self::COLLISION != 0,
+            resolution: bits & Self::RESOLUTION != 0,
+            lores16: bits & Self::LORES16 != 0,
+            scroll: bits & Self::SCROLL != 0,
+            lores_tall: bits & Self::LORES_TALL != 0,
+            key_release: bits & Self::KEY_RELEASE != 0,
+        }
+    }
+
+    /// Behaviour of the `DXY0` instruction in low-resolution mode.
+    pub fn lores_dxy0(&self) -> LoresDxy0 {
+        if self.lores16 {
+            LoresDxy0::Large
+        }
+        else if self.lores_tall {
+            LoresDxy0::Tall
+        }
+        else {
+            LoresDxy0::NoOp
+        }
+    }
+
+    /// Quirks enabled in either set.
+    pub fn union(&self, other: Quirks) -> Self {
+        Self::from_bits(self.to_bits() | other.to_bits())
+    }
+}
+
+/// Behaviour of the `DXY0` instruction in low-resolution mode, which differs between
+/// interpreters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LoresDxy0 {
+    /// Draw nothing (a sprite with 0 rows).
+    NoOp,
+    /// Draw an 8x16 sprite (SUPER-CHIP 1.1).
+    Tall,
+    /// Draw a 16x16 sprite, as in high-resolution mode.
+    Large,
+}
+
+/// Interpreter variant whose behaviour should be reproduced.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Platform {
+    /// Original CHIP-8 interpreter, with the SUPER-CHIP instructions available as extensions.
+    #[default]
+    Chip8,
+    /// SUPER-CHIP, as implemented by modern interpreters.
+    SuperChip,
+    /// Original SUPER-CHIP 1.1 interpreter for the HP48 calculators.
+    SuperChipLegacy,
+    /// CHIP-8 interpreter of the ETI-660 computer, which loads programs at `0x600`.
+    Eti660,
+}
+
+impl Platform {
+    /// Default quirks of the platform.
+    pub fn quirks(&self) -> Quirks {
+        match self {
+            Self::Chip8 => Quirks::default(),
+            Self::SuperChip => Quirks { memory: true, shift: true, lores16: true, ..Quirks::default() },
+            Self::SuperChipLegacy => Quirks {
+                memory: true, shift: true, collision: true, scroll: true, lores_tall: true, ..Quirks::default()
+            },
+            Self::Eti660 => Quirks::default(),
+        }
+    }
+
+    /// Smallest sound timer value that produces a sound. On the COSMAC VIP, the tone only
+    /// starts for values of 2 or more, and some programs set the timer to 1 as a "no beep".
+    pub fn min_sound_timer(&self) -> u8 {
+        match self {
+            Self::Chip8 => 2,
+            _ => 1,
+        }
+    }
+
+    /// Default fonts, their placement and the start address of programs. The original
+    /// SUPER-CHIP lacks large digits `A` to `F`.
+    pub fn memory_layout(&self) -> MemoryLayout {
+        match self {
+            Self::SuperChipLegacy => MemoryLayout { large_font_hex: false, ..MemoryLayout::default() },
+            Self::Eti660 => MemoryLayout { program_addr: 0x600, ..MemoryLayout::default() },
+            _ => MemoryLayout::default(),
+        }
+    }
+
+    /// Name used for the platform in command-line arguments and core options.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Chip8 => "chip8",
+            Self::SuperChip => "schip",
+            Self::SuperChipLegacy => "schip-legacy",
+            Self::Eti660 => "eti660",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "chip8" => Some(Self::Chip8),
+            "schip" => Some(Self::SuperChip),
+            "schip-legacy" => Some(Self::SuperChipLegacy),
+            "eti660" => Some(Self::Eti660),
+            _ => None,
+        }
+    }
+
+    /// Identifier used by movie and state files.
+    pub fn id(&self) -> u8 {
+        *self as u8
+    }
+
+    pub fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(Self::Chip8),
+            1 => Some(Self::SuperChip),
+            2 => Some(Self::SuperChipLegacy),
+            3 => Some(Self::Eti660),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quirks_bits() {
+        let quirks = Quirks { shift: true, lores16: true, ..Quirks::default() };
+
+        assert_eq!(Quirks::from_bits(quirks.to_bits()), quirks);
+        assert_eq!(Quirks::default().to_bits(), 0);
+    }
+}
@@ -0,0 +1,132 @@
+/// Source of the random numbers of the `RAND` instruction, which can replace the seeded
+/// generator of the core (see `Chip8Core::set_random_source`) to make programs that
+/// depend on random numbers behave predictably in tests.
+pub trait RandomSource: Send + Sync {
+    /// Next random byte, masked by the instruction.
+    fn next_byte(&mut self) -> u8;
+
+    /// Restart the sequence, called when the core is reset.
+    fn reset(&mut self);
+}
+
+/// Repeats a fixed sequence of bytes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FixedSequence {
+    bytes: Vec<u8>,
+    index: usize,
+}
+
+impl FixedSequence {
+    /// Sequence of the given bytes, which must not be empty.
+    pub fn new(bytes: &[u8]) -> Self {
+        assert!(!bytes.is_empty(), "random sequence must not be empty");
+        Self { bytes: bytes.to_vec(), index: 0 }
+    }
+}
+
+impl RandomSource for FixedSequence {
+    fn next_byte(&mut self) -> u8 {
+        let byte = self.bytes[self.index];
+        self.index = (self.index + 1) % self.bytes.len();
+        byte
+    }
+
+    fn reset(&mut self) {
+        self.index = 0;
+    }
+}
+
+/// Counts up from a starting value, wrapping around, so that every byte is produced
+/// once every 256 draws.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Counter {
+    start: u8,
+    value: u8,
+}
+
+impl Counter {
+    pub fn new(start: u8) -> Self {
+        Self { start, value: start }
+    }
+}
+
+impl RandomSource for Counter {
+    fn next_byte(&mut self) -> u8 {
+        let byte = self.value;
+        self.value = self.value.wrapping_add(1);
+        byte
+    }
+
+    fn reset(&mut self) {
+        self.value = self.start;
+    }
+}
+
+/// PCG32 generator (XSH RR variant), a small generator with a well-known output
+/// sequence for a given seed, unlike the default generator of the core.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Pcg32 {
+    seed: u64,
+    increment: u64,
+    state: u64,
+}
+
+impl Pcg32 {
+    const MULTIPLIER: u64 = 6364136223846793005;
+    /// Stream of the reference implementation's default increment.
+    const DEFAULT_STREAM: u64 = 0x0A02_BDBF_7BB3_C0A7;
+
+    pub fn new(seed: u64) -> Self {
+        Self::with_stream(seed, Self::DEFAULT_STREAM)
+    }
+
+    /// Generator with a seed and a stream selector, as in `pcg32_srandom_r`.
+    pub fn with_stream(seed: u64, stream: u64) -> Self {
+        let mut pcg = Self { seed, increment: (stream << 1) | 1, state: 0 };
+        pcg.reset();
+        pcg
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let state = self.state;
+        self.state = state.wrapping_mul(Self::MULTIPLIER).wrapping_add(self.increment);
+
+        let xorshifted = (((state >> 18) ^ state) >> 27) as u32;
+        xorshifted.rotate_right((state >> 59) as u32)
+    }
+}
+
+impl RandomSource for Pcg32 {
+    fn next_byte(&mut self) -> u8 {
+        (self.next_u32() >> 24) as u8
+    }
+
+    fn reset(&mut self) {
+        self.state = 0;
+        self.next_u32();
+        self.state = self.state.wrapping_add(self.seed);
+        self.next_u32();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sources() {
+        let mut sequence = FixedSequence::new(&[3, 1]);
+        assert_eq!([sequence.next_byte(), sequence.next_byte(), sequence.next_byte()], [3, 1, 3]);
+        sequence.reset();
+        assert_eq!(sequence.next_byte(), 3);
+
+        let mut counter = Counter::new(0xFF);
+        assert_eq!([counter.next_byte(), counter.next_byte()], [0xFF, 0x00]);
+
+        // Reference output of the PCG32 demo program (seed 42, stream 54)
+        let mut pcg = Pcg32::with_stream(42, 54);
+        assert_eq!([pcg.next_u32(), pcg.next_u32()], [0xA15C02B7, 0x7B47F409]);
+        pcg.reset();
+        assert_eq!(pcg.next_byte(), 0xA1);
+    }
+}
@@ -0,0 +1,353 @@
+use std::fmt;
+
+use crate::cpu::Cpu;
+use crate::platform::{Platform, Quirks};
+
+/// Error returned when a memory snapshot cannot be parsed.
+#[derive(Debug, PartialEq, Eq)]
+pub enum SnapshotError {
+    InvalidFormat,
+    UnsupportedVersion(u8),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidFormat => write!(f, "not a valid memory snapshot"),
+            Self::UnsupportedVersion(v) => write!(f, "unsupported memory snapshot format version {}", v),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+/// Full memory image of the interpreter along with its registers and stack, for offline
+/// analysis. Snapshots taken from the core also record its platform and quirks, since
+/// resuming a program under a different configuration can make it behave differently.
+///
+/// Binary layout (little endian):
+///
+/// | Size | Field                                     |
+/// |------|-------------------------------------------|
+/// | 4    | Magic (`OX8D`)                            |
+/// | 1    | Format version                            |
+/// | 1    | Platform identifier (`0xFF` if unknown)   |
+/// | 1    | Quirks bit field                          |
+/// | 2    | `PC`                                      |
+/// | 2    | `I`                                       |
+/// | 16   | `V0` to `VF`                              |
+/// | 1    | Delay timer                               |
+/// | 1    | Sound timer                               |
+/// | 2    | Stack depth `N`                           |
+/// | 2N   | Stack, from the bottom                    |
+/// | 4096 | Memory                                    |
+///
+/// The stack is unbounded, so a program that keeps calling subroutines without returning
+/// can grow it past `u16::MAX` entries, of which only the top ones are kept.
+///
+/// Versions 1 and 2 store the stack depth in a single byte, and are still read. Version 1
+/// also lacks the platform and quirks, and is read without a configuration.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MemorySnapshot {
+    /// Platform and quirks in effect when the snapshot was taken, if known.
+    pub config: Option<(Platform, Quirks)>,
+    pub pc: u16,
+    pub i_register: u16,
+    pub registers: [u8; 16],
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    pub stack: Vec<u16>,
+    pub memory: Vec<u8>,
+}
+
+impl MemorySnapshot {
+    const MAGIC: &'static [u8; 4] = b"OX8D";
+    const VERSION: u8 = 3;
+    const HEADER_SIZE: usize = 31;
+    const MEMORY_SIZE: usize = 4 * 1024;
+    const UNKNOWN_PLATFORM: u8 = 0xFF;
+
+    /// Capture the state of a CPU, without a configuration.
+    pub fn from_cpu(cpu: &Cpu) -> Self {
+        Self {
+            config: None,
+            pc: cpu.pc,
+            i_register: cpu.i_register,
+            registers: cpu.registers,
+            delay_timer: cpu.delay_timer,
+            sound_timer: cpu.sound_timer,
+            stack: cpu.stack.clone(),
+            memory: cpu.memory.to_vec(),
+        }
+    }
+
+    /// Restore the captured state into a CPU.
+    pub fn restore(&self, cpu: &mut Cpu) {
+        cpu.pc = self.pc;
+        cpu.i_register = self.i_register;
+        cpu.registers = self.registers;
+        cpu.delay_timer = self.delay_timer;
+        cpu.sound_timer = self.sound_timer;
+        cpu.stack.clone_from(&self.stack);
+        cpu.memory.copy_from_slice(&self.memory);
+    }
+
+    /// Warning to show when resuming the snapshot under a configuration other than the
+    /// one it was taken with.
+    pub fn config_warning(&self, platform: Platform, quirks: Quirks) -> Option<String> {
+        let (saved_platform, saved_quirks) = self.config?;
+
+        if saved_platform != platform {
+            Some(format!("snapshot was taken on platform {}, current platform is {}", saved_platform.name(), platform.name()))
+        }
+        else if saved_quirks != quirks {
+            Some(format!("snapshot was taken with quirks {:?}, current quirks are {:?}", saved_quirks, quirks))
+        }
+        else {
+            None
+        }
+    }
+
+    /// Differences from an earlier snapshot, with a line per changed register, timer,
+    /// stack or range of consecutive memory bytes, for finding what was modified by the
+    /// execution in between. Empty if the snapshots are identical.
+    pub fn diff(&self, earlier: &Self) -> String {
+        let mut diff = String::new();
+
+        if self.pc != earlier.pc {
+            diff += &format!("PC: 0x{:03X} -> 0x{:03X}\n", earlier.pc, self.pc);
+        }
+        if self.i_register != earlier.i_register {
+            diff += &format!("I: 0x{:03X} -> 0x{:03X}\n", earlier.i_register, self.i_register);
+        }
+        for (x, (before, after)) in earlier.registers.iter().zip(&self.registers).enumerate() {
+            if before != after {
+                diff += &format!("V{:X}: 0x{:02X} -> 0x{:02X}\n", x, before, after);
+            }
+        }
+        if self.delay_timer != earlier.delay_timer {
+            diff += &format!("DT: {} -> {}\n", earlier.delay_timer, self.delay_timer);
+        }
+        if self.sound_timer != earlier.sound_timer {
+            diff += &format!("ST: {} -> {}\n", earlier.sound_timer, self.sound_timer);
+        }
+        if self.stack != earlier.stack {
+            let format_stack = |stack: &[u16]| stack.iter().map(|addr| format!("0x{:03X}", addr)).collect::<Vec<_>>().join(" ");
+            diff += &format!("stack: [{}] -> [{}]\n", format_stack(&earlier.stack), format_stack(&self.stack));
+        }
+
+        let format_bytes = |bytes: &[u8]| bytes.iter().map(|b| format!("{:02X}", b)).collect::<Vec<_>>().join(" ");
+        let size = self.memory.len().min(earlier.memory.len());
+        let mut addr = 0;
+        while addr < size {
+            let end = (addr..size).find(|&a| self.memory[a] == earlier.memory[a]).unwrap_or(size);
+
+            if end == addr {
+                addr += 1;
+                continue;
+            }
+
+            diff += &format!("0x{:03X}-0x{:03X}: {} -> {}\n", addr, end - 1,
+                format_bytes(&earlier.memory[addr..end]), format_bytes(&self.memory[addr..end]));
+            addr = end;
+        }
+
+        diff
+    }
+
+    /// Serialize the snapshot as human-readable JSON for debugging and bug reports, with
+    /// a field per line and memory as rows of 32 hex-encoded bytes, so that snapshots can
+    /// be compared with a text diff. There is no parser for it: only the binary format can
+    /// be loaded back.
+    pub fn to_json(&self) -> String {
+        let (platform, quirks) = match self.config {
+            Some((platform, quirks)) => (format!("\"{}\"", platform.name()), quirks.to_bits().to_string()),
+            None => ("null".to_string(), "null".to_string()),
+        };
+        let list = |values: &mut dyn Iterator<Item = String>| values.collect::<Vec<_>>().join(",");
+        let memory: Vec<String> = self.memory.chunks(32)
+            .map(|row| format!("    \"{}\"", row.iter().map(|b| format!("{:02x}", b)).collect::<String>()))
+            .collect();
+
+        format!(
+            "{{\n  \"platform\": {},\n  \"quirks\": {},\n  \"pc\": {},\n  \"i\": {},\n  \"registers\": [{}],\n  \
+             \"delay_timer\": {},\n  \"sound_timer\": {},\n  \"stack\": [{}],\n  \"memory\": [\n{}\n  ]\n}}\n",
+            platform, quirks, self.pc, self.i_register, list(&mut self.registers.iter().map(u8::to_string)),
+            self.delay_timer, self.sound_timer, list(&mut self.stack.iter().map(u16::to_string)), memory.join(",\n"),
+        )
+    }
+
+    /// Whether data starts like a memory snapshot, as opposed to a program.
+    pub fn is_snapshot(data: &[u8]) -> bool {
+        data.starts_with(Self::MAGIC)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(Self::HEADER_SIZE + 2 * self.stack.len() + self.memory.len());
+        data.extend_from_slice(Self::MAGIC);
+        data.push(Self::VERSION);
+        match self.config {
+            Some((platform, quirks)) => data.extend_from_slice(&[platform.id(), quirks.to_bits()]),
+            None => data.extend_from_slice(&[Self::UNKNOWN_PLATFORM, 0]),
+        }
+        data.extend_from_slice(&self.pc.to_le_bytes());
+        data.extend_from_slice(&self.i_register.to_le_bytes());
+        data.extend_from_slice(&self.registers);
+        data.push(self.delay_timer);
+        data.push(self.sound_timer);
+        let stack = &self.stack[self.stack.len().saturating_sub(u16::MAX as usize)..];
+        data.extend_from_slice(&(stack.len() as u16).to_le_bytes());
+
+        for addr in stack {
+            data.extend_from_slice(&addr.to_le_bytes());
+        }
+
+        data.extend_from_slice(&self.memory);
+        data
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, SnapshotError> {
+        if data.len() < 5 || &data[0..4] != Self::MAGIC {
+            return Err(SnapshotError::InvalidFormat);
+        }
+
+        // Version 1 lacks the configuration bytes, and versions before 3 have a single byte
+        // for the stack depth
+        let (config, data, depth_size) = match data[4] {
+            1 => (None, &data[5..], 1),
+            2..=Self::VERSION if data.len() >= 7 => {
+                let config = Platform::from_id(data[5]).map(|platform| (platform, Quirks::from_bits(data[6])));
+                (config, &data[7..], if data[4] == Self::VERSION { 2 } else { 1 })
+            }
+            2..=Self::VERSION => return Err(SnapshotError::InvalidFormat),
+            version => return Err(SnapshotError::UnsupportedVersion(version)),
+        };
+
+        // Offsets from here on are relative to `PC`
+        let stack_start = 22 + depth_size;
+        if data.len() < stack_start {
+            return Err(SnapshotError::InvalidFormat);
+        }
+
+        let u16_at = |i: usize| u16::from_le_bytes([data[i], data[i + 1]]);
+        let depth = if depth_size == 2 { u16_at(22) as usize } else { data[22] as usize };
+        let memory_start = stack_start + 2 * depth;
+
+        if data.len() != memory_start + Self::MEMORY_SIZE {
+            return Err(SnapshotError::InvalidFormat);
+        }
+
+        Ok(Self {
+            config,
+            pc: u16_at(0),
+            i_register: u16_at(2),
+            registers: data[4..20].try_into().unwrap(),
+            delay_timer: data[20],
+            sound_timer: data[21],
+            stack: (0..depth).map(|i| u16_at(stack_start + 2 * i)).collect(),
+            memory: data[memory_start..].to_vec(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        let mut cpu = Cpu::new();
+        cpu.pc = 0x2A4;
+        cpu.i_register = 0x3F0;
+        cpu.registers[0xF] = 1;
+        cpu.stack = vec![0x202, 0x240];
+        cpu.memory[0xFFF] = 0x55;
+
+        let snapshot = MemorySnapshot::from_cpu(&cpu);
+        let data = snapshot.to_bytes();
+
+        assert_eq!(data.len(), 31 + 4 + 4096);
+        assert_eq!(MemorySnapshot::from_bytes(&data), Ok(snapshot.clone()));
+        assert_eq!(MemorySnapshot::from_bytes(&data[..100]), Err(SnapshotError::InvalidFormat));
+
+        let config = Some((Platform::SuperChip, Platform::SuperChip.quirks()));
+        let configured = MemorySnapshot { config, ..snapshot.clone() };
+        assert_eq!(MemorySnapshot::from_bytes(&configured.to_bytes()), Ok(configured));
+
+        // Version 2, with a single byte for the stack depth, and version 1, also without
+        // the configuration bytes
+        let mut version2 = data.clone();
+        version2.remove(7 + 23);
+        version2[4] = 2;
+        assert_eq!(MemorySnapshot::from_bytes(&version2), Ok(snapshot.clone()));
+
+        let mut version1 = version2.clone();
+        version1.drain(5..7);
+        version1[4] = 1;
+        assert_eq!(MemorySnapshot::from_bytes(&version1), Ok(snapshot.clone()));
+
+        // Runaway recursion, deeper than a byte can count
+        let deep = MemorySnapshot { stack: (0..300).map(|i| 0x200 + 2 * i).collect(), ..snapshot.clone() };
+        assert_eq!(MemorySnapshot::from_bytes(&deep.to_bytes()), Ok(deep));
+
+        let mut restored = Cpu::new();
+        snapshot.restore(&mut restored);
+        assert_eq!(MemorySnapshot::from_cpu(&restored), snapshot);
+    }
+
+    #[test]
+    fn diff() {
+        let mut cpu = Cpu::new();
+        let earlier = MemorySnapshot::from_cpu(&cpu);
+        assert_eq!(earlier.diff(&earlier), "");
+
+        cpu.pc = 0x204;
+        cpu.registers[0xA] = 0x10;
+        cpu.sound_timer = 4;
+        cpu.stack = vec![0x202];
+        cpu.memory[0x300..0x302].copy_from_slice(&[0x12, 0x34]);
+        cpu.memory[0x310] = 0xFF;
+
+        assert_eq!(
+            MemorySnapshot::from_cpu(&cpu).diff(&earlier),
+            "PC: 0x200 -> 0x204\nVA: 0x00 -> 0x10\nST: 0 -> 4\nstack: [] -> [0x202]\n\
+             0x300-0x301: 00 00 -> 12 34\n0x310-0x310: 00 -> FF\n",
+        );
+    }
+
+    #[test]
+    fn json() {
+        let mut cpu = Cpu::new();
+        cpu.pc = 0x204;
+        cpu.registers[1] = 7;
+        cpu.stack = vec![0x202, 0x240];
+        cpu.memory[0x301] = 0xAB;
+
+        let json = MemorySnapshot::from_cpu(&cpu).to_json();
+        assert!(json.starts_with("{\n  \"platform\": null,\n  \"quirks\": null,\n  \"pc\": 516,\n  \"i\": 0,\n  \
+            \"registers\": [0,7,0,0,0,0,0,0,0,0,0,0,0,0,0,0],\n  \"delay_timer\": 0,\n  \"sound_timer\": 0,\n  \
+            \"stack\": [514,576],\n  \"memory\": [\n"));
+        assert!(json.contains(&format!("\n    \"00ab{}\",\n", "00".repeat(30))));
+        assert_eq!(json.lines().count(), 10 + 4096 / 32 + 2);
+
+        let config = Some((Platform::SuperChip, Platform::SuperChip.quirks()));
+        let json = MemorySnapshot { config, ..MemorySnapshot::from_cpu(&cpu) }.to_json();
+        assert!(json.contains(&format!("\"platform\": \"schip\",\n  \"quirks\": {},", Platform::SuperChip.quirks().to_bits())));
+    }
+
+    #[test]
+    fn config_warning() {
+        let snapshot = MemorySnapshot {
+            config: Some((Platform::SuperChip, Platform::SuperChip.quirks())),
+            ..MemorySnapshot::from_cpu(&Cpu::new())
+        };
+
+        assert_eq!(snapshot.config_warning(Platform::SuperChip, Platform::SuperChip.quirks()), None);
+        assert_eq!(
+            snapshot.config_warning(Platform::Chip8, Quirks::default()).as_deref(),
+            Some("snapshot was taken on platform schip, current platform is chip8"),
+        );
+        assert!(snapshot.config_warning(Platform::SuperChip, Quirks::default()).is_some());
+        assert_eq!(MemorySnapshot::from_cpu(&Cpu::new()).config_warning(Platform::Chip8, Quirks::default()), None);
+    }
+}
@@ -0,0 +1,157 @@
+use std::{collections::HashSet, fmt};
+
+/// Misuse of the stack found by `StackMonitor`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StackWarning {
+    /// The subroutine at `target` was called from `from` while already running, making
+    /// `depth` calls to it active at once, more than the configured limit.
+    Recursion { target: u16, from: u16, depth: usize },
+    /// The stack has kept growing over several seconds, so subroutines are called more
+    /// often than they return (e.g. a subroutine jumps back to the main loop instead of
+    /// returning).
+    Imbalance { depth: usize, calls: u64, returns: u64 },
+}
+
+impl fmt::Display for StackWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Recursion { target, from, depth } =>
+                write!(f, "subroutine at 0x{:03X} called recursively from 0x{:03X} ({} calls active)", target, from, depth),
+            Self::Imbalance { depth, calls, returns } =>
+                write!(f, "stack keeps growing ({} entries, {} calls and {} returns since reset)", depth, calls, returns),
+        }
+    }
+}
+
+/// Tracks subroutine calls and returns to find recursion deeper than a limit and stacks
+/// that grow without bound, two common bugs in programs that the interpreter otherwise
+/// runs without complaint since its stack is unbounded. Each problem is reported once.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StackMonitor {
+    max_recursion: usize,
+    /// Target of each active call, from the bottom of the stack.
+    targets: Vec<u16>,
+    reported: HashSet<u16>,
+    calls: u64,
+    returns: u64,
+    warnings: Vec<StackWarning>,
+    /// Frames of the current window, and the smallest stack depth seen during it.
+    window_frames: u32,
+    window_low: usize,
+    previous_low: usize,
+    growing_windows: u32,
+}
+
+impl StackMonitor {
+    /// Frames over which the lowest stack depth is taken.
+    const WINDOW_FRAMES: u32 = 60;
+    /// Consecutive windows in which the lowest depth increases before an imbalance is
+    /// reported.
+    const GROWING_WINDOWS: u32 = 3;
+
+    /// Monitor reporting subroutines with more than `max_recursion` calls active at once.
+    pub fn new(max_recursion: usize) -> Self {
+        Self {
+            max_recursion,
+            targets: Vec::new(),
+            reported: HashSet::new(),
+            calls: 0,
+            returns: 0,
+            warnings: Vec::new(),
+            window_frames: 0,
+            window_low: 0,
+            previous_low: 0,
+            growing_windows: 0,
+        }
+    }
+
+    /// Problems found since the last reset, in the order they were found.
+    pub fn warnings(&self) -> &[StackWarning] {
+        &self.warnings
+    }
+
+    pub fn calls(&self) -> u64 {
+        self.calls
+    }
+
+    pub fn returns(&self) -> u64 {
+        self.returns
+    }
+
+    pub(crate) fn reset(&mut self) {
+        *self = Self::new(self.max_recursion);
+    }
+
+    pub(crate) fn call(&mut self, from: u16, target: u16) -> Option<StackWarning> {
+        self.calls += 1;
+        self.targets.push(target);
+
+        let depth = self.targets.iter().filter(|&&t| t == target).count();
+        (depth > self.max_recursion && self.reported.insert(target))
+            .then(|| self.warn(StackWarning::Recursion { target, from, depth }))
+    }
+
+    pub(crate) fn ret(&mut self) {
+        self.returns += 1;
+        self.targets.pop();
+    }
+
+    /// Sample the stack depth at the end of a frame.
+    pub(crate) fn end_frame(&mut self, depth: usize) -> Option<StackWarning> {
+        self.window_low = if self.window_frames == 0 { depth } else { self.window_low.min(depth) };
+        self.window_frames += 1;
+
+        if self.window_frames < Self::WINDOW_FRAMES {
+            return None;
+        }
+
+        let growing = self.window_low > self.previous_low;
+        self.growing_windows = if growing { self.growing_windows + 1 } else { 0 };
+        self.previous_low = self.window_low;
+        self.window_frames = 0;
+
+        let reported = self.warnings.iter().any(|w| matches!(w, StackWarning::Imbalance { .. }));
+        (self.growing_windows == Self::GROWING_WINDOWS && !reported)
+            .then(|| self.warn(StackWarning::Imbalance { depth, calls: self.calls, returns: self.returns }))
+    }
+
+    fn warn(&mut self, warning: StackWarning) -> StackWarning {
+        self.warnings.push(warning.clone());
+        warning
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recursion() {
+        let mut monitor = StackMonitor::new(2);
+
+        assert_eq!(monitor.call(0x200, 0x300), None);
+        assert_eq!(monitor.call(0x302, 0x300), None);
+        assert_eq!(monitor.call(0x302, 0x400), None);
+        assert_eq!(monitor.call(0x402, 0x300), Some(StackWarning::Recursion { target: 0x300, from: 0x402, depth: 3 }));
+
+        // Reported once
+        monitor.ret();
+        assert_eq!(monitor.call(0x402, 0x300), None);
+        assert_eq!(monitor.warnings().len(), 1);
+        assert_eq!((monitor.calls(), monitor.returns()), (5, 1));
+    }
+
+    #[test]
+    fn imbalance() {
+        let mut monitor = StackMonitor::new(8);
+
+        // A stack that varies but returns to the same depth is fine
+        for frame in 0..600 {
+            assert_eq!(monitor.end_frame(frame % 5), None);
+        }
+
+        // One more entry every second
+        let warnings: Vec<_> = (0..600).filter_map(|frame| monitor.end_frame(frame / 60 + 1)).collect();
+        assert_eq!(warnings, [StackWarning::Imbalance { depth: 3, calls: 0, returns: 0 }]);
+    }
+}
@@ -0,0 +1,129 @@
+//! Threaded-code execution engine, enabled with the `threaded` feature.
+//!
+//! Instead of fetching and decoding every instruction, code is translated once into a
+//! table of instruction callbacks with their arguments already extracted, indexed by
+//! address. Execution then runs through straight-line code (a basic block) by calling
+//! the callbacks in sequence, stopping at the first instruction that can change the flow
+//! of execution, such as jumps, skips, key waits and draws (which may stall). The table
+//! is allocated once, so translating new code does not allocate.
+//!
+//! Each translated instruction keeps its opcode, which is compared against memory before
+//! it runs. If a program modifies its own code, the stale entry is translated again, and
+//! instructions that are not supported are left to the interpreter, which reports them.
+
+use crate::Chip8Core;
+use crate::cpu::Args;
+
+/// Instructions after which execution may not continue at the next address.
+const BLOCK_END: [&str; 13] = [
+    "JMP", "CALL", "RET", "JMPR", "SKPEQ", "SKPNE", "SKPEQR", "SKPNER", "SKPK", "SKPNK", "KEY", "DRAW", "EXIT",
+];
+
+#[derive(Clone, Copy)]
+struct Op {
+    opcode: u16,
+    callback: fn(&mut Chip8Core, Args),
+    args: Args,
+    ends_block: bool,
+}
+
+/// Translated instructions, indexed by address.
+pub(crate) struct BlockCache {
+    ops: Vec<Option<Op>>,
+}
+
+impl Default for BlockCache {
+    fn default() -> Self {
+        Self { ops: vec![None; Chip8Core::MEMORY_SIZE] }
+    }
+}
+
+impl Chip8Core {
+    /// Execute the basic block at the program counter, up to `max` instructions (at least
+    /// one), returning the number executed.
+    pub(crate) fn execute(&mut self, max: usize) -> usize {
+        let mut executed = 0;
+
+        while executed < max {
+            let addr = self.cpu.pc as usize;
+            if addr + 1 >= Self::MEMORY_SIZE {
+                break;
+            }
+
+            let opcode = self.opcode_at(addr);
+            let op = match self.blocks.ops[addr] {
+                Some(op) if op.opcode == opcode => op,
+                _ => match self.translate(addr, opcode) {
+                    Some(op) => op,
+                    None => break,
+                },
+            };
+
+            self.executed[addr] = true;
+            self.executed[addr + 1] = true;
+            self.history.record(addr as u16, opcode);
+            self.cpu.pc += 2;
+            (op.callback)(self, op.args);
+            self.counters.instructions += 1;
+            executed += 1;
+
+            if op.ends_block || self.halted || self.draw_stalled || self.cpu.store_keypress.is_some() {
+                break;
+            }
+        }
+
+        if executed == 0 {
+            self.execute_instruction();
+            executed = 1;
+        }
+
+        executed
+    }
+
+    fn opcode_at(&self, addr: usize) -> u16 {
+        u16::from_be_bytes([self.cpu.memory[addr], self.cpu.memory[addr + 1]])
+    }
+
+    /// Translate the instruction at `addr`, or return `None` if it is not supported.
+    fn translate(&mut self, addr: usize, opcode: u16) -> Option<Op> {
+        let instruction = self.cpu.decode_instruction(opcode);
+
+        let op = (instruction.name() != "NOP").then(|| Op {
+            opcode,
+            callback: instruction.callback,
+            args: instruction.args(opcode),
+            ends_block: BLOCK_END.contains(&instruction.name()),
+        });
+
+        self.blocks.ops[addr] = op;
+        op
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn self_modifying_code() {
+        let mut core = Chip8Core::new();
+
+        // 0x200: MOV V0 0x01, MOVI 0x206, JMP 0x206
+        // 0x206: ADD V1 0x01, MOV V2 0xFF, SAVE V0 (overwrites the ADD), JMP 0x200
+        core.load_program(&[0x60, 0x01, 0xA2, 0x06, 0x12, 0x06, 0x71, 0x01, 0x62, 0xFF, 0xF0, 0x55, 0x12, 0x00]);
+
+        assert_eq!(core.execute(10), 3);
+        assert_eq!(core.execute(10), 4);
+        assert_eq!(core.cpu.memory[0x206..0x208], [0x01, 0x01]);
+        assert_eq!(core.execute(10), 3);
+
+        // The entry at 0x206 is stale, and the new instruction (NOP) is interpreted instead
+        assert_eq!(core.execute(10), 1);
+        assert_eq!(core.cpu.pc, 0x208);
+        assert_eq!(core.cpu.registers[0x1], 1);
+        assert!(core.blocks.ops[0x206].is_none());
+
+        assert_eq!(core.execute(2), 2);
+        assert_eq!(core.counters().instructions, 13);
+    }
+}
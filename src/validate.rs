@@ -0,0 +1,88 @@
+use std::fmt;
+
+/// Reason content is clearly not a CHIP-8 program, found by `validate`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InvalidContent {
+    Empty,
+    /// File in another format, recognized by its magic bytes.
+    Format(&'static str),
+    /// Mostly printable text, such as source code or documentation.
+    Text,
+}
+
+impl fmt::Display for InvalidContent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "file is empty"),
+            Self::Format(format) => write!(f, "file is a {}, not a CHIP-8 program", format),
+            Self::Text => write!(f, "file is text (e.g. source code or documentation), not a CHIP-8 program"),
+        }
+    }
+}
+
+impl std::error::Error for InvalidContent {}
+
+/// Magic bytes of common file formats. Signatures that are also likely first instructions
+/// of a program (such as `MZ` for Windows executables, `SKPNE VD 0x5A`) are left out.
+const SIGNATURES: &[(&[u8], &str)] = &[
+    (b"\x89PNG\r\n\x1A\n", "PNG image"),
+    (b"\xFF\xD8\xFF", "JPEG image"),
+    (b"GIF87a", "GIF image"),
+    (b"GIF89a", "GIF image"),
+    (b"PK\x03\x04", "ZIP archive"),
+    (b"Rar!\x1A\x07", "RAR archive"),
+    (b"7z\xBC\xAF\x27\x1C", "7-Zip archive"),
+    (b"%PDF-", "PDF document"),
+    (b"\x7FELF", "ELF executable"),
+    (b"RIFF", "RIFF file (e.g. WAV audio)"),
+    (b"OggS", "Ogg file"),
+    (b"ID3", "MP3 file"),
+];
+
+/// Minimum size of a file for it to be considered text, since short programs can consist
+/// of printable bytes by chance.
+const MIN_TEXT_SIZE: usize = 32;
+
+/// Check that content could be a CHIP-8 program, rejecting files that are clearly not
+/// (images, archives, text files and the like), so that they are not run as garbage.
+/// CHIP-8 programs have no header, so this only finds files that are something else.
+pub fn validate(data: &[u8]) -> Result<(), InvalidContent> {
+    if data.is_empty() {
+        return Err(InvalidContent::Empty);
+    }
+
+    if let Some(&(_, format)) = SIGNATURES.iter().find(|(magic, _)| data.starts_with(magic)) {
+        return Err(InvalidContent::Format(format));
+    }
+
+    // Instructions and sprites have many bytes outside of printable ASCII, while text
+    // has almost none (a UTF-8 byte order mark also gives text away)
+    let printable = data.iter().filter(|&&b| matches!(b, 0x20..=0x7E | b'\t' | b'\n' | b'\r')).count();
+    let is_text = data.len() >= MIN_TEXT_SIZE && data.contains(&b'\n') && printable * 100 >= data.len() * 95;
+    if is_text || data.starts_with(b"\xEF\xBB\xBF") {
+        return Err(InvalidContent::Text);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_content() {
+        let roms = concat!(env!("CARGO_MANIFEST_DIR"), "/roms");
+        for entry in std::fs::read_dir(roms).unwrap() {
+            let path = entry.unwrap().path();
+            assert_eq!(validate(&std::fs::read(&path).unwrap()), Ok(()), "{}", path.display());
+        }
+
+        assert_eq!(validate(&[]), Err(InvalidContent::Empty));
+        assert_eq!(validate(b"\x89PNG\r\n\x1A\n\0\0\0\x0DIHDR"), Err(InvalidContent::Format("PNG image")));
+        assert_eq!(validate(b"; Pong in Octo\n: main\n  clear\n  loop again\n"), Err(InvalidContent::Text));
+
+        // Short programs may be printable by chance
+        assert_eq!(validate(b"a\nb\n"), Ok(()));
+    }
+}
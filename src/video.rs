@@ -0,0 +1,293 @@
+/// Clockwise rotation of the video output, for devices held in portrait orientation.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Rotation {
+    #[default]
+    None,
+    Clockwise90,
+    Clockwise180,
+    Clockwise270,
+}
+
+impl Rotation {
+    pub fn from_degrees(degrees: u32) -> Option<Self> {
+        match degrees {
+            0 => Some(Self::None),
+            90 => Some(Self::Clockwise90),
+            180 => Some(Self::Clockwise180),
+            270 => Some(Self::Clockwise270),
+            _ => None,
+        }
+    }
+
+    pub fn degrees(&self) -> u32 {
+        match self {
+            Self::None => 0,
+            Self::Clockwise90 => 90,
+            Self::Clockwise180 => 180,
+            Self::Clockwise270 => 270,
+        }
+    }
+
+    /// Whether the output is turned sideways, swapping its width and height.
+    pub fn is_sideways(&self) -> bool {
+        matches!(self, Self::Clockwise90 | Self::Clockwise270)
+    }
+
+    /// Position in the rotated output of pixel `(x, y)` of a `width` by `height` frame.
+    fn apply(&self, x: usize, y: usize, width: usize, height: usize) -> (usize, usize) {
+        match self {
+            Self::None => (x, y),
+            Self::Clockwise90 => (height - 1 - y, x),
+            Self::Clockwise180 => (width - 1 - x, height - 1 - y),
+            Self::Clockwise270 => (y, width - 1 - x),
+        }
+    }
+}
+
+/// Shape of the displayed image, reported to the frontend, which stretches the video
+/// output to match.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AspectRatio {
+    /// Square pixels, so the display is 2:1 like the frame buffer.
+    #[default]
+    Square,
+    /// Pixels twice as tall as they are wide, as low-resolution programs appear on the
+    /// COSMAC VIP (a 1:1 display).
+    Stretch,
+    /// A 4:3 television screen.
+    Tv,
+    /// Display ratio of width to height.
+    Custom(u16, u16),
+}
+
+impl AspectRatio {
+    /// Parse `square`, `stretch`, `tv` or a custom ratio such as `16:9`.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "square" => Some(Self::Square),
+            "stretch" => Some(Self::Stretch),
+            "tv" | "4:3" => Some(Self::Tv),
+            _ => {
+                let (width, height) = value.split_once(':')?;
+                let (width, height) = (width.parse().ok()?, height.parse().ok()?);
+                (width > 0 && height > 0).then_some(Self::Custom(width, height))
+            },
+        }
+    }
+
+    /// Display ratio of width to height for a frame of the given size.
+    pub fn ratio(&self, width: usize, height: usize) -> f32 {
+        match self {
+            Self::Square => width as f32 / height as f32,
+            Self::Stretch => width as f32 / (2 * height) as f32,
+            Self::Tv => 4.0 / 3.0,
+            Self::Custom(width, height) => *width as f32 / *height as f32,
+        }
+    }
+}
+
+/// Integer upscaling applied when converting the frame buffer into a video frame, for
+/// frontends that cannot scale the output themselves.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VideoFilter {
+    /// Integer scaling factor (nearest neighbour), from 1 to `MAX_SCALE`.
+    pub scale: usize,
+    /// Darken the last row of each scaled pixel row. Has no effect without upscaling.
+    pub scanlines: bool,
+    /// Rotation applied after scaling. Scanlines stay horizontal relative to the program.
+    pub rotation: Rotation,
+    pub aspect: AspectRatio,
+}
+
+impl VideoFilter {
+    pub const MAX_SCALE: usize = 3;
+
+    /// Dimensions of the output frame for a frame buffer of the given size.
+    pub fn output_size(&self, width: usize, height: usize) -> (usize, usize) {
+        let (width, height) = (width * self.scale, height * self.scale);
+        if self.rotation.is_sideways() { (height, width) } else { (width, height) }
+    }
+
+    /// Display ratio of width to height of the output for a frame buffer of the given
+    /// size, after rotation.
+    pub fn aspect_ratio(&self, width: usize, height: usize) -> f32 {
+        let ratio = self.aspect.ratio(width, height);
+        if self.rotation.is_sideways() { 1.0 / ratio } else { ratio }
+    }
+}
+
+impl Default for VideoFilter {
+    fn default() -> Self {
+        Self { scale: 1, scanlines: false, rotation: Rotation::None, aspect: AspectRatio::Square }
+    }
+}
+
+/// Halve the intensity of each component of an RGB565 color.
+fn darken(color: u16) -> u16 {
+    (color >> 1) & 0x7BEF
+}
+
+/// Whether pixel `x` of a packed frame buffer row is set. Rows store pixels from the
+/// most significant bit, so column 0 is bit 127.
+pub fn pixel(row: u128, x: usize) -> bool {
+    row & (1 << (127 - x)) != 0
+}
+
+/// Convert a frame buffer of packed rows `width` pixels wide into an RGB565 video frame
+/// (little endian), applying the filter. The output buffer is resized to fit the frame.
+pub fn render(rows: &[u128], width: usize, on: u16, off: u16, filter: VideoFilter, out: &mut Vec<u8>) {
+    let scale = filter.scale.clamp(1, VideoFilter::MAX_SCALE);
+    let (out_width, out_height) = (width * scale, rows.len() * scale);
+    let pitch = if filter.rotation.is_sideways() { out_height } else { out_width };
+
+    out.resize(2 * out_width * out_height, 0);
+
+    for (y, &row) in rows.iter().enumerate() {
+        for offset_y in 0..scale {
+            let scanline = filter.scanlines && scale > 1 && offset_y == scale - 1;
+            let out_y = y * scale + offset_y;
+
+            for x in 0..width {
+                let mut color = if pixel(row, x) { on } else { off };
+                if scanline {
+                    color = darken(color);
+                }
+
+                for offset_x in 0..scale {
+                    let (rx, ry) = filter.rotation.apply(x * scale + offset_x, out_y, out_width, out_height);
+                    let i = 2 * (ry * pitch + rx);
+                    out[i..=i + 1].copy_from_slice(&color.to_le_bytes());
+                }
+            }
+        }
+    }
+}
+
+/// 64-bit FNV-1a hash of a frame buffer, with pixels packed 8 per byte row by row.
+/// The hash is stable across platforms and versions, so it can be stored in tests.
+pub fn hash(rows: &[u128], width: usize) -> u64 {
+    rows.iter().fold(FNV_OFFSET_BASIS, |hash, row| fnv1a(hash, &row.to_be_bytes()[..width.div_ceil(8)]))
+}
+
+pub(crate) const FNV_OFFSET_BASIS: u64 = 0xCBF2_9CE4_8422_2325;
+
+/// Continue a 64-bit FNV-1a hash, starting from `FNV_OFFSET_BASIS`, with more data.
+pub(crate) fn fnv1a(hash: u64, data: &[u8]) -> u64 {
+    const PRIME: u64 = 0x0000_0100_0000_01B3;
+
+    data.iter().fold(hash, |hash, &byte| (hash ^ byte as u64).wrapping_mul(PRIME))
+}
+
+/// Character set used to render a frame buffer as text.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextStyle {
+    /// One character per pixel: `#` if set, `.` otherwise.
+    Ascii,
+    /// One Unicode Braille pattern per block of 2x4 pixels, for a compact rendering.
+    Braille,
+}
+
+/// Render a frame buffer as text, one line per row of characters, for logs and test
+/// failure messages.
+pub fn to_text(rows: &[u128], width: usize, style: TextStyle) -> String {
+    let is_set = |x: usize, y: usize| rows.get(y).is_some_and(|&r| pixel(r, x));
+    let mut text = String::new();
+
+    match style {
+        TextStyle::Ascii => {
+            for &row in rows {
+                text.extend((0..width).map(|x| if pixel(row, x) { '#' } else { '.' }));
+                text.push('\n');
+            }
+        },
+        TextStyle::Braille => {
+            // Dot numbering of the Braille patterns (bit index of each pixel in a block)
+            const DOTS: [[u32; 2]; 4] = [[0, 3], [1, 4], [2, 5], [6, 7]];
+
+            for y in (0..rows.len()).step_by(4) {
+                for x in (0..width).step_by(2) {
+                    let mut bits = 0;
+                    for (dy, dots) in DOTS.iter().enumerate() {
+                        for (dx, &dot) in dots.iter().enumerate() {
+                            if x + dx < width && is_set(x + dx, y + dy) {
+                                bits |= 1 << dot;
+                            }
+                        }
+                    }
+                    text.push(char::from_u32(0x2800 + bits).unwrap());
+                }
+                text.push('\n');
+            }
+        },
+    }
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn upscale_scanlines() {
+        let rows = [1 << 127];
+        let mut out = Vec::new();
+
+        render(&rows, 2, 0xFFFF, 0x0000, VideoFilter { scale: 2, scanlines: true, ..VideoFilter::default() }, &mut out);
+
+        let pixels: Vec<u16> = out.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).collect();
+        assert_eq!(pixels, [0xFFFF, 0xFFFF, 0x0000, 0x0000, 0x7BEF, 0x7BEF, 0x0000, 0x0000]);
+    }
+
+    #[test]
+    fn rotation() {
+        // Top left pixel set in a 2x1 frame buffer
+        let rows = [1 << 127];
+        let mut out = Vec::new();
+        let pixels = |out: &[u8]| out.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).collect::<Vec<_>>();
+
+        let filter = |degrees| VideoFilter { rotation: Rotation::from_degrees(degrees).unwrap(), ..VideoFilter::default() };
+        assert_eq!(filter(90).output_size(2, 1), (1, 2));
+        assert_eq!(filter(180).output_size(2, 1), (2, 1));
+
+        render(&rows, 2, 1, 0, filter(90), &mut out);
+        assert_eq!(pixels(&out), [1, 0]);
+        render(&rows, 2, 1, 0, filter(180), &mut out);
+        assert_eq!(pixels(&out), [0, 1]);
+        render(&rows, 2, 1, 0, filter(270), &mut out);
+        assert_eq!(pixels(&out), [0, 1]);
+        assert_eq!(Rotation::from_degrees(45), None);
+    }
+
+    #[test]
+    fn aspect_ratio() {
+        assert_eq!(AspectRatio::parse("stretch"), Some(AspectRatio::Stretch));
+        assert_eq!(AspectRatio::parse("16:9"), Some(AspectRatio::Custom(16, 9)));
+        assert_eq!(AspectRatio::parse("16:0"), None);
+        assert_eq!(AspectRatio::parse("wide"), None);
+
+        let filter = VideoFilter { aspect: AspectRatio::Stretch, ..VideoFilter::default() };
+        assert_eq!(filter.aspect_ratio(128, 64), 1.0);
+        assert_eq!(VideoFilter::default().aspect_ratio(128, 64), 2.0);
+
+        let filter = VideoFilter { aspect: AspectRatio::Custom(3, 2), rotation: Rotation::Clockwise90, ..VideoFilter::default() };
+        assert_eq!(filter.aspect_ratio(128, 64), 2.0 / 3.0);
+    }
+
+    #[test]
+    fn frame_hash() {
+        let a = [1 << 127, 0];
+        let b = [1 << 126, 0];
+
+        assert_ne!(hash(&a, 2), hash(&b, 2));
+        assert_eq!(hash(&a, 2), hash(&[1 << 127, 0], 2));
+    }
+
+    #[test]
+    fn text() {
+        let rows = [0b101 << 125, 0b010 << 125];
+
+        assert_eq!(to_text(&rows, 3, TextStyle::Ascii), "#.#\n.#.\n");
+        assert_eq!(to_text(&rows, 3, TextStyle::Braille), "\u{2811}\u{2801}\n");
+    }
+}
@@ -0,0 +1,57 @@
+//! Checks that running frames does not allocate once the core has warmed up, so that
+//! the run loop stays cheap at high speed settings. Heap allocations are counted by a
+//! global allocator, which is why this test has its own binary.
+
+use std::{alloc::{GlobalAlloc, Layout, System}, fs, sync::atomic::{AtomicUsize, Ordering}};
+
+use oxid_8::Chip8Core;
+use oxid_8::input::KeypadState;
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Frames run before counting, during which buffers reach their final size.
+const WARMUP_FRAMES: u32 = 10;
+const FRAMES: u32 = 600;
+
+#[test]
+fn run_frame_does_not_allocate() {
+    for rom in ["ibm.ch8", "heart_monitor.ch8", "test_opcode.ch8"] {
+        let data = fs::read(format!("{}/roms/{}", env!("CARGO_MANIFEST_DIR"), rom)).unwrap();
+
+        let mut core = Chip8Core::new();
+        core.load_program(&data);
+
+        for _ in 0..WARMUP_FRAMES {
+            core.run_frame(KeypadState::default());
+        }
+
+        let before = ALLOCATIONS.load(Ordering::Relaxed);
+        for _ in 0..FRAMES {
+            core.run_frame(KeypadState::default());
+        }
+        let allocations = ALLOCATIONS.load(Ordering::Relaxed) - before;
+
+        assert_eq!(allocations, 0, "{}: {} allocations in {} frames", rom, allocations, FRAMES);
+    }
+}
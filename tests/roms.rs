@@ -0,0 +1,75 @@
+//! Regression tests running the programs in `roms/` without a frontend and comparing the
+//! resulting screen against a known hash. Cases run in parallel, each on its own core.
+
+use std::{fs, thread, sync::atomic::{AtomicUsize, Ordering}};
+
+use oxid_8::Chip8Core;
+use oxid_8::input::KeypadState;
+use oxid_8::video::TextStyle;
+
+struct RomCase {
+    rom: &'static str,
+    frames: u32,
+    hash: u64,
+}
+
+const CASES: &[RomCase] = &[
+    RomCase { rom: "ibm.ch8", frames: 60, hash: 0xE58F_C86E_2D15_B8B1 },
+    RomCase { rom: "heart_monitor.ch8", frames: 120, hash: 0x92BC_5A25_45FB_56B5 },
+    RomCase { rom: "test_opcode.ch8", frames: 60, hash: 0x4A6B_7B06_12C6_C835 },
+];
+
+/// Run a case, returning a description of the failure (including the screen) if the
+/// hash does not match.
+fn run_case(case: &RomCase) -> Result<(), String> {
+    let data = fs::read(format!("{}/roms/{}", env!("CARGO_MANIFEST_DIR"), case.rom))
+        .map_err(|e| format!("{}: {}", case.rom, e))?;
+
+    let mut core = Chip8Core::new();
+    core.set_seed(0);
+    core.load_program(&data);
+    core.soft_reset();
+
+    for _ in 0..case.frames {
+        core.run_frame(KeypadState::default());
+    }
+
+    let hash = core.framebuffer_hash();
+    if hash == case.hash {
+        Ok(())
+    }
+    else {
+        Err(format!("{}: expected hash {:016X}, got {:016X}\n{}", case.rom, case.hash, hash, core.screen_text(TextStyle::Ascii)))
+    }
+}
+
+/// Cores must not share state, so that cases can run on separate threads.
+#[test]
+fn core_is_send() {
+    fn assert_send<T: Send>() {}
+    assert_send::<Chip8Core>();
+}
+
+#[test]
+fn roms() {
+    let next = AtomicUsize::new(0);
+    let workers = thread::available_parallelism().map_or(1, |n| n.get()).min(CASES.len());
+
+    let failures: Vec<String> = thread::scope(|s| {
+        let handles: Vec<_> = (0..workers)
+            .map(|_| s.spawn(|| {
+                let mut failures = Vec::new();
+                while let Some(case) = CASES.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    if let Err(failure) = run_case(case) {
+                        failures.push(failure);
+                    }
+                }
+                failures
+            }))
+            .collect();
+
+        handles.into_iter().flat_map(|h| h.join().unwrap()).collect()
+    });
+
+    assert!(failures.is_empty(), "{} of {} ROM cases failed:\n{}", failures.len(), CASES.len(), failures.join("\n"));
+}